@@ -1,4 +1,24 @@
+//
+// The demo as a desktop app
+//
+// Usage: desktop [--record FILE | --replay FILE]
+//
+
 #[cfg(not(target_family = "wasm"))]
 pub fn main() -> Result<(), Box<dyn std::error::Error>> {
-    wasm_winit_wgpu::main()
+    use wasm_winit_wgpu::{AppConfig, Session, SessionMode};
+
+    let mut config = AppConfig::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let path = args
+            .next()
+            .ok_or_else(|| format!("missing file name for '{flag}'"))?;
+        config.session = match flag.as_str() {
+            "--record" => SessionMode::Record { path: path.into() },
+            "--replay" => SessionMode::Replay(Session::load(path.as_ref())?),
+            _ => return Err(format!("unknown argument '{flag}'").into()),
+        };
+    }
+    Ok(wasm_winit_wgpu::not_wasm::desktop_main(config)?)
 }
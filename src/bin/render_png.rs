@@ -0,0 +1,76 @@
+//
+// Renders the demo UI without a window and writes every frame as a PNG
+//
+// Usage: render_png [--frames N] [--size WIDTHxHEIGHT] [--scale FACTOR] [--out DIR]
+//
+
+#[cfg(not(target_family = "wasm"))]
+struct Args {
+    frames: u32,
+    size: winit::dpi::PhysicalSize<u32>,
+    scale: f32,
+    out: std::path::PathBuf,
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl Args {
+    fn parse() -> Result<Self, String> {
+        let mut args = Args {
+            frames: 1,
+            size: winit::dpi::PhysicalSize::new(800, 600),
+            scale: 1.0,
+            out: std::path::PathBuf::from("."),
+        };
+        let mut iter = std::env::args().skip(1);
+        while let Some(flag) = iter.next() {
+            let value = iter
+                .next()
+                .ok_or_else(|| format!("missing value for '{flag}'"))?;
+            let invalid = || format!("invalid value for '{flag}': '{value}'");
+            match flag.as_str() {
+                "--frames" => args.frames = value.parse().map_err(|_| invalid())?,
+                "--size" => {
+                    let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+                    args.size = winit::dpi::PhysicalSize::new(
+                        width.parse().map_err(|_| invalid())?,
+                        height.parse().map_err(|_| invalid())?,
+                    );
+                }
+                "--scale" => args.scale = value.parse().map_err(|_| invalid())?,
+                "--out" => args.out = value.into(),
+                _ => return Err(format!("unknown argument '{flag}'")),
+            }
+        }
+        if args.size.width == 0 || args.size.height == 0 || args.scale <= 0.0 {
+            return Err("size and scale must be positive".to_string());
+        }
+        Ok(args)
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+pub fn main() -> Result<(), Box<dyn std::error::Error>> {
+    use wasm_winit_wgpu::{headless, GpuConfig, GpuState};
+
+    env_logger::init();
+    let args = Args::parse().map_err(|err| {
+        format!("{err}\nusage: render_png [--frames N] [--size WIDTHxHEIGHT] [--scale FACTOR] [--out DIR]")
+    })?;
+    std::fs::create_dir_all(&args.out)?;
+
+    let gpu = GpuState::headless(&GpuConfig::default())?;
+    let mut app = headless::HeadlessApp::new(
+        gpu,
+        wasm_winit_wgpu::demo::DemoApp::new(),
+        args.size,
+        args.scale,
+    );
+    for frame in 0..args.frames {
+        let raw_input = app.raw_input();
+        app.render_frame(raw_input);
+        let path = args.out.join(format!("frame_{frame:04}.png"));
+        headless::write_png(&path, args.size, &app.read_rgba()?)?;
+        log::info!("wrote {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,388 @@
+//
+// Checksums and hashes of files, and the lists of expected ones that come
+// with downloads (`SHA256SUMS`, `app.zip.sha256`...)
+//
+// `Hasher` is fed the bytes piece by piece, eg. the chunks of a file as they
+// are read. `ChecksumTask` hashes bytes that are already in memory without
+// blocking the event loop: natively on a background thread, on the web a chunk
+// at a time between frames. It can also be fed the chunks of a file that is
+// too large to keep, as they arrive.
+//
+
+use crate::*;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// The checksums and hashes [`Hasher`] can compute.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    Crc32,
+    Adler32,
+    Md5,
+    Sha1,
+    Sha256,
+    Blake3,
+}
+impl HashAlgorithm {
+    pub const ALL: [Self; 6] = [
+        Self::Crc32,
+        Self::Adler32,
+        Self::Md5,
+        Self::Sha1,
+        Self::Sha256,
+        Self::Blake3,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Crc32 => "CRC32",
+            Self::Adler32 => "Adler-32",
+            Self::Md5 => "MD5",
+            Self::Sha1 => "SHA-1",
+            Self::Sha256 => "SHA-256",
+            Self::Blake3 => "BLAKE3",
+        }
+    }
+
+    /// Length of the checksum in hex digits.
+    pub fn hex_len(self) -> usize {
+        match self {
+            Self::Crc32 | Self::Adler32 => 8,
+            Self::Md5 => 32,
+            Self::Sha1 => 40,
+            Self::Sha256 | Self::Blake3 => 64,
+        }
+    }
+
+    /// The algorithm a checksum list is for, judged by how such lists are
+    /// usually named (`SHA256SUMS`, `app.zip.sha256`, `B3SUMS`...) or tagged
+    /// (`SHA256 (app.zip) = ...`).
+    fn from_tag(tag: &str) -> Option<Self> {
+        let tag = tag.to_ascii_lowercase().replace('-', "");
+        let tag = tag.strip_suffix("sums").unwrap_or(&tag);
+        let tag = tag.strip_suffix("sum").unwrap_or(tag);
+        match tag {
+            "crc32" | "crc" => Some(Self::Crc32),
+            "adler32" => Some(Self::Adler32),
+            "md5" => Some(Self::Md5),
+            "sha1" => Some(Self::Sha1),
+            "sha256" => Some(Self::Sha256),
+            "b3" | "blake3" => Some(Self::Blake3),
+            _ => None,
+        }
+    }
+}
+impl std::fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A computed checksum, as lower case hex.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checksum {
+    pub algorithm: HashAlgorithm,
+    pub hex: String,
+}
+
+enum State {
+    Crc32(crc32fast::Hasher),
+    Adler32(adler2::Adler32),
+    Md5(md5::Md5),
+    Sha1(sha1::Sha1),
+    Sha256(sha2::Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+/// Computes several checksums of the same bytes in one pass.
+pub struct Hasher(Vec<State>);
+impl Hasher {
+    pub fn new(algorithms: &[HashAlgorithm]) -> Self {
+        use sha2::Digest;
+        Self(
+            algorithms
+                .iter()
+                .map(|algorithm| match algorithm {
+                    HashAlgorithm::Crc32 => State::Crc32(crc32fast::Hasher::new()),
+                    HashAlgorithm::Adler32 => State::Adler32(adler2::Adler32::new()),
+                    HashAlgorithm::Md5 => State::Md5(md5::Md5::new()),
+                    HashAlgorithm::Sha1 => State::Sha1(sha1::Sha1::new()),
+                    HashAlgorithm::Sha256 => State::Sha256(sha2::Sha256::new()),
+                    HashAlgorithm::Blake3 => State::Blake3(Box::default()),
+                })
+                .collect(),
+        )
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        use sha2::Digest;
+        for state in &mut self.0 {
+            match state {
+                State::Crc32(hasher) => hasher.update(bytes),
+                State::Adler32(hasher) => hasher.write_slice(bytes),
+                State::Md5(hasher) => hasher.update(bytes),
+                State::Sha1(hasher) => hasher.update(bytes),
+                State::Sha256(hasher) => hasher.update(bytes),
+                State::Blake3(hasher) => {
+                    hasher.update(bytes);
+                }
+            }
+        }
+    }
+
+    /// In the order the algorithms were passed to [`Hasher::new`].
+    pub fn finish(self) -> Vec<Checksum> {
+        use sha2::Digest;
+        let hex = |bytes: &[u8]| bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+        self.0
+            .into_iter()
+            .map(|state| match state {
+                State::Crc32(hasher) => Checksum {
+                    algorithm: HashAlgorithm::Crc32,
+                    hex: format!("{:08x}", hasher.finalize()),
+                },
+                State::Adler32(hasher) => Checksum {
+                    algorithm: HashAlgorithm::Adler32,
+                    hex: format!("{:08x}", hasher.checksum()),
+                },
+                State::Md5(hasher) => Checksum {
+                    algorithm: HashAlgorithm::Md5,
+                    hex: hex(&hasher.finalize()),
+                },
+                State::Sha1(hasher) => Checksum {
+                    algorithm: HashAlgorithm::Sha1,
+                    hex: hex(&hasher.finalize()),
+                },
+                State::Sha256(hasher) => Checksum {
+                    algorithm: HashAlgorithm::Sha256,
+                    hex: hex(&hasher.finalize()),
+                },
+                State::Blake3(hasher) => Checksum {
+                    algorithm: HashAlgorithm::Blake3,
+                    hex: hasher.finalize().to_hex().to_string(),
+                },
+            })
+            .collect()
+    }
+}
+
+/// Shared between a [`ChecksumTask`] and whatever does the hashing.
+#[derive(Default)]
+struct TaskState {
+    cancelled: AtomicBool,
+    hashed: AtomicU64,
+    checksums: Mutex<Option<Vec<Checksum>>>,
+    /// Why there will be no checksums.
+    error: Mutex<Option<String>>,
+}
+
+/// Hashes bytes in the background; poll it for the result. Dropping it stops
+/// the hashing.
+pub struct ChecksumTask {
+    len: u64,
+    state: Arc<TaskState>,
+    /// For tasks that are fed the bytes, see [`ChecksumTask::fed`].
+    hasher: Option<Hasher>,
+}
+impl ChecksumTask {
+    pub fn spawn(bytes: Arc<[u8]>, algorithms: &[HashAlgorithm]) -> Self {
+        let state = Arc::new(TaskState::default());
+        let task = Self {
+            len: bytes.len() as u64,
+            state: state.clone(),
+            hasher: None,
+        };
+        let hasher = Hasher::new(algorithms);
+        #[cfg(not(target_family = "wasm"))]
+        {
+            let spawned = std::thread::Builder::new()
+                .name("checksums".to_string())
+                .spawn(move || Self::hash(bytes, hasher, &state));
+            if let Err(err) = spawned {
+                log::error!("cannot start a checksum thread: {err}");
+                *task.state.error.lock().unwrap() = Some(err.to_string());
+            }
+        }
+        #[cfg(target_family = "wasm")]
+        wasm_bindgen_futures::spawn_local(async move {
+            let mut hasher = hasher;
+            for chunk in bytes.chunks(FILE_CHUNK_SIZE) {
+                if state.cancelled.load(Ordering::Acquire) {
+                    return;
+                }
+                hasher.update(chunk);
+                state.hashed.fetch_add(chunk.len() as u64, Ordering::AcqRel);
+                // Lets the browser draw a frame in between.
+                file_read::sleep(0).await;
+            }
+            *state.checksums.lock().unwrap() = Some(hasher.finish());
+        });
+        task
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn hash(bytes: Arc<[u8]>, mut hasher: Hasher, state: &TaskState) {
+        for chunk in bytes.chunks(FILE_CHUNK_SIZE) {
+            if state.cancelled.load(Ordering::Acquire) {
+                return;
+            }
+            hasher.update(chunk);
+            state.hashed.fetch_add(chunk.len() as u64, Ordering::AcqRel);
+        }
+        *state.checksums.lock().unwrap() = Some(hasher.finish());
+    }
+
+    /// A task that is handed `len` bytes piece by piece, with
+    /// [`ChecksumTask::update`] and then [`ChecksumTask::finish`].
+    pub fn fed(len: u64, algorithms: &[HashAlgorithm]) -> Self {
+        Self {
+            len,
+            state: Default::default(),
+            hasher: Some(Hasher::new(algorithms)),
+        }
+    }
+    /// Hashes `bytes` right away; does nothing for spawned tasks.
+    pub fn update(&mut self, bytes: &[u8]) {
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(bytes);
+            let hashed = bytes.len() as u64;
+            self.state.hashed.fetch_add(hashed, Ordering::AcqRel);
+        }
+    }
+    /// Computes the checksums of a fed task from what it was handed.
+    pub fn finish(&mut self) {
+        if let Some(hasher) = self.hasher.take() {
+            *self.state.checksums.lock().unwrap() = Some(hasher.finish());
+        }
+    }
+    /// Gives up on a fed task, eg. because its file could not be read.
+    pub fn fail(&mut self, error: String) {
+        if self.hasher.take().is_some() {
+            *self.state.error.lock().unwrap() = Some(error);
+        }
+    }
+
+    /// From 0 to 1.
+    pub fn progress(&self) -> f32 {
+        match self.len {
+            0 => 1.0,
+            len => self.state.hashed.load(Ordering::Acquire) as f32 / len as f32,
+        }
+    }
+
+    /// `None` until all bytes have been hashed.
+    pub fn checksums(&self) -> Option<Vec<Checksum>> {
+        self.state.checksums.lock().unwrap().clone()
+    }
+    /// Why the checksums could not be computed, eg. no thread could be
+    /// started for it.
+    pub fn error(&self) -> Option<String> {
+        self.state.error.lock().unwrap().clone()
+    }
+    /// Whether there are checksums, or an error.
+    pub fn is_done(&self) -> bool {
+        self.state.checksums.lock().unwrap().is_some() || self.error().is_some()
+    }
+}
+impl Drop for ChecksumTask {
+    fn drop(&mut self) {
+        self.state.cancelled.store(true, Ordering::Release);
+    }
+}
+
+/// A checksum someone expects a file to have: pasted, or a line of a
+/// checksum list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpectedChecksum {
+    /// The file it is for, if known; without directories.
+    pub name: Option<String>,
+    /// If known; otherwise anything of the same length may match.
+    pub algorithm: Option<HashAlgorithm>,
+    /// Lower case.
+    pub hex: String,
+}
+impl ExpectedChecksum {
+    /// A pasted hash; `None` if it is not hex of a known length.
+    pub fn parse(text: &str) -> Option<Self> {
+        let hex = text.trim().to_ascii_lowercase();
+        let is_known_len = HashAlgorithm::ALL
+            .iter()
+            .any(|algorithm| algorithm.hex_len() == hex.len());
+        (is_known_len && hex.chars().all(|c| c.is_ascii_hexdigit())).then_some(Self {
+            name: None,
+            algorithm: None,
+            hex,
+        })
+    }
+
+    pub fn matches(&self, checksum: &Checksum) -> bool {
+        self.algorithm.map_or(
+            self.hex.len() == checksum.algorithm.hex_len(),
+            |algorithm| algorithm == checksum.algorithm,
+        ) && self.hex == checksum.hex
+    }
+
+    /// Whether this is about the file called `name`.
+    pub fn is_for(&self, name: &str) -> bool {
+        self.name.as_deref() == Some(name)
+    }
+}
+
+/// Whether a file called `name` looks like a checksum list, eg. `SHA256SUMS`
+/// or `app.zip.sha256`.
+pub fn is_checksum_list(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    let extension = name.rsplit_once('.').map(|(_, extension)| extension);
+    upper.ends_with("SUMS") || extension.is_some_and(|tag| HashAlgorithm::from_tag(tag).is_some())
+}
+
+/// The checksums in a checksum list called `list_name`. Understands the
+/// lines `sha256sum` and friends write (`<hex>  <name>`, with `*` before the
+/// name for binary mode), BSD style tagged lines (`SHA256 (<name>) = <hex>`)
+/// and bare hashes; anything else is skipped.
+pub fn parse_checksum_list(list_name: &str, text: &str) -> Vec<ExpectedChecksum> {
+    let list_algorithm = match list_name.rsplit_once('.') {
+        Some((_, extension)) => HashAlgorithm::from_tag(extension),
+        None => HashAlgorithm::from_tag(list_name),
+    };
+    // `app.zip.sha256` is about `app.zip`, even if it holds just the hash.
+    let list_target = list_name
+        .rsplit_once('.')
+        .filter(|(_, extension)| HashAlgorithm::from_tag(extension).is_some())
+        .map(|(target, _)| target.to_string());
+    let base_name = |path: &str| {
+        let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+        name.to_string()
+    };
+    text.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            // BSD style (`SHA256 (app.zip) = ...`) only after a known tag, so
+            // GNU style names may have ` (` in them: `...  app (1).zip`.
+            let bsd = line.split_once(" (").and_then(|(tag, rest)| {
+                let algorithm =
+                    HashAlgorithm::from_tag(tag).filter(|_| !tag.contains(char::is_whitespace))?;
+                Some((algorithm, rest.rsplit_once(") = ")?))
+            });
+            let (algorithm, name, hex) = match bsd {
+                Some((algorithm, (name, hex))) => (Some(algorithm), Some(base_name(name)), hex),
+                None => match line.split_once(char::is_whitespace) {
+                    Some((hex, name)) => {
+                        let name = name.trim_start().trim_start_matches('*');
+                        (list_algorithm, Some(base_name(name)), hex)
+                    }
+                    None => (list_algorithm, list_target.clone(), line),
+                },
+            };
+            let expected = ExpectedChecksum::parse(hex)?;
+            Some(ExpectedChecksum {
+                name,
+                algorithm: algorithm.or(expected.algorithm),
+                ..expected
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,131 @@
+//
+// Clocks: where the app gets its notion of "now" from
+//
+
+use std::time::Duration;
+use web_time::Instant;
+
+/// The time source of an app; animations read the time from here instead of
+/// the wall clock, so they can be paused, scrubbed and made deterministic.
+pub trait Clock {
+    /// Time elapsed since the clock was started.
+    fn now(&self) -> Duration;
+    /// Called once after every rendered frame.
+    fn tick(&mut self) {}
+}
+
+/// Monotonic real time; unaffected by changes of the system clock.
+pub struct RealClock {
+    start: Instant,
+}
+impl RealClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+impl Default for RealClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Clock for RealClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Advances by a fixed step per frame, regardless of how long frames take;
+/// for tests and offline rendering.
+pub struct FixedStepClock {
+    step: Duration,
+    frames: u32,
+}
+impl FixedStepClock {
+    pub fn new(step: Duration) -> Self {
+        Self { step, frames: 0 }
+    }
+    /// Steps of `1 / fps` seconds.
+    pub fn from_fps(fps: u32) -> Self {
+        Self::new(Duration::from_secs(1) / fps.max(1))
+    }
+}
+impl Clock for FixedStepClock {
+    fn now(&self) -> Duration {
+        self.step * self.frames
+    }
+    fn tick(&mut self) {
+        self.frames += 1;
+    }
+}
+
+/// Wraps another clock so time can be stopped, resumed and moved around.
+pub struct PausableClock<C: Clock = RealClock> {
+    source: C,
+    /// This clock's time at `source_base` on the source clock.
+    base: Duration,
+    source_base: Duration,
+    paused_at: Option<Duration>,
+}
+impl<C: Clock> PausableClock<C> {
+    pub fn new(source: C) -> Self {
+        let source_base = source.now();
+        Self {
+            source,
+            base: Duration::ZERO,
+            source_base,
+            paused_at: None,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    pub fn pause(&mut self) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(self.now());
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.seek(paused_at);
+        }
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused {
+            self.pause();
+        } else {
+            self.resume();
+        }
+    }
+
+    /// Jumps to `time`; a paused clock stays paused there.
+    pub fn seek(&mut self, time: Duration) {
+        if self.paused_at.is_some() {
+            self.paused_at = Some(time);
+        } else {
+            self.base = time;
+            self.source_base = self.source.now();
+        }
+    }
+}
+impl Default for PausableClock {
+    fn default() -> Self {
+        Self::new(RealClock::new())
+    }
+}
+impl<C: Clock> Clock for PausableClock<C> {
+    fn now(&self) -> Duration {
+        match self.paused_at {
+            Some(paused_at) => paused_at,
+            None => self.base + self.source.now().saturating_sub(self.source_base),
+        }
+    }
+    fn tick(&mut self) {
+        self.source.tick();
+    }
+}
@@ -0,0 +1,427 @@
+//
+// The demo app: an example implementation of `AppLogic`
+//
+
+use crate::*;
+use std::collections::BTreeMap;
+
+mod background;
+mod file_manager;
+mod hex_view;
+mod image_view;
+mod integrity;
+
+pub use background::{BackgroundShader, ShaderInputs, EXAMPLE_SHADER};
+pub use file_manager::{format_size, FileManager, SortKey};
+pub use hex_view::{interpret, parse_pattern, HexViewer, SearchMode};
+pub use image_view::{is_viewable_image, Channel, ImageDecoding, ImageViewer};
+pub use integrity::IntegrityPanel;
+
+pub struct UiState {
+    num_clicks: usize,
+    checked: bool,
+    num_checks: usize,
+    file_manager: FileManager,
+    integrity: IntegrityPanel,
+    /// The ids of the checksums of the files in the file manager, by their
+    /// keys there.
+    checksum_ids: BTreeMap<u64, u64>,
+    /// Like the image viewer, with the key of the file it shows.
+    hex_viewer: Option<(u64, HexViewer)>,
+    image_viewer: Option<(u64, ImageViewer)>,
+    /// The image to show next, while it is decoded.
+    decoding_image: Option<(u64, ImageDecoding)>,
+    /// Closed (or replaced) viewers whose textures are still to be freed.
+    closed_image_viewers: Vec<ImageViewer>,
+    /// The last image dropped, to open with the next frame.
+    dropped_image: Option<(u64, DroppedFile)>,
+    /// Why the last image could not be opened.
+    image_error: Option<String>,
+    background: BackgroundShader,
+    software_rendering: bool,
+    animate_background: bool,
+}
+impl UiState {
+    pub fn new() -> Self {
+        Self {
+            num_clicks: 0,
+            checked: false,
+            num_checks: 0,
+            file_manager: FileManager::new(),
+            integrity: IntegrityPanel::new(),
+            checksum_ids: BTreeMap::new(),
+            hex_viewer: None,
+            image_viewer: None,
+            decoding_image: None,
+            closed_image_viewers: Vec::new(),
+            dropped_image: None,
+            image_error: None,
+            background: BackgroundShader::new(),
+            software_rendering: false,
+            animate_background: true,
+        }
+    }
+    pub fn run_egui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Test egui window")
+            .resizable([true, true])
+            .show(ctx, |ui| {
+                if self.software_rendering {
+                    ui.label("(running on a software rasterizer)");
+                }
+                let button_text = match self.num_clicks {
+                    0 => "I dare you! I double-dare you!".to_string(),
+                    1 => "Oo-ooh! Now you've done it!".to_string(),
+                    2 => "Oo-ooh! Now you've done it! Twice! >:[".to_string(),
+                    _ => format!(
+                        "Oo-ooh! Now you've done it! {} times already... m(_ _)m",
+                        self.num_clicks
+                    ),
+                };
+                ui.checkbox(&mut self.animate_background, "Animate background");
+                if ui.button(button_text).clicked() {
+                    self.num_clicks += 1;
+                }
+                if self.num_clicks > 0 {
+                    ui.label(format!(
+                        "You've clicked the button {} time(s)",
+                        self.num_clicks
+                    ));
+                }
+                if ui.checkbox(&mut self.checked, "Some checkbox").changed() && self.checked {
+                    self.num_checks += 1;
+                }
+                let label_text = if self.checked {
+                    "The checkbox *is* checked"
+                } else {
+                    "The checkbox is *not* checked"
+                };
+                ui.label(label_text);
+                ui.label(format!(
+                    "The checkbox has been checked {} time(s)",
+                    self.num_checks
+                ));
+            });
+        self.file_manager.ui(ctx);
+        self.integrity.ui(ctx);
+        self.background.ui(ctx);
+        for key in self.file_manager.take_removed() {
+            self.forget_file(key);
+        }
+        if let Some((key, file)) = self.file_manager.take_opened() {
+            self.hex_viewer = Some((key, HexViewer::new(&file)));
+        }
+        if let Some((_, hex_viewer)) = &mut self.hex_viewer {
+            if !hex_viewer.ui(ctx) {
+                self.hex_viewer = None;
+            }
+        }
+        let opened_image = self.file_manager.take_opened_image();
+        if let Some((key, file)) = opened_image.or(self.dropped_image.take()) {
+            self.decoding_image = Some((key, ImageDecoding::spawn(&file)));
+        }
+        self.decoding_image_ui(ctx);
+        if let Some((_, image_viewer)) = &mut self.image_viewer {
+            if !image_viewer.ui(ctx) {
+                self.close_image_viewer();
+            }
+        }
+        if let Some(error) = &self.image_error {
+            let mut open = true;
+            egui::Window::new("Image viewer")
+                .open(&mut open)
+                .resizable(false)
+                .show(ctx, |ui| ui.label(error.as_str()));
+            if !open {
+                self.image_error = None;
+            }
+        }
+    }
+    /// Drops the checksums of a file removed from the file manager and
+    /// closes the viewers showing it.
+    fn forget_file(&mut self, key: u64) {
+        if let Some(id) = self.checksum_ids.remove(&key) {
+            self.integrity.remove(id);
+        }
+        if self
+            .hex_viewer
+            .as_ref()
+            .is_some_and(|(shown, _)| *shown == key)
+        {
+            self.hex_viewer = None;
+        }
+        if self
+            .image_viewer
+            .as_ref()
+            .is_some_and(|(shown, _)| *shown == key)
+        {
+            self.close_image_viewer();
+        }
+        if self
+            .decoding_image
+            .as_ref()
+            .is_some_and(|(shown, _)| *shown == key)
+        {
+            self.decoding_image = None;
+        }
+        if self
+            .dropped_image
+            .as_ref()
+            .is_some_and(|(shown, _)| *shown == key)
+        {
+            self.dropped_image = None;
+        }
+    }
+    fn close_image_viewer(&mut self) {
+        let closed = self.image_viewer.take();
+        self.closed_image_viewers
+            .extend(closed.map(|(_, image_viewer)| image_viewer));
+    }
+    /// Opens the image being decoded once it is done, says "decoding…" until
+    /// then.
+    fn decoding_image_ui(&mut self, ctx: &egui::Context) {
+        let Some((key, decoding)) = &mut self.decoding_image else {
+            return;
+        };
+        let key = *key;
+        match decoding.take_result() {
+            Some(Ok(image_viewer)) => {
+                self.close_image_viewer();
+                self.image_viewer = Some((key, image_viewer));
+                self.image_error = None;
+            }
+            Some(Err(err)) => self.image_error = Some(format!("{}: {err}", decoding.name())),
+            None => {
+                egui::Window::new("Decoding image")
+                    .resizable(false)
+                    .collapsible(false)
+                    .show(ctx, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label(format!("decoding {}…", decoding.name()));
+                        });
+                    });
+                // Nothing else wakes the event loop when decoding is done.
+                ctx.request_repaint_after(Duration::from_millis(50));
+                return;
+            }
+        }
+        self.decoding_image = None;
+    }
+    /// See [`AppLogic::update_textures`].
+    pub fn update_textures(&mut self, gpu: &GpuState, textures: &mut NativeTextures<'_>) {
+        for mut image_viewer in self.closed_image_viewers.drain(..) {
+            image_viewer.free_textures(textures);
+        }
+        if let Some((_, image_viewer)) = &mut self.image_viewer {
+            image_viewer.update_textures(gpu, textures);
+        }
+    }
+    pub fn num_clicks(&self) -> usize {
+        self.num_clicks
+    }
+    pub fn is_checked(&self) -> bool {
+        self.checked
+    }
+    pub fn num_checks(&self) -> usize {
+        self.num_checks
+    }
+    pub fn drop_file(&mut self, file: DroppedFile) {
+        if file.name.to_lowercase().ends_with(".wgsl") {
+            match std::str::from_utf8(&file.bytes) {
+                Ok(source) => self.background.set_source(&file.name, source),
+                Err(err) => log::warn!("{} is not UTF-8: {err}", file.name),
+            }
+        }
+        let checksum_id = self.integrity.add(&file);
+        let image = is_viewable_image(&file).then(|| file.clone());
+        let key = self.file_manager.add(file);
+        if let Some(id) = checksum_id {
+            self.checksum_ids.insert(key, id);
+        }
+        if let Some(image) = image {
+            self.dropped_image = Some((key, image));
+        }
+    }
+    pub fn file_manager(&self) -> &FileManager {
+        &self.file_manager
+    }
+    pub fn file_manager_mut(&mut self) -> &mut FileManager {
+        &mut self.file_manager
+    }
+    pub fn integrity(&self) -> &IntegrityPanel {
+        &self.integrity
+    }
+    pub fn integrity_mut(&mut self) -> &mut IntegrityPanel {
+        &mut self.integrity
+    }
+    pub fn hex_viewer(&self) -> Option<&HexViewer> {
+        self.hex_viewer.as_ref().map(|(_, hex_viewer)| hex_viewer)
+    }
+    pub fn image_viewer(&self) -> Option<&ImageViewer> {
+        self.image_viewer
+            .as_ref()
+            .map(|(_, image_viewer)| image_viewer)
+    }
+    pub fn is_decoding_image(&self) -> bool {
+        self.decoding_image.is_some()
+    }
+    pub fn image_viewer_mut(&mut self) -> Option<&mut ImageViewer> {
+        self.image_viewer
+            .as_mut()
+            .map(|(_, image_viewer)| image_viewer)
+    }
+    pub fn background(&self) -> &BackgroundShader {
+        &self.background
+    }
+    pub fn background_mut(&mut self) -> &mut BackgroundShader {
+        &mut self.background
+    }
+}
+impl Default for UiState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Payload of the "set_theme" message.
+#[derive(Clone, Copy, Debug, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Theme {
+    Dark,
+    Light,
+}
+
+/// The demo: a background that cycles through all hues (or runs a fragment
+/// shader of your own) plus a small egui window to click around in.
+pub struct DemoApp {
+    ui_state: UiState,
+    timeline: Timeline,
+    proxy: Option<AppProxy>,
+    /// Set by a "set_theme" message, applied with the next frame.
+    pending_theme: Option<Theme>,
+}
+
+impl DemoApp {
+    pub fn new() -> Self {
+        let hue = Animation::new(0.0)
+            .key(Duration::from_millis(5000), 360.0, Easing::Linear)
+            .repeat(Repeat::Loop);
+        Self {
+            ui_state: UiState::new(),
+            timeline: Timeline::new()
+                .with("hue", hue)
+                .with("saturation", Animation::new(0.5))
+                .with("lightness", Animation::new(0.5)),
+            proxy: None,
+            pending_theme: None,
+        }
+    }
+
+    /// The animations behind the background color: "hue" (in degrees),
+    /// "saturation" and "lightness" (both `0..=1`).
+    pub fn timeline_mut(&mut self) -> &mut Timeline {
+        &mut self.timeline
+    }
+
+    /// Stops the background animation at `time`, so frames are reproducible
+    /// (eg. for screenshots and golden images).
+    pub fn freeze_at(&mut self, time: Duration) {
+        self.ui_state.animate_background = false;
+        self.timeline.set_paused(true);
+        self.timeline.seek(time);
+    }
+
+    fn post_to_host(&self, kind: &str, payload: impl serde::Serialize) {
+        match (&self.proxy, Message::new(kind, payload)) {
+            (Some(proxy), Ok(message)) => {
+                proxy.post_to_host(message);
+            }
+            (_, Err(err)) => log::warn!("{err}"),
+            (None, Ok(_)) => (),
+        }
+    }
+
+    fn current_color(&self) -> Color {
+        use palette::{FromColor, Hsl, Srgb};
+        let value = |name| self.timeline.value(name).unwrap_or_default();
+        let hsl = Hsl::new(value("hue"), value("saturation"), value("lightness"));
+        let rgb: Srgb = Srgb::from_color(hsl);
+        Color {
+            r: rgb.red as f64,
+            g: rgb.green as f64,
+            b: rgb.blue as f64,
+            a: 1.0,
+        }
+    }
+}
+impl Default for DemoApp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AppLogic for DemoApp {
+    fn init(&mut self, gpu: &GpuState, proxy: AppProxy) {
+        self.ui_state.software_rendering = gpu.tier() == AdapterTier::Software;
+        self.proxy = Some(proxy);
+    }
+    fn update(&mut self, _gpu: &GpuState, now: Duration) {
+        self.timeline.set_paused(!self.ui_state.animate_background);
+        self.timeline.update(now);
+        self.ui_state.background.set_time(now);
+    }
+    fn is_animating(&self) -> bool {
+        // A shader of your own gets the time, so it may change every frame.
+        self.ui_state.animate_background || self.ui_state.background.is_enabled()
+    }
+    fn ui(&mut self, ctx: &egui::Context) {
+        match self.pending_theme.take() {
+            Some(Theme::Dark) => ctx.set_visuals(egui::Visuals::dark()),
+            Some(Theme::Light) => ctx.set_visuals(egui::Visuals::light()),
+            None => (),
+        }
+        let num_clicks = self.ui_state.num_clicks;
+        self.ui_state.run_egui(ctx);
+        if self.ui_state.num_clicks != num_clicks {
+            self.post_to_host("clicked", self.ui_state.num_clicks);
+        }
+    }
+    fn update_textures(&mut self, gpu: &GpuState, textures: &mut NativeTextures<'_>) {
+        self.ui_state.update_textures(gpu, textures);
+    }
+    fn render(&mut self, gpu: &GpuState, mut target: RenderTarget<'_>) {
+        if !self.ui_state.background.render(gpu, &mut target) {
+            target.clear(self.current_color());
+        }
+    }
+    fn on_device_recreated(&mut self, _gpu: &GpuState) {
+        self.ui_state.background.on_device_recreated();
+    }
+    fn on_file_dropped(&mut self, dropped_file: DroppedFile) {
+        self.ui_state.drop_file(dropped_file);
+    }
+    fn on_file_event(&mut self, event: FileEvent) {
+        self.ui_state.integrity.on_file_event(&event);
+    }
+    /// Understands "set_theme" (`"dark"` or `"light"`) and
+    /// "set_animate_background" (`true` or `false`); problems are posted back
+    /// as "error".
+    fn on_user_message(&mut self, message: Message) {
+        log::info!("DemoApp received message: {message:?}");
+        let result = match message.kind.as_str() {
+            "set_theme" => message
+                .payload()
+                .map(|theme| self.pending_theme = Some(theme)),
+            "set_animate_background" => message
+                .payload()
+                .map(|animate| self.ui_state.animate_background = animate),
+            kind => {
+                self.post_to_host("error", format!("unknown message '{kind}'"));
+                return;
+            }
+        };
+        if let Err(err) = result {
+            self.post_to_host("error", err.to_string());
+        }
+    }
+}
@@ -0,0 +1,495 @@
+//
+// The demo's background shader: a full-screen fragment shader, typed into the
+// editor or dropped as a `.wgsl` file, drawn instead of the clear color
+//
+// Only `fs_main` is written by the user; the uniforms and the vertex shader
+// come from the prelude in front of it. Code that does not compile leaves the
+// previous shader running.
+//
+
+use crate::*;
+
+/// Declarations in front of every background shader.
+const PRELUDE: &str = "\
+struct Uniforms {
+    resolution: vec2<f32>,
+    mouse: vec2<f32>,
+    time: f32,
+    frame: u32,
+    scale_factor: f32,
+}
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // One triangle covering the whole screen.
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+";
+
+/// What the editor starts with.
+pub const EXAMPLE_SHADER: &str = "\
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let phase = uniforms.time + in.uv.xyx * 3.0 + vec3<f32>(0.0, 2.0, 4.0);
+    let color = 0.5 + 0.5 * cos(phase);
+    // A glow that follows the mouse.
+    let to_mouse = (in.position.xy - uniforms.mouse) / uniforms.scale_factor;
+    let glow = 20.0 / max(length(to_mouse), 1.0);
+    return vec4<f32>(color + glow, 1.0);
+}
+";
+
+/// Size of `Uniforms` in the prelude.
+const UNIFORMS_SIZE: u64 = 32;
+
+/// The values of the built-in `uniforms`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ShaderInputs {
+    /// Of the render target, in pixels.
+    pub resolution: [f32; 2],
+    /// The last known pointer position, in pixels from the top left.
+    pub mouse: [f32; 2],
+    /// Seconds on the app's clock.
+    pub time: f32,
+    /// Frames rendered with a shader so far.
+    pub frame: u32,
+    pub scale_factor: f32,
+}
+impl ShaderInputs {
+    fn to_bytes(self) -> Vec<u8> {
+        let [width, height] = self.resolution;
+        let [x, y] = self.mouse;
+        let mut bytes: Vec<u8> = [width, height, x, y, self.time]
+            .into_iter()
+            .flat_map(f32::to_le_bytes)
+            .collect();
+        bytes.extend(self.frame.to_le_bytes());
+        bytes.extend(self.scale_factor.to_le_bytes());
+        bytes.resize(UNIFORMS_SIZE as usize, 0);
+        bytes
+    }
+}
+
+/// The buffer behind `uniforms`, shared by every shader.
+struct Uniforms {
+    buffer: Buffer,
+    bind_group: BindGroup,
+    pipeline_layout: PipelineLayout,
+}
+impl Uniforms {
+    fn new(device: &Device) -> Self {
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("background uniforms"),
+            size: UNIFORMS_SIZE,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("background uniforms"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX_FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("background uniforms"),
+            layout: &layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("background"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        Self {
+            buffer,
+            bind_group,
+            pipeline_layout,
+        }
+    }
+}
+
+/// The shader being drawn: its code, and its pipeline for the format it was
+/// last drawn to (`None` after the device was lost).
+struct Running {
+    source: String,
+    pipeline: Option<(TextureFormat, RenderPipeline)>,
+}
+
+/// A fragment shader as the background, with an editor window for its code.
+pub struct BackgroundShader {
+    /// The code in the editor, without the prelude.
+    source: String,
+    /// Where the code came from: "editor" or the name of a dropped file.
+    origin: String,
+    /// The code changed since it was last compiled.
+    changed: bool,
+    /// Draw the shader instead of the clear color.
+    enabled: bool,
+    error: Option<ShaderError>,
+    running: Option<Running>,
+    uniforms: Option<Uniforms>,
+    inputs: ShaderInputs,
+}
+impl BackgroundShader {
+    pub fn new() -> Self {
+        Self {
+            source: EXAMPLE_SHADER.to_string(),
+            origin: "editor".to_string(),
+            changed: true,
+            enabled: false,
+            error: None,
+            running: None,
+            uniforms: None,
+            inputs: ShaderInputs {
+                scale_factor: 1.0,
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+    /// Replaces the code (eg. with a dropped `.wgsl` file) and draws it from
+    /// the next frame on.
+    pub fn set_source(&mut self, origin: &str, source: &str) {
+        self.source = source.to_string();
+        self.origin = origin.to_string();
+        self.changed = true;
+        self.enabled = true;
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+    /// `false` goes back to the clear color.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+    /// Why the code in the editor did not compile; lines count from the
+    /// start of the editor, not of the prelude.
+    pub fn error(&self) -> Option<&ShaderError> {
+        self.error.as_ref()
+    }
+    /// The code of the shader being drawn, which is the last one that
+    /// compiled.
+    pub fn running_source(&self) -> Option<&str> {
+        Some(&self.running.as_ref()?.source)
+    }
+    pub fn inputs(&self) -> ShaderInputs {
+        self.inputs
+    }
+    pub fn set_time(&mut self, now: Duration) {
+        self.inputs.time = now.as_secs_f32();
+    }
+
+    /// Everything created from the old device is gone; the running shader
+    /// is rebuilt with the next frame.
+    pub fn on_device_recreated(&mut self) {
+        self.uniforms = None;
+        if let Some(running) = &mut self.running {
+            running.pipeline = None;
+        }
+    }
+
+    /// Draws the shader over the whole `target`; returns `false` (and draws
+    /// nothing) when the clear color is to be used instead.
+    pub fn render(&mut self, gpu: &GpuState, target: &mut RenderTarget<'_>) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let uniforms = self
+            .uniforms
+            .get_or_insert_with(|| Uniforms::new(gpu.device()));
+        if self.changed {
+            self.changed = false;
+            match compile(gpu, uniforms, target.format, &self.source) {
+                Ok(pipeline) => {
+                    self.running = Some(Running {
+                        source: self.source.clone(),
+                        pipeline: Some((target.format, pipeline)),
+                    });
+                    self.error = None;
+                }
+                Err(err) => self.error = Some(err),
+            }
+        }
+        let Some(running) = &mut self.running else {
+            return false;
+        };
+        if !running
+            .pipeline
+            .as_ref()
+            .is_some_and(|(format, _)| *format == target.format)
+        {
+            match compile(gpu, uniforms, target.format, &running.source) {
+                Ok(pipeline) => running.pipeline = Some((target.format, pipeline)),
+                Err(err) => {
+                    log::error!("background shader: {err}");
+                    self.running = None;
+                    return false;
+                }
+            }
+        }
+        let Some((_, pipeline)) = &running.pipeline else {
+            return false;
+        };
+
+        self.inputs.resolution = [target.size.width as f32, target.size.height as f32];
+        gpu.queue()
+            .write_buffer(&uniforms.buffer, 0, &self.inputs.to_bytes());
+        self.inputs.frame = self.inputs.frame.wrapping_add(1);
+        let mut render_pass = target.encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("background"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: target.view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::BLACK),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &uniforms.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        true
+    }
+
+    /// The editor window; also picks up the pointer position for `uniforms`.
+    pub fn ui(&mut self, ctx: &egui::Context) {
+        let (pointer, pixels_per_point) =
+            ctx.input(|i| (i.pointer.latest_pos(), i.pixels_per_point));
+        if let Some(pointer) = pointer {
+            self.inputs.mouse = (pointer.to_vec2() * pixels_per_point).into();
+        }
+        self.inputs.scale_factor = pixels_per_point;
+
+        egui::Window::new("Background shader")
+            .anchor(egui::Align2::LEFT_BOTTOM, [8.0, -8.0])
+            .default_open(false)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.enabled, "draw the shader");
+                    if ui.button("example").clicked() {
+                        self.set_source("editor", EXAMPLE_SHADER);
+                    }
+                });
+                let status = match (self.enabled, &self.running, &self.error) {
+                    (false, ..) => "the clear color is drawn",
+                    (true, Some(_), Some(_)) => {
+                        "does not compile, the previous shader keeps running"
+                    }
+                    (true, None, Some(_)) => "does not compile",
+                    (true, Some(_), None) => "running",
+                    (true, None, None) => "compiling...",
+                };
+                ui.label(format!("{}: {status}", self.origin));
+                ui.weak(
+                    "fs_main(in: VertexOutput) -> @location(0) vec4<f32>, with \
+                     uniforms.resolution, mouse, time, frame and scale_factor",
+                );
+                ui.separator();
+                if self.editor_ui(ui) {
+                    self.changed = true;
+                    self.enabled = true;
+                    ctx.request_repaint();
+                }
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error.to_string());
+                }
+            });
+    }
+
+    /// The code with line numbers, and the line with the error highlighted;
+    /// returns `true` when it was edited.
+    fn editor_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let error_line = self.error.as_ref().and_then(|error| error.line);
+        let mut layouter = |ui: &egui::Ui, text: &str, _wrap_width: f32| {
+            let font = egui::TextStyle::Monospace.resolve(ui.style());
+            let mut job = egui::text::LayoutJob::default();
+            for (index, line) in text.split_inclusive('\n').enumerate() {
+                let mut format = egui::TextFormat::simple(font.clone(), ui.visuals().text_color());
+                if error_line == Some(index as u32 + 1) {
+                    format.background = ui.visuals().error_fg_color.gamma_multiply(0.3);
+                }
+                job.append(line, 0.0, format);
+            }
+            // No wrapping, so the line numbers stay next to their lines.
+            job.wrap.max_width = f32::INFINITY;
+            ui.fonts(|fonts| fonts.layout_job(job))
+        };
+        let line_count = self.source.split('\n').count();
+        let line_numbers: Vec<String> = (1..=line_count).map(|line| line.to_string()).collect();
+        egui::ScrollArea::vertical()
+            .max_height(320.0)
+            .show(ui, |ui| {
+                ui.horizontal_top(|ui| {
+                    ui.add(egui::Label::new(
+                        egui::RichText::new(line_numbers.join("\n"))
+                            .monospace()
+                            .weak(),
+                    ));
+                    ui.add(
+                        egui::TextEdit::multiline(&mut self.source)
+                            .code_editor()
+                            .desired_width(f32::INFINITY)
+                            .desired_rows(12)
+                            .layouter(&mut layouter),
+                    )
+                    .changed()
+                })
+                .inner
+            })
+            .inner
+    }
+}
+impl Default for BackgroundShader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A pipeline drawing `source` (after the prelude) into `format`; errors are
+/// located in `source`.
+fn compile(
+    gpu: &GpuState,
+    uniforms: &Uniforms,
+    format: TextureFormat,
+    source: &str,
+) -> Result<RenderPipeline, ShaderError> {
+    let code = format!("{PRELUDE}{source}");
+    let module = check_wgsl(&code, gpu.shader_capabilities()).map_err(|mut err| {
+        let prelude_lines = PRELUDE.lines().count() as u32;
+        match err.line {
+            Some(line) if line > prelude_lines => err.line = Some(line - prelude_lines),
+            Some(_) => {
+                err.line = None;
+                err.column = None;
+                err.message = format!("in the prelude: {}", err.message);
+            }
+            None => (),
+        }
+        err
+    })?;
+    check_interface(&module)?;
+
+    // Anything the checks above missed must not reach the default error
+    // handler, which panics.
+    let device = gpu.device();
+    validated(device, "background shader", || {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("background"),
+            source: ShaderSource::Wgsl(code.into()),
+        });
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("background"),
+            layout: Some(&uniforms.pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        })
+    })
+}
+
+/// Whether `module` fits the pipeline: an `fs_main` that reads at most what
+/// `vs_main` writes, writes one color, and binds nothing but `uniforms`.
+fn check_interface(module: &naga::Module) -> Result<(), ShaderError> {
+    let fs_main = module
+        .entry_points
+        .iter()
+        .find(|entry| entry.name == "fs_main" && entry.stage == naga::ShaderStage::Fragment)
+        .ok_or_else(|| ShaderError::new("there is no `@fragment fn fs_main`"))?;
+
+    let is_vec = |ty: naga::Handle<naga::Type>, size| {
+        matches!(
+            module.types[ty].inner,
+            naga::TypeInner::Vector { size: s, scalar } if s == size && scalar.kind == naga::ScalarKind::Float
+        )
+    };
+    let returns_color = fs_main.function.result.as_ref().is_some_and(|result| {
+        matches!(
+            result.binding,
+            Some(naga::Binding::Location { location: 0, .. })
+        ) && is_vec(result.ty, naga::VectorSize::Quad)
+    });
+    if !returns_color {
+        return Err(ShaderError::new(
+            "fs_main has to return `@location(0) vec4<f32>`",
+        ));
+    }
+
+    // Arguments, or the members of struct arguments, with their bindings.
+    let mut inputs = Vec::new();
+    for argument in &fs_main.function.arguments {
+        match (&argument.binding, &module.types[argument.ty].inner) {
+            (None, naga::TypeInner::Struct { members, .. }) => inputs.extend(
+                members
+                    .iter()
+                    .map(|member| (member.binding.as_ref(), member.ty)),
+            ),
+            (binding, _) => inputs.push((binding.as_ref(), argument.ty)),
+        }
+    }
+    for (binding, ty) in inputs {
+        if let Some(naga::Binding::Location { location, .. }) = binding {
+            if *location != 0 || !is_vec(ty, naga::VectorSize::Bi) {
+                return Err(ShaderError::new(format!(
+                    "fs_main can only read `@location(0) uv: vec2<f32>`, not @location({location})"
+                )));
+            }
+        }
+    }
+
+    for (_, variable) in module.global_variables.iter() {
+        if let Some(binding) = &variable.binding {
+            if (binding.group, binding.binding) != (0, 0) {
+                return Err(ShaderError::new(format!(
+                    "only `uniforms` can be bound, not @group({}) @binding({})",
+                    binding.group, binding.binding
+                )));
+            }
+        }
+    }
+    Ok(())
+}
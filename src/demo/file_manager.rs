@@ -0,0 +1,361 @@
+//
+// The demo's file manager: the dropped files, with their metadata
+//
+
+use super::is_viewable_image;
+use crate::*;
+use std::collections::BTreeSet;
+
+/// What the file list is sorted by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    Size,
+    Type,
+    DroppedAt,
+}
+impl SortKey {
+    const ALL: [Self; 4] = [Self::Name, Self::Size, Self::Type, Self::DroppedAt];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Size => "size",
+            Self::Type => "type",
+            Self::DroppedAt => "dropped",
+        }
+    }
+}
+
+/// `bytes` for humans, eg. "1.5 KiB".
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// The files dropped onto the demo, to sort, filter, select and remove.
+pub struct FileManager {
+    /// In the order they were dropped, each with a key that stays the same
+    /// while files around it are removed.
+    files: Vec<(u64, DroppedFile)>,
+    next_key: u64,
+    sort_key: SortKey,
+    ascending: bool,
+    /// Matched against names and MIME types, ignoring case.
+    filter: String,
+    selected: BTreeSet<u64>,
+    /// The file clicked last: shown in the detail view, and where
+    /// shift-click selections start.
+    focused: Option<u64>,
+    /// Asked to be opened in a viewer, see [`FileManager::take_opened`].
+    opened: Option<u64>,
+    /// See [`FileManager::take_opened_image`].
+    opened_image: Option<u64>,
+    /// See [`FileManager::take_removed`].
+    removed: Vec<u64>,
+}
+impl FileManager {
+    pub fn new() -> Self {
+        Self {
+            files: Vec::new(),
+            next_key: 0,
+            sort_key: SortKey::DroppedAt,
+            ascending: true,
+            filter: String::new(),
+            selected: BTreeSet::new(),
+            focused: None,
+            opened: None,
+            opened_image: None,
+            removed: Vec::new(),
+        }
+    }
+
+    /// Returns the key the file goes by, see [`FileManager::take_removed`].
+    pub fn add(&mut self, file: DroppedFile) -> u64 {
+        let key = self.next_key;
+        self.next_key += 1;
+        self.files.push((key, file));
+        key
+    }
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+    /// In the order they were dropped.
+    pub fn files(&self) -> impl Iterator<Item = &DroppedFile> {
+        self.files.iter().map(|(_, file)| file)
+    }
+
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+    pub fn set_filter(&mut self, filter: &str) {
+        self.filter = filter.to_string();
+    }
+    pub fn sort_key(&self) -> (SortKey, bool) {
+        (self.sort_key, self.ascending)
+    }
+    /// Sorts by `key`, or reverses the order if already sorted by it.
+    pub fn sort_by(&mut self, key: SortKey) {
+        self.ascending = key != self.sort_key || !self.ascending;
+        self.sort_key = key;
+    }
+
+    /// The files that pass the filter, sorted.
+    pub fn visible(&self) -> Vec<&DroppedFile> {
+        self.visible_entries()
+            .into_iter()
+            .map(|(_, file)| file)
+            .collect()
+    }
+    fn visible_entries(&self) -> Vec<(u64, &DroppedFile)> {
+        let filter = self.filter.to_lowercase();
+        let mut entries: Vec<_> = self
+            .files
+            .iter()
+            .filter(|(_, file)| {
+                file.name.to_lowercase().contains(&filter) || file.mime_type.contains(&filter)
+            })
+            .map(|(key, file)| (*key, file))
+            .collect();
+        entries.sort_by(|(key_a, a), (key_b, b)| {
+            let order = match self.sort_key {
+                SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+                SortKey::Size => a.len().cmp(&b.len()),
+                SortKey::Type => a.mime_type.cmp(b.mime_type),
+                SortKey::DroppedAt => a.dropped_at.cmp(&b.dropped_at),
+            }
+            .then(key_a.cmp(key_b));
+            if self.ascending {
+                order
+            } else {
+                order.reverse()
+            }
+        });
+        entries
+    }
+
+    pub fn selected(&self) -> Vec<&DroppedFile> {
+        self.files
+            .iter()
+            .filter(|(key, _)| self.selected.contains(key))
+            .map(|(_, file)| file)
+            .collect()
+    }
+    /// The file shown in the detail view.
+    pub fn focused(&self) -> Option<&DroppedFile> {
+        let focused = self.focused?;
+        self.files
+            .iter()
+            .find(|(key, _)| *key == focused)
+            .map(|(_, file)| file)
+    }
+    pub fn remove_selected(&mut self) {
+        let selected = std::mem::take(&mut self.selected);
+        self.remove(|key| selected.contains(&key));
+    }
+    pub fn clear(&mut self) {
+        self.selected.clear();
+        self.remove(|_| true);
+    }
+    fn remove(&mut self, remove: impl Fn(u64) -> bool) {
+        let removed = &mut self.removed;
+        self.files.retain(|(key, _)| {
+            let keep = !remove(*key);
+            if !keep {
+                removed.push(*key);
+            }
+            keep
+        });
+        for key in [&mut self.focused, &mut self.opened, &mut self.opened_image] {
+            if key.is_some_and(&remove) {
+                *key = None;
+            }
+        }
+    }
+    /// The keys of the files removed since the last call, to close what
+    /// shows them.
+    pub fn take_removed(&mut self) -> Vec<u64> {
+        std::mem::take(&mut self.removed)
+    }
+
+    /// The file the user asked to open with the button in the detail view,
+    /// once, with its key.
+    pub fn take_opened(&mut self) -> Option<(u64, DroppedFile)> {
+        let opened = self.opened.take()?;
+        self.file(opened)
+    }
+    /// Like [`FileManager::take_opened`], for the image viewer.
+    pub fn take_opened_image(&mut self) -> Option<(u64, DroppedFile)> {
+        let opened = self.opened_image.take()?;
+        self.file(opened)
+    }
+    fn file(&self, key: u64) -> Option<(u64, DroppedFile)> {
+        self.files.iter().find(|(other, _)| *other == key).cloned()
+    }
+
+    /// Like a file browser: a click selects just that file, ctrl-click adds
+    /// or removes it and shift-click selects everything from the focused file
+    /// up to it (or just it, while the focused file is filtered out).
+    fn click(&mut self, key: u64, modifiers: egui::Modifiers, visible: &[u64]) {
+        let position = |key| visible.iter().position(|visible| *visible == key);
+        let range = self.focused.and_then(position).zip(position(key));
+        match range {
+            // The focused file stays the anchor for the next shift-click.
+            Some((from, to)) if modifiers.shift => {
+                self.selected.extend(&visible[from.min(to)..=from.max(to)]);
+            }
+            _ if modifiers.command && !modifiers.shift => {
+                if !self.selected.remove(&key) {
+                    self.selected.insert(key);
+                }
+                self.focused = Some(key);
+            }
+            _ => {
+                self.selected.clear();
+                self.selected.insert(key);
+                self.focused = Some(key);
+            }
+        }
+    }
+
+    pub fn ui(&mut self, ctx: &egui::Context) {
+        if self.files.is_empty() {
+            return;
+        }
+        egui::Window::new("Dropped files")
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.filter)
+                            .hint_text("filter by name or type")
+                            .desired_width(180.0),
+                    );
+                    if ui
+                        .add_enabled(
+                            !self.selected.is_empty(),
+                            egui::Button::new("remove selected"),
+                        )
+                        .clicked()
+                    {
+                        self.remove_selected();
+                    }
+                    if ui.button("clear").clicked() {
+                        self.clear();
+                    }
+                });
+                let entries = self.visible_entries();
+                ui.label(format!(
+                    "{} of {} files shown, {} selected",
+                    entries.len(),
+                    self.files.len(),
+                    self.selected.len()
+                ));
+
+                let mut sort_by = None;
+                let mut clicked = None;
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("dropped files")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for key in SortKey::ALL {
+                                    let arrow = match (key == self.sort_key, self.ascending) {
+                                        (false, _) => "",
+                                        (true, true) => " ⏶",
+                                        (true, false) => " ⏷",
+                                    };
+                                    if ui.button(format!("{}{arrow}", key.label())).clicked() {
+                                        sort_by = Some(key);
+                                    }
+                                }
+                                ui.end_row();
+                                for (key, file) in &entries {
+                                    let selected = self.selected.contains(key);
+                                    if ui.selectable_label(selected, file.name.as_str()).clicked() {
+                                        clicked = Some(*key);
+                                    }
+                                    ui.label(format_size(file.len()));
+                                    ui.label(file.mime_type);
+                                    ui.label(file.dropped_at.format("%H:%M:%S").to_string());
+                                    ui.end_row();
+                                }
+                            });
+                    });
+                let visible: Vec<u64> = entries.iter().map(|(key, _)| *key).collect();
+                let mut open = None;
+                let mut open_image = None;
+
+                if let Some(file) = self.focused() {
+                    ui.separator();
+                    egui::Grid::new("file details").show(ui, |ui| {
+                        ui.label("name");
+                        ui.label(file.name.as_str());
+                        ui.end_row();
+                        ui.label("path");
+                        match &file.path {
+                            Some(path) => ui.label(path.display().to_string()),
+                            None => ui.weak("unknown"),
+                        };
+                        ui.end_row();
+                        ui.label("size");
+                        ui.label(format!(
+                            "{} ({} bytes)",
+                            format_size(file.len()),
+                            file.len()
+                        ));
+                        ui.end_row();
+                        ui.label("type");
+                        ui.label(file.mime_type);
+                        ui.end_row();
+                        ui.label("dropped at");
+                        ui.label(file.dropped_at.format("%Y-%m-%d %H:%M:%S").to_string());
+                        ui.end_row();
+                        ui.label("from");
+                        ui.label(file.source.to_string());
+                        ui.end_row();
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button("hex view").clicked() {
+                            open = self.focused;
+                        }
+                        if is_viewable_image(file) && ui.button("view image").clicked() {
+                            open_image = self.focused;
+                        }
+                    });
+                }
+                if open.is_some() {
+                    self.opened = open;
+                }
+                if open_image.is_some() {
+                    self.opened_image = open_image;
+                }
+
+                if let Some(key) = sort_by {
+                    self.sort_by(key);
+                }
+                if let Some(key) = clicked {
+                    let modifiers = ui.input(|input| input.modifiers);
+                    self.click(key, modifiers, &visible);
+                }
+            });
+    }
+}
+impl Default for FileManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,836 @@
+//
+// The demo's hex viewer: the bytes of a dropped file, to inspect and edit
+//
+// Only the rows on screen are laid out and painted, and the scroll position is
+// a row index rather than a pixel offset, so large files scroll the same. It
+// shows files that are kept whole, up to `AppConfig::max_assembled_file_size`.
+//
+
+use super::format_size;
+use crate::*;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::PathBuf;
+
+const BYTES_PER_ROW: usize = 16;
+const VISIBLE_ROWS: usize = 24;
+
+/// How the search text is read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchMode {
+    /// As UTF-8 text.
+    Text,
+    /// As hex bytes, eg. `"89 50 4e 47"`.
+    Hex,
+}
+
+/// `text` as the bytes to search for; `None` if there are none.
+pub fn parse_pattern(text: &str, mode: SearchMode) -> Option<Vec<u8>> {
+    let pattern = match mode {
+        SearchMode::Text => text.as_bytes().to_vec(),
+        SearchMode::Hex => {
+            let digits: Vec<u8> = text
+                .bytes()
+                .filter(|byte| !byte.is_ascii_whitespace())
+                .collect();
+            let pairs = digits.chunks_exact(2);
+            if !pairs.remainder().is_empty() {
+                return None;
+            }
+            pairs
+                .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+                .collect::<Option<_>>()?
+        }
+    };
+    (!pattern.is_empty()).then_some(pattern)
+}
+
+/// An offset as typed: `0x` hex, or decimal.
+fn parse_offset(text: &str) -> Option<usize> {
+    let text = text.trim();
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// What the bytes at the start of `bytes` are as numbers: each type that
+/// fits, with its little and big endian value.
+pub fn interpret(bytes: &[u8]) -> Vec<(&'static str, String, String)> {
+    macro_rules! both_endians {
+        ($ty:ty) => {{
+            const SIZE: usize = std::mem::size_of::<$ty>();
+            bytes.get(..SIZE).map(|head| {
+                let head: [u8; SIZE] = head.try_into().unwrap();
+                (
+                    stringify!($ty),
+                    <$ty>::from_le_bytes(head).to_string(),
+                    <$ty>::from_be_bytes(head).to_string(),
+                )
+            })
+        }};
+    }
+    [
+        both_endians!(u8),
+        both_endians!(i8),
+        both_endians!(u16),
+        both_endians!(i16),
+        both_endians!(u32),
+        both_endians!(i32),
+        both_endians!(u64),
+        both_endians!(i64),
+        both_endians!(f32),
+        both_endians!(f64),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Where the columns go, in points from the left edge of the rows.
+struct Layout {
+    font_id: egui::FontId,
+    char_width: f32,
+    row_height: f32,
+    offset_digits: usize,
+    hex_column: f32,
+    ascii_column: f32,
+}
+impl Layout {
+    fn new(ui: &egui::Ui, last_offset: usize) -> Self {
+        let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+        let (char_width, row_height) =
+            ui.fonts(|fonts| (fonts.glyph_width(&font_id, '0'), fonts.row_height(&font_id)));
+        let offset_digits = format!("{last_offset:x}").len().max(8);
+        let hex_column = (offset_digits + 2) as f32 * char_width;
+        let mut layout = Self {
+            font_id,
+            char_width,
+            row_height,
+            offset_digits,
+            hex_column,
+            ascii_column: 0.0,
+        };
+        layout.ascii_column = layout.byte_x(BYTES_PER_ROW) + char_width;
+        layout
+    }
+
+    /// Of the `i`th byte of a row in the hex column, with a gap after eight.
+    fn byte_x(&self, i: usize) -> f32 {
+        self.hex_column + (i * 3 + i / 8) as f32 * self.char_width
+    }
+
+    fn size(&self) -> egui::Vec2 {
+        egui::vec2(
+            self.ascii_column + BYTES_PER_ROW as f32 * self.char_width,
+            VISIBLE_ROWS as f32 * self.row_height,
+        )
+    }
+
+    /// The offset at `pos` (relative to the top left of the rows), and
+    /// whether it is in the ASCII column.
+    fn hit(&self, pos: egui::Vec2, top_row: usize) -> (usize, bool) {
+        let row = top_row + (pos.y / self.row_height).max(0.0) as usize;
+        let (column, ascii) = if pos.x >= self.ascii_column - self.char_width {
+            let column = (pos.x - self.ascii_column) / self.char_width;
+            (column.max(0.0) as usize, true)
+        } else {
+            let units = ((pos.x - self.hex_column) / self.char_width).max(0.0) as usize;
+            let units = if units > 8 * 3 { units - 1 } else { units };
+            (units / 3, false)
+        };
+        (row * BYTES_PER_ROW + column.min(BYTES_PER_ROW - 1), ascii)
+    }
+}
+
+/// A change to one byte.
+#[derive(Clone, Copy, Debug)]
+struct Edit {
+    offset: usize,
+    old: u8,
+    new: u8,
+}
+
+/// Shows the bytes of a file as offset, hex and ASCII columns; finds, selects,
+/// interprets and edits them.
+pub struct HexViewer {
+    name: String,
+    path: Option<PathBuf>,
+    /// As dropped, shared with the other parts of the demo.
+    bytes: Arc<[u8]>,
+    /// The new values of the bytes that were changed.
+    modified: BTreeMap<usize, u8>,
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    /// Where typing goes; the selection runs from `anchor` to here.
+    cursor: usize,
+    anchor: usize,
+    /// The high nibble typed at the cursor, waiting for the low one.
+    pending_nibble: Option<u8>,
+    /// Typing replaces characters in the ASCII column instead of hex digits.
+    ascii_focus: bool,
+    top_row: usize,
+    /// Mouse wheel scrolling that does not add up to a whole row yet.
+    scroll: f32,
+    goto: String,
+    search: String,
+    search_mode: SearchMode,
+    /// How the last goto, search or save went.
+    status: Option<String>,
+}
+impl HexViewer {
+    pub fn new(file: &DroppedFile) -> Self {
+        Self {
+            name: file.name.clone(),
+            path: file.path.clone(),
+            bytes: file.bytes.clone(),
+            modified: BTreeMap::new(),
+            undo: Vec::new(),
+            redo: Vec::new(),
+            cursor: 0,
+            anchor: 0,
+            pending_nibble: None,
+            ascii_focus: false,
+            top_row: 0,
+            scroll: 0.0,
+            goto: String::new(),
+            search: String::new(),
+            search_mode: SearchMode::Text,
+            status: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// With the edits; only copied if there are any.
+    pub fn bytes(&self) -> Cow<'_, [u8]> {
+        if self.modified.is_empty() {
+            return Cow::Borrowed(&self.bytes);
+        }
+        let mut bytes = self.bytes.to_vec();
+        for (&offset, &byte) in &self.modified {
+            bytes[offset] = byte;
+        }
+        Cow::Owned(bytes)
+    }
+    /// The byte at `offset`, edited or not.
+    pub fn byte(&self, offset: usize) -> Option<u8> {
+        let edited = self.modified.get(&offset).copied();
+        edited.or_else(|| self.bytes.get(offset).copied())
+    }
+    pub fn is_modified(&self) -> bool {
+        !self.modified.is_empty()
+    }
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+    pub fn selection(&self) -> Range<usize> {
+        if self.bytes.is_empty() {
+            return 0..0;
+        }
+        self.anchor.min(self.cursor)..self.anchor.max(self.cursor) + 1
+    }
+    /// The first row on screen.
+    pub fn top_row(&self) -> usize {
+        self.top_row
+    }
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
+    fn rows(&self) -> usize {
+        self.bytes.len().div_ceil(BYTES_PER_ROW)
+    }
+    fn last_offset(&self) -> usize {
+        self.bytes.len().saturating_sub(1)
+    }
+
+    /// Moves the cursor (and the selection with it) to `offset`, scrolling
+    /// it into view.
+    pub fn set_cursor(&mut self, offset: usize) {
+        self.move_cursor(offset, false);
+    }
+    /// Selects `range`, with the cursor on its last byte.
+    pub fn select(&mut self, range: Range<usize>) {
+        self.set_cursor(range.start);
+        self.move_cursor(range.end.saturating_sub(1), true);
+    }
+    fn move_cursor(&mut self, offset: usize, extend_selection: bool) {
+        self.cursor = offset.min(self.last_offset());
+        if !extend_selection {
+            self.anchor = self.cursor;
+        }
+        self.pending_nibble = None;
+        let row = self.cursor / BYTES_PER_ROW;
+        if row < self.top_row {
+            self.top_row = row;
+        } else if row >= self.top_row + VISIBLE_ROWS {
+            self.top_row = row + 1 - VISIBLE_ROWS;
+        }
+    }
+
+    /// Moves the cursor to the offset in `text`; returns `false` (and says so
+    /// in the status) if there is no such offset.
+    pub fn goto(&mut self, text: &str) -> bool {
+        match parse_offset(text) {
+            Some(offset) if offset < self.bytes.len() => {
+                self.set_cursor(offset);
+                self.status = None;
+                true
+            }
+            Some(offset) => {
+                self.status = Some(format!("offset {offset:#x} is past the end"));
+                false
+            }
+            None => {
+                self.status = Some(format!("'{}' is not an offset", text.trim()));
+                false
+            }
+        }
+    }
+
+    /// Selects the next occurrence of `pattern` after the cursor, starting
+    /// over at the beginning if need be.
+    pub fn find(&mut self, pattern: &[u8]) -> Option<usize> {
+        // Over the edited bytes, without copying the file to apply them.
+        let matches_at = |offset: usize| {
+            let range = offset..offset + pattern.len();
+            if self.modified.range(range.clone()).next().is_none() {
+                return self.bytes[range] == *pattern;
+            }
+            range
+                .zip(pattern)
+                .all(|(offset, byte)| self.byte(offset) == Some(*byte))
+        };
+        let position = |from: usize| {
+            let last = self.bytes.len().checked_sub(pattern.len())?;
+            (from..=last).find(|offset| matches_at(*offset))
+        };
+        let found = position(self.cursor + 1).or_else(|| position(0));
+        match found {
+            Some(offset) => {
+                self.select(offset..offset + pattern.len());
+                self.status = Some(format!("found at {offset:#x}"));
+            }
+            None => self.status = Some("not found".to_string()),
+        }
+        found
+    }
+
+    /// Finds the search text, see [`HexViewer::find`].
+    pub fn find_next(&mut self, search: &str, mode: SearchMode) -> Option<usize> {
+        self.search = search.to_string();
+        self.search_mode = mode;
+        let Some(pattern) = parse_pattern(search, mode) else {
+            self.status = Some("nothing to search for".to_string());
+            return None;
+        };
+        self.find(&pattern)
+    }
+
+    /// Changes the byte at `offset`, which can be undone.
+    pub fn write(&mut self, offset: usize, byte: u8) {
+        let Some(old) = self.byte(offset) else {
+            return;
+        };
+        self.apply(Edit {
+            offset,
+            old,
+            new: byte,
+        });
+        self.undo.push(Edit {
+            offset,
+            old,
+            new: byte,
+        });
+        self.redo.clear();
+    }
+    fn apply(&mut self, edit: Edit) {
+        if self.bytes[edit.offset] == edit.new {
+            self.modified.remove(&edit.offset);
+        } else {
+            self.modified.insert(edit.offset, edit.new);
+        }
+    }
+
+    /// Returns `false` if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(edit) = self.undo.pop() else {
+            return false;
+        };
+        self.apply(Edit {
+            offset: edit.offset,
+            old: edit.new,
+            new: edit.old,
+        });
+        self.move_cursor(edit.offset, false);
+        self.redo.push(edit);
+        true
+    }
+    /// Returns `false` if there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let Some(edit) = self.redo.pop() else {
+            return false;
+        };
+        self.apply(edit);
+        self.move_cursor(edit.offset, false);
+        self.undo.push(edit);
+        true
+    }
+
+    /// Where [`HexViewer::save`] puts the edited file: next to the original
+    /// (natively), with `.edited` added to the name.
+    pub fn save_path(&self) -> PathBuf {
+        let name = match self.name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => format!("{stem}.edited.{extension}"),
+            _ => format!("{}.edited", self.name),
+        };
+        match &self.path {
+            Some(path) => path.with_file_name(name),
+            None => name.into(),
+        }
+    }
+    /// Writes the edited file to [`HexViewer::save_path`] (natively) or
+    /// offers it as a download (on the web).
+    pub fn save(&mut self) -> Result<()> {
+        let path = self.save_path();
+        let result = save_file(&path, &self.bytes());
+        self.status = Some(match &result {
+            Ok(()) => format!("saved as '{}'", path.display()),
+            Err(err) => err.to_string(),
+        });
+        result
+    }
+
+    /// Typing at the cursor: hex digits (two per byte) or, in the ASCII
+    /// column, characters.
+    fn type_text(&mut self, text: &str) {
+        for c in text.chars() {
+            if self.ascii_focus {
+                if c.is_ascii() && !c.is_ascii_control() {
+                    self.write(self.cursor, c as u8);
+                    self.move_cursor(self.cursor + 1, false);
+                }
+                continue;
+            }
+            let Some(nibble) = c.to_digit(16) else {
+                continue;
+            };
+            match self.pending_nibble.take() {
+                None => self.pending_nibble = Some(nibble as u8),
+                Some(high) => {
+                    self.write(self.cursor, high << 4 | nibble as u8);
+                    self.move_cursor(self.cursor + 1, false);
+                }
+            }
+        }
+    }
+
+    fn on_key(&mut self, key: egui::Key, modifiers: egui::Modifiers) {
+        use egui::Key;
+        let row = BYTES_PER_ROW;
+        let page = row * VISIBLE_ROWS;
+        let cursor = self.cursor;
+        let target = match key {
+            Key::Z if modifiers.command && modifiers.shift => {
+                self.redo();
+                return;
+            }
+            Key::Z if modifiers.command => {
+                self.undo();
+                return;
+            }
+            Key::Y if modifiers.command => {
+                self.redo();
+                return;
+            }
+            Key::ArrowLeft => cursor.saturating_sub(1),
+            Key::ArrowRight => cursor + 1,
+            Key::ArrowUp => cursor.saturating_sub(row),
+            Key::ArrowDown if cursor + row <= self.last_offset() => cursor + row,
+            Key::PageUp => cursor.saturating_sub(page),
+            Key::PageDown => cursor + page,
+            Key::Home if modifiers.command => 0,
+            Key::End if modifiers.command => self.last_offset(),
+            Key::Home => cursor - cursor % row,
+            Key::End => cursor - cursor % row + row - 1,
+            _ => return,
+        };
+        self.move_cursor(target, modifiers.shift);
+    }
+
+    /// Returns `false` once the window has been closed.
+    pub fn ui(&mut self, ctx: &egui::Context) -> bool {
+        let mut open = true;
+        egui::Window::new(format!("Hex view: {}", self.name))
+            .id(egui::Id::new("hex view"))
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                self.toolbar_ui(ui);
+                if let Some(status) = &self.status {
+                    ui.label(status.as_str());
+                }
+                ui.horizontal(|ui| self.rows_ui(ui));
+                ui.separator();
+                self.selection_ui(ui);
+            });
+        open
+    }
+
+    fn toolbar_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.goto)
+                    .hint_text("offset, eg. 0x1f0")
+                    .desired_width(120.0),
+            );
+            let entered = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.button("go").clicked() || entered {
+                let goto = self.goto.clone();
+                self.goto(&goto);
+            }
+            ui.separator();
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.search)
+                    .hint_text("search")
+                    .desired_width(120.0),
+            );
+            let entered = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            ui.selectable_value(&mut self.search_mode, SearchMode::Text, "text");
+            ui.selectable_value(&mut self.search_mode, SearchMode::Hex, "hex");
+            if ui.button("find next").clicked() || entered {
+                let search = self.search.clone();
+                self.find_next(&search, self.search_mode);
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(!self.undo.is_empty(), egui::Button::new("undo"))
+                .clicked()
+            {
+                self.undo();
+            }
+            if ui
+                .add_enabled(!self.redo.is_empty(), egui::Button::new("redo"))
+                .clicked()
+            {
+                self.redo();
+            }
+            if ui
+                .add_enabled(self.is_modified(), egui::Button::new("save"))
+                .clicked()
+            {
+                let _ = self.save();
+            }
+            ui.label(format!(
+                "{} ({}), {} bytes changed",
+                format_size(self.bytes.len() as u64),
+                self.bytes.len(),
+                self.modified.len()
+            ));
+        });
+    }
+
+    fn rows_ui(&mut self, ui: &mut egui::Ui) {
+        let layout = Layout::new(ui, self.last_offset());
+        let size = layout.size();
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+        response
+            .widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Other, true, "hex view"));
+
+        if response.clicked() || response.drag_started() {
+            response.request_focus();
+        }
+        if let Some(pos) = response.interact_pointer_pos() {
+            let (offset, ascii) = layout.hit(pos - rect.min, self.top_row);
+            let shift = ui.input(|i| i.modifiers.shift);
+            if response.drag_started() || response.clicked() {
+                self.ascii_focus = ascii;
+                self.move_cursor(offset, shift);
+            } else if response.dragged() {
+                self.move_cursor(offset, true);
+            }
+        }
+        if response.has_focus() {
+            ui.memory_mut(|memory| {
+                let filter = egui::EventFilter {
+                    horizontal_arrows: true,
+                    vertical_arrows: true,
+                    ..Default::default()
+                };
+                memory.set_focus_lock_filter(response.id, filter);
+            });
+            let events = ui.input(|i| i.events.clone());
+            if !events.is_empty() {
+                // The toolbar above has already been drawn.
+                ui.ctx().request_repaint();
+            }
+            for event in events {
+                match event {
+                    egui::Event::Text(text) => self.type_text(&text),
+                    egui::Event::Key {
+                        key,
+                        pressed: true,
+                        modifiers,
+                        ..
+                    } => self.on_key(key, modifiers),
+                    _ => (),
+                }
+            }
+        }
+        let max_top_row = self.rows().saturating_sub(VISIBLE_ROWS);
+        if response.hovered() {
+            self.scroll -= ui.input(|i| i.smooth_scroll_delta.y) / layout.row_height;
+            let rows = self.scroll.trunc();
+            self.scroll -= rows;
+            let top_row = self.top_row as f64 + rows as f64;
+            self.top_row = top_row.clamp(0.0, max_top_row as f64) as usize;
+        }
+
+        // A slider (with the start at the top) instead of a scroll area, which
+        // would need the height of all rows in pixels.
+        let mut from_bottom = max_top_row - self.top_row.min(max_top_row);
+        let slider = egui::Slider::new(&mut from_bottom, 0..=max_top_row)
+            .vertical()
+            .show_value(false);
+        if ui.add_sized([16.0, size.y], slider).changed() {
+            self.top_row = max_top_row - from_bottom;
+        }
+
+        self.paint_rows(ui, &layout, rect, response.has_focus());
+    }
+
+    /// Just the rows on screen.
+    fn paint_rows(&self, ui: &egui::Ui, layout: &Layout, rect: egui::Rect, has_focus: bool) {
+        let painter = ui.painter_at(rect);
+        let visuals = ui.visuals();
+        let font_id = &layout.font_id;
+        let (char_width, row_height) = (layout.char_width, layout.row_height);
+        let selection = self.selection();
+        // The file signature, to tell apart the header from the contents.
+        let head: Vec<u8> = (0..BYTES_PER_ROW).map_while(|i| self.byte(i)).collect();
+        let signature = file_signature(&head).map_or(0..0, |(len, _)| 0..len);
+        let signature_fill = visuals.warn_fg_color.gamma_multiply(0.25);
+        let byte_color = |byte: u8| match byte {
+            0 => visuals.weak_text_color(),
+            b' '..=b'~' => visuals.text_color(),
+            0x80.. => egui::Color32::from_rgb(230, 150, 70),
+            _ => egui::Color32::from_rgb(110, 160, 255),
+        };
+
+        let rows = self.top_row..(self.top_row + VISIBLE_ROWS).min(self.rows());
+        for (screen_row, row) in rows.enumerate() {
+            let y = rect.top() + screen_row as f32 * row_height;
+            let start = row * BYTES_PER_ROW;
+            painter.text(
+                egui::pos2(rect.left(), y),
+                egui::Align2::LEFT_TOP,
+                format!("{start:0width$x}", width = layout.offset_digits),
+                font_id.clone(),
+                visuals.weak_text_color(),
+            );
+            let end = (start + BYTES_PER_ROW).min(self.bytes.len());
+            for (i, offset) in (start..end).enumerate() {
+                let byte = self.byte(offset).unwrap_or_default();
+                let hex_pos = egui::pos2(rect.left() + layout.byte_x(i), y);
+                let ascii_x = rect.left() + layout.ascii_column + i as f32 * char_width;
+                let ascii_pos = egui::pos2(ascii_x, y);
+                let hex_rect =
+                    egui::Rect::from_min_size(hex_pos, egui::vec2(2.0 * char_width, row_height));
+                let ascii_rect =
+                    egui::Rect::from_min_size(ascii_pos, egui::vec2(char_width, row_height));
+                let fill = if selection.contains(&offset) {
+                    Some(visuals.selection.bg_fill)
+                } else if signature.contains(&offset) {
+                    Some(signature_fill)
+                } else {
+                    None
+                };
+                if let Some(fill) = fill {
+                    let gap = egui::vec2(char_width / 2.0, 0.0);
+                    painter.rect_filled(hex_rect.expand2(gap), 0.0, fill);
+                    painter.rect_filled(ascii_rect, 0.0, fill);
+                }
+                if offset == self.cursor && has_focus {
+                    let (typing, other) = if self.ascii_focus {
+                        (ascii_rect, hex_rect)
+                    } else {
+                        (hex_rect, ascii_rect)
+                    };
+                    painter.rect_stroke(typing, 0.0, (1.0, visuals.text_color()));
+                    painter.rect_stroke(other, 0.0, (1.0, visuals.weak_text_color()));
+                }
+                let color = if self.modified.contains_key(&offset) {
+                    visuals.warn_fg_color
+                } else {
+                    byte_color(byte)
+                };
+                let hex = match self.pending_nibble {
+                    Some(high) if offset == self.cursor => format!("{high:x}_"),
+                    _ => format!("{byte:02x}"),
+                };
+                painter.text(hex_pos, egui::Align2::LEFT_TOP, hex, font_id.clone(), color);
+                let c = if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                };
+                painter.text(ascii_pos, egui::Align2::LEFT_TOP, c, font_id.clone(), color);
+            }
+        }
+    }
+
+    fn selection_ui(&self, ui: &mut egui::Ui) {
+        if self.bytes.is_empty() {
+            ui.weak("empty file");
+            return;
+        }
+        let selection = self.selection();
+        ui.label(format!(
+            "selected {:#x}..{:#x} ({} bytes)",
+            selection.start,
+            selection.end,
+            selection.len()
+        ));
+        egui::Grid::new("interpretation")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("type");
+                ui.strong("little endian");
+                ui.strong("big endian");
+                ui.end_row();
+                // A single byte selected is where to start reading numbers.
+                let end = match selection.len() {
+                    1 => self.bytes.len(),
+                    _ => selection.end,
+                };
+                // Enough for the widest number.
+                let end = end.min(selection.start + 8);
+                let bytes: Vec<u8> = (selection.start..end).map_while(|i| self.byte(i)).collect();
+                for (ty, little, big) in interpret(&bytes) {
+                    ui.label(ty);
+                    ui.monospace(little);
+                    ui.monospace(big);
+                    ui.end_row();
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn viewer(bytes: &[u8]) -> HexViewer {
+        HexViewer::new(&DroppedFile::new("test.bin", bytes))
+    }
+
+    #[test]
+    fn patterns_and_numbers() {
+        assert_eq!(
+            parse_pattern("89 50 4E47", SearchMode::Hex),
+            Some(vec![0x89, 0x50, 0x4e, 0x47])
+        );
+        assert_eq!(parse_pattern("895", SearchMode::Hex), None);
+        assert_eq!(parse_pattern("zz", SearchMode::Hex), None);
+        assert_eq!(
+            parse_pattern("PNG", SearchMode::Text),
+            Some(b"PNG".to_vec())
+        );
+        assert_eq!(parse_pattern("", SearchMode::Text), None);
+
+        let values = interpret(&[0x01, 0x02, 0x03, 0x04]);
+        let value = |ty| values.iter().find(|(name, ..)| *name == ty).cloned();
+        assert_eq!(
+            value("u16"),
+            Some(("u16", "513".to_string(), "258".to_string()))
+        );
+        assert_eq!(
+            value("i32"),
+            Some(("i32", "67305985".to_string(), "16909060".to_string()))
+        );
+        assert!(value("u64").is_none(), "only what fits");
+        let values = interpret(&1.5f32.to_be_bytes());
+        assert_eq!(
+            values.iter().find(|(name, ..)| *name == "f32").unwrap().2,
+            "1.5"
+        );
+    }
+
+    #[test]
+    fn find_and_goto() {
+        let mut viewer = viewer(b"abc PNG abc PNG");
+        assert_eq!(viewer.find_next("PNG", SearchMode::Text), Some(4));
+        assert_eq!(viewer.selection(), 4..7);
+        assert_eq!(viewer.find_next("50 4e", SearchMode::Hex), Some(12));
+        // Starts over at the beginning.
+        assert_eq!(viewer.find_next("PNG", SearchMode::Text), Some(4));
+        assert_eq!(viewer.find_next("xyz", SearchMode::Text), None);
+        assert_eq!(viewer.status(), Some("not found"));
+
+        // In the edited bytes.
+        viewer.write(8, b'x');
+        assert_eq!(viewer.find_next("xbc", SearchMode::Text), Some(8));
+        assert!(viewer.goto("1"));
+        assert_eq!(viewer.find_next("abc PNG", SearchMode::Text), Some(0));
+        viewer.undo();
+
+        assert!(viewer.goto("0xa"));
+        assert_eq!(viewer.cursor(), 10);
+        assert!(viewer.goto("3"));
+        assert_eq!(viewer.selection(), 3..4);
+        assert!(!viewer.goto("100"));
+        assert!(!viewer.goto("nowhere"));
+    }
+
+    #[test]
+    fn large_files_scroll_by_rows() {
+        let mut viewer = HexViewer::new(&DroppedFile::from_bytes(
+            "large.bin".to_string(),
+            vec![0; 64 << 20],
+        ));
+        assert!(viewer.goto("0x3fffff0"));
+        assert_eq!(viewer.cursor(), 0x3ff_fff0);
+        let last_row = 0x3ff_fff0 / 16;
+        assert!(viewer.top_row() <= last_row && last_row < viewer.top_row() + 24);
+        viewer.set_cursor(0);
+        assert_eq!(viewer.top_row(), 0);
+    }
+
+    #[test]
+    fn edits_can_be_undone_and_saved() {
+        let dir = std::env::temp_dir()
+            .join(format!("wasm_winit_wgpu-{}", std::process::id()))
+            .join("hex_view");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("edit_me.bin");
+        std::fs::write(&path, b"0123").unwrap();
+        let file = DroppedFile::from_path(path.clone(), b"0123".to_vec());
+        let mut viewer = HexViewer::new(&file);
+
+        viewer.write(1, b'x');
+        viewer.write(2, b'y');
+        assert_eq!(&*viewer.bytes(), b"0xy3");
+        assert!(viewer.is_modified());
+        assert!(viewer.undo());
+        assert_eq!(&*viewer.bytes(), b"0x23");
+        assert!(viewer.redo());
+        assert_eq!(&*viewer.bytes(), b"0xy3");
+        // Writing back the original value is no change at all.
+        viewer.write(2, b'2');
+        viewer.write(1, b'1');
+        assert!(!viewer.is_modified());
+        assert!(!viewer.redo());
+        assert!(matches!(viewer.bytes(), Cow::Borrowed(_)));
+        assert!(Arc::ptr_eq(&viewer.bytes, &file.bytes), "not copied");
+
+        viewer.write(0, b'A');
+        assert_eq!(viewer.save_path(), dir.join("edit_me.edited.bin"));
+        viewer.save().unwrap();
+        assert_eq!(std::fs::read(viewer.save_path()).unwrap(), b"A123");
+        assert_eq!(std::fs::read(&path).unwrap(), b"0123", "the original stays");
+    }
+}
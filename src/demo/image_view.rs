@@ -0,0 +1,557 @@
+//
+// The demo's image viewer: a dropped image as wgpu textures, to pan, zoom and
+// pick pixel colors from
+//
+// Images are decoded off the UI thread, see `ImageDecoding`. The decoded
+// pixels stay on the CPU for the pixel readout; on the GPU the image is cut
+// into tiles that each fit into one texture, so images larger than
+// `max_texture_dimension_2d` are shown at full resolution too.
+//
+
+use crate::*;
+use image::ImageFormat;
+use std::sync::Mutex;
+
+/// The formats the viewer decodes.
+const FORMATS: [ImageFormat; 6] = [
+    ImageFormat::Png,
+    ImageFormat::Jpeg,
+    ImageFormat::WebP,
+    ImageFormat::Bmp,
+    ImageFormat::Gif,
+    ImageFormat::Tga,
+];
+
+/// Size of the image area, in points.
+const VIEW_SIZE: egui::Vec2 = egui::vec2(512.0, 384.0);
+const MIN_ZOOM: f32 = 1.0 / 64.0;
+const MAX_ZOOM: f32 = 256.0;
+/// From this zoom (points per image pixel) on, a grid outlines the pixels.
+const PIXEL_GRID_ZOOM: f32 = 8.0;
+/// EXIF values are cut off after this many characters.
+const MAX_EXIF_VALUE_LEN: usize = 80;
+
+/// The format of `file`, judged by its contents; TGA files have no file
+/// signature and are told by their name.
+fn image_format(file: &DroppedFile) -> Option<ImageFormat> {
+    let format = image::guess_format(&file.bytes)
+        .or_else(|_| ImageFormat::from_path(&file.name))
+        .ok()?;
+    FORMATS.contains(&format).then_some(format)
+}
+
+/// Whether [`ImageViewer`] can show `file`.
+pub fn is_viewable_image(file: &DroppedFile) -> bool {
+    image_format(file).is_some()
+}
+
+/// Which part of the color is shown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    /// The image as it is.
+    Color,
+    /// Just one channel, as shades of gray.
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+impl Channel {
+    pub const ALL: [Self; 5] = [Self::Color, Self::Red, Self::Green, Self::Blue, Self::Alpha];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Color => "color",
+            Self::Red => "red",
+            Self::Green => "green",
+            Self::Blue => "blue",
+            Self::Alpha => "alpha",
+        }
+    }
+
+    /// How an (unmultiplied) `pixel` is shown, premultiplied the way egui
+    /// expects its textures.
+    fn shown(self, [r, g, b, a]: [u8; 4]) -> [u8; 4] {
+        let gray = |value| [value, value, value, 255];
+        match self {
+            Self::Color => egui::Color32::from_rgba_unmultiplied(r, g, b, a).to_array(),
+            Self::Red => gray(r),
+            Self::Green => gray(g),
+            Self::Blue => gray(b),
+            Self::Alpha => gray(a),
+        }
+    }
+}
+
+/// One texture holding the pixels from `origin` on.
+struct Tile {
+    origin: [u32; 2],
+    size: [u32; 2],
+    texture: Texture,
+    view: TextureView,
+    id: egui::TextureId,
+    filter: FilterMode,
+}
+
+/// A window showing a dropped image.
+pub struct ImageViewer {
+    name: String,
+    format: ImageFormat,
+    image: image::RgbaImage,
+    /// Tag and value of the EXIF fields of the main image.
+    exif: Vec<(String, String)>,
+    channel: Channel,
+    /// Screen points per image pixel.
+    zoom: f32,
+    /// The position in the image (in pixels) at the center of the view.
+    center: egui::Pos2,
+    /// The pixel under the pointer.
+    hovered: Option<[u32; 2]>,
+    tiles: Vec<Tile>,
+    /// See [`NativeTextures::generation`]; `None` while there are no tiles.
+    generation: Option<u64>,
+    max_tile_side: u32,
+    /// The tiles have to be cut anew (the tile size changed).
+    retile: bool,
+    /// The pixels in the tiles are out of date (the channel changed).
+    stale: bool,
+}
+impl ImageViewer {
+    /// Decodes `file`; of a GIF just the first frame.
+    pub fn new(file: &DroppedFile) -> Result<Self> {
+        let format = image_format(file).ok_or_else(|| {
+            let hint = image::error::ImageFormatHint::Name(file.name.clone());
+            Error::Image(image::ImageError::Unsupported(hint.into()))
+        })?;
+        let image = image::load_from_memory_with_format(&file.bytes, format)
+            .map_err(Error::Image)?
+            .to_rgba8();
+        let mut viewer = Self {
+            name: file.name.clone(),
+            format,
+            image,
+            exif: read_exif(&file.bytes),
+            channel: Channel::Color,
+            zoom: 1.0,
+            center: egui::Pos2::ZERO,
+            hovered: None,
+            tiles: Vec::new(),
+            generation: None,
+            max_tile_side: u32::MAX,
+            retile: false,
+            stale: false,
+        };
+        viewer.fit();
+        Ok(viewer)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Width and height in pixels.
+    pub fn size(&self) -> [u32; 2] {
+        [self.image.width(), self.image.height()]
+    }
+    /// The color of the pixel at `x`, `y` as RGBA, not premultiplied.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        let pixel = self.image.get_pixel_checked(x, y)?;
+        Some(pixel.0)
+    }
+    pub fn exif(&self) -> &[(String, String)] {
+        &self.exif
+    }
+    pub fn hovered(&self) -> Option<[u32; 2]> {
+        self.hovered
+    }
+
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+    pub fn set_channel(&mut self, channel: Channel) {
+        self.stale |= self.channel != channel;
+        self.channel = channel;
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+    /// The position in the image (in pixels) at `offset` points from the
+    /// center of the view.
+    pub fn image_pos(&self, offset: egui::Vec2) -> egui::Pos2 {
+        self.center + offset / self.zoom
+    }
+    /// Zooms in (or out, for a `factor` below 1) while the spot at `offset`
+    /// points from the center of the view stays where it is, like under the
+    /// mouse wheel.
+    pub fn zoom_around(&mut self, factor: f32, offset: egui::Vec2) {
+        let fixed = self.image_pos(offset);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.center = fixed - offset / self.zoom;
+        self.clamp_center();
+    }
+    /// Shows the whole image, as large as it fits.
+    pub fn fit(&mut self) {
+        let size = egui::vec2(self.image.width() as f32, self.image.height() as f32);
+        let zoom = (VIEW_SIZE / size.max(egui::Vec2::splat(1.0))).min_elem();
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        self.center = (size / 2.0).to_pos2();
+    }
+    /// Keeps some of the image in view.
+    fn clamp_center(&mut self) {
+        let max = egui::pos2(self.image.width() as f32, self.image.height() as f32);
+        self.center = self.center.clamp(egui::Pos2::ZERO, max);
+    }
+
+    /// Limits the tiles to less than the device allows, eg. to try out tiling
+    /// with small images.
+    pub fn set_max_tile_side(&mut self, side: u32) {
+        self.retile |= self.max_tile_side != side;
+        self.max_tile_side = side.max(1);
+    }
+    /// How many textures the image is cut into; 0 until the first frame.
+    pub fn tile_count(&self) -> usize {
+        self.tiles.len()
+    }
+
+    /// Uploads the image, once per egui renderer; see
+    /// [`AppLogic::update_textures`].
+    pub fn update_textures(&mut self, gpu: &GpuState, textures: &mut NativeTextures<'_>) {
+        if self.retile {
+            self.free_textures(textures);
+            self.retile = false;
+        }
+        let filter = self.filter();
+        if self.generation != Some(textures.generation()) {
+            // A new renderer does not know the old textures any more.
+            let side = textures.max_texture_side().min(self.max_tile_side);
+            self.tiles = self.create_tiles(gpu, textures, side, filter);
+            self.generation = Some(textures.generation());
+            self.stale = false;
+        }
+        if self.stale {
+            for tile in &self.tiles {
+                self.write_tile(gpu, tile);
+            }
+            self.stale = false;
+        }
+        for tile in &mut self.tiles {
+            if tile.filter != filter {
+                textures.update(tile.id, &tile.view, filter);
+                tile.filter = filter;
+            }
+        }
+    }
+
+    /// Crisp pixels when zoomed in, less aliasing when zoomed out.
+    fn filter(&self) -> FilterMode {
+        if self.zoom >= 1.0 {
+            FilterMode::Nearest
+        } else {
+            FilterMode::Linear
+        }
+    }
+
+    /// Whether [`ImageViewer::update_textures`] has work to do.
+    fn needs_texture_update(&self) -> bool {
+        self.generation.is_none()
+            || self.retile
+            || self.stale
+            || self.tiles.iter().any(|tile| tile.filter != self.filter())
+    }
+
+    /// Gives the textures back to the renderer, eg. when the viewer closes.
+    pub fn free_textures(&mut self, textures: &mut NativeTextures<'_>) {
+        let tiles = std::mem::take(&mut self.tiles);
+        if self.generation.take() == Some(textures.generation()) {
+            for tile in tiles {
+                textures.free(tile.id);
+            }
+        }
+    }
+
+    fn create_tiles(
+        &self,
+        gpu: &GpuState,
+        textures: &mut NativeTextures<'_>,
+        side: u32,
+        filter: FilterMode,
+    ) -> Vec<Tile> {
+        let (width, height) = self.image.dimensions();
+        let mut tiles = Vec::new();
+        for y in (0..height).step_by(side as usize) {
+            for x in (0..width).step_by(side as usize) {
+                let size = [side.min(width - x), side.min(height - y)];
+                let texture = gpu.device().create_texture(&TextureDescriptor {
+                    label: Some("image tile"),
+                    size: Extent3d {
+                        width: size[0],
+                        height: size[1],
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::Rgba8UnormSrgb,
+                    usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                    view_formats: &[],
+                });
+                let view = texture.create_view(&TextureViewDescriptor::default());
+                let id = textures.register(&view, filter);
+                let tile = Tile {
+                    origin: [x, y],
+                    size,
+                    texture,
+                    view,
+                    id,
+                    filter,
+                };
+                self.write_tile(gpu, &tile);
+                tiles.push(tile);
+            }
+        }
+        tiles
+    }
+
+    fn write_tile(&self, gpu: &GpuState, tile: &Tile) {
+        let [x0, y0] = tile.origin;
+        let [width, height] = tile.size;
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in y0..y0 + height {
+            for x in x0..x0 + width {
+                pixels.extend(self.channel.shown(self.image.get_pixel(x, y).0));
+            }
+        }
+        gpu.queue().write_texture(
+            ImageCopyTexture {
+                texture: &tile.texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            &pixels,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 4),
+                rows_per_image: Some(height),
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Shows the viewer window; returns `false` once it was closed.
+    pub fn ui(&mut self, ctx: &egui::Context) -> bool {
+        let mut open = true;
+        egui::Window::new(format!("Image: {}", self.name))
+            .id(egui::Id::new("image view"))
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                self.toolbar_ui(ui);
+                self.image_ui(ui);
+                self.pixel_ui(ui);
+                ui.separator();
+                self.exif_ui(ui);
+            });
+        if self.needs_texture_update() {
+            ctx.request_repaint();
+        }
+        open
+    }
+
+    fn toolbar_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let mut channel = self.channel;
+            for option in Channel::ALL {
+                ui.selectable_value(&mut channel, option, option.name());
+            }
+            self.set_channel(channel);
+            ui.separator();
+            if ui.button("fit").clicked() {
+                self.fit();
+            }
+            if ui.button("1:1").clicked() {
+                self.zoom_around(1.0 / self.zoom, egui::Vec2::ZERO);
+            }
+            ui.label(format!("{:.0}%", self.zoom * 100.0));
+        });
+        let [width, height] = self.size();
+        ui.label(format!(
+            "{width} × {height} px, {:?}, in {} texture(s)",
+            self.format,
+            self.tiles.len()
+        ));
+    }
+
+    fn image_ui(&mut self, ui: &mut egui::Ui) {
+        let (rect, response) = ui.allocate_exact_size(VIEW_SIZE, egui::Sense::click_and_drag());
+        response
+            .widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Other, true, "image view"));
+        if response.dragged() {
+            self.center -= response.drag_delta() / self.zoom;
+            self.clamp_center();
+        }
+        if response.double_clicked() {
+            self.fit();
+        }
+        self.hovered = None;
+        if let Some(pointer) = response.hover_pos() {
+            let (scroll, zoom) = ui.input(|i| (i.smooth_scroll_delta.y, i.zoom_delta()));
+            let factor = zoom * (scroll / 200.0).exp();
+            if factor != 1.0 {
+                self.zoom_around(factor, pointer - rect.center());
+            }
+            let pos = self.image_pos(pointer - rect.center());
+            if pos.x >= 0.0 && pos.y >= 0.0 {
+                let [x, y] = [pos.x as u32, pos.y as u32];
+                if x < self.image.width() && y < self.image.height() {
+                    self.hovered = Some([x, y]);
+                }
+            }
+        }
+
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+        let to_screen = |x: u32, y: u32| {
+            rect.center() + (egui::pos2(x as f32, y as f32) - self.center) * self.zoom
+        };
+        let uv = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0));
+        for tile in &self.tiles {
+            let [x, y] = tile.origin;
+            let [width, height] = tile.size;
+            let tile_rect =
+                egui::Rect::from_min_max(to_screen(x, y), to_screen(x + width, y + height));
+            if tile_rect.intersects(rect) {
+                painter.image(tile.id, tile_rect, uv, egui::Color32::WHITE);
+            }
+        }
+
+        if self.zoom >= PIXEL_GRID_ZOOM {
+            // Just the lines in view.
+            let (width, height) = self.image.dimensions();
+            let min = self.image_pos(rect.min - rect.center());
+            let max = self.image_pos(rect.max - rect.center());
+            let [x0, y0] = [min.x.max(0.0) as u32, min.y.max(0.0) as u32];
+            let x1 = (max.x.ceil().max(0.0) as u32).min(width);
+            let y1 = (max.y.ceil().max(0.0) as u32).min(height);
+            let stroke = egui::Stroke::new(1.0, egui::Color32::from_gray(128).gamma_multiply(0.5));
+            for x in x0..=x1 {
+                painter.line_segment([to_screen(x, y0), to_screen(x, y1)], stroke);
+            }
+            for y in y0..=y1 {
+                painter.line_segment([to_screen(x0, y), to_screen(x1, y)], stroke);
+            }
+        }
+        if let Some([x, y]) = self.hovered {
+            let pixel = egui::Rect::from_min_max(to_screen(x, y), to_screen(x + 1, y + 1));
+            painter.rect_stroke(pixel, 0.0, ui.visuals().selection.stroke);
+        }
+    }
+
+    fn pixel_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let (swatch, _) = ui.allocate_exact_size(egui::vec2(16.0, 16.0), egui::Sense::hover());
+            let Some([x, y]) = self.hovered else {
+                ui.label("hover the image for pixel colors");
+                return;
+            };
+            let [r, g, b, a] = self.image.get_pixel(x, y).0;
+            let color = egui::Color32::from_rgba_unmultiplied(r, g, b, a);
+            ui.painter().rect_filled(swatch, 2.0, color);
+            ui.label(format!(
+                "pixel {x}, {y}: #{r:02x}{g:02x}{b:02x}{a:02x} ({r}, {g}, {b}, {a})"
+            ));
+        });
+    }
+
+    fn exif_ui(&mut self, ui: &mut egui::Ui) {
+        if self.exif.is_empty() {
+            ui.label("no EXIF metadata");
+            return;
+        }
+        egui::CollapsingHeader::new(format!("EXIF metadata ({} fields)", self.exif.len())).show(
+            ui,
+            |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(160.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("exif").striped(true).show(ui, |ui| {
+                            for (tag, value) in &self.exif {
+                                ui.label(tag.as_str());
+                                ui.label(value.as_str());
+                                ui.end_row();
+                            }
+                        });
+                    });
+            },
+        );
+    }
+}
+
+/// A dropped image being decoded for an [`ImageViewer`]: natively on a
+/// background thread, on the web once the browser has drawn a frame (there
+/// are no threads to spare, but "decoding…" is shown meanwhile).
+pub struct ImageDecoding {
+    name: String,
+    result: Arc<Mutex<Option<Result<ImageViewer>>>>,
+}
+impl ImageDecoding {
+    pub fn spawn(file: &DroppedFile) -> Self {
+        let result = Arc::new(Mutex::new(None));
+        let decoding = Self {
+            name: file.name.clone(),
+            result: result.clone(),
+        };
+        let decode = {
+            let file = file.clone();
+            move || *result.lock().unwrap() = Some(ImageViewer::new(&file))
+        };
+        #[cfg(not(target_family = "wasm"))]
+        {
+            let spawned = std::thread::Builder::new()
+                .name("image decoder".to_string())
+                .spawn(decode);
+            if let Err(err) = spawned {
+                log::warn!("cannot start an image decoder thread, decoding right away: {err}");
+                *decoding.result.lock().unwrap() = Some(ImageViewer::new(file));
+            }
+        }
+        #[cfg(target_family = "wasm")]
+        wasm_bindgen_futures::spawn_local(async move {
+            file_read::sleep(0).await;
+            decode();
+        });
+        decoding
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// The viewer, or why there is none, once decoding is done.
+    pub fn take_result(&mut self) -> Option<Result<ImageViewer>> {
+        self.result.lock().unwrap().take()
+    }
+}
+
+/// The EXIF fields of the main image (not of its thumbnail) in `bytes`, if
+/// there are any.
+fn read_exif(bytes: &[u8]) -> Vec<(String, String)> {
+    let mut cursor = std::io::Cursor::new(bytes);
+    let Ok(exif) = exif::Reader::new().read_from_container(&mut cursor) else {
+        return Vec::new();
+    };
+    exif.fields()
+        .filter(|field| field.ifd_num == exif::In::PRIMARY)
+        .map(|field| {
+            let mut value = field.display_value().with_unit(&exif).to_string();
+            if let Some((end, _)) = value.char_indices().nth(MAX_EXIF_VALUE_LEN) {
+                value.truncate(end);
+                value.push('…');
+            }
+            (field.tag.to_string(), value)
+        })
+        .collect()
+}
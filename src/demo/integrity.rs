@@ -0,0 +1,319 @@
+//
+// The demo's integrity panel: checksums of the dropped files, checked against
+// pasted hashes and dropped checksum lists
+//
+// Files that are assembled (see `AppConfig::max_assembled_file_size`) are
+// added whole once dropped; larger ones are hashed chunk by chunk as they are
+// read, from their `FileEvent`s.
+//
+
+use crate::*;
+
+/// A dropped file and its checksums, computed in the background.
+struct FileChecksums {
+    /// Tells apart files of the same name.
+    id: u64,
+    name: String,
+    /// Kept to compute again when other algorithms are picked; `None` for
+    /// files too large to keep.
+    bytes: Option<Arc<[u8]>>,
+    /// While the chunks of a file too large to keep arrive.
+    reading: Option<FileId>,
+    task: ChecksumTask,
+}
+
+/// Checksums of every dropped file, to verify eg. build artifacts against
+/// the hashes published with them.
+pub struct IntegrityPanel {
+    /// The ones computed and shown, in [`HashAlgorithm::ALL`] order.
+    algorithms: Vec<HashAlgorithm>,
+    files: Vec<FileChecksums>,
+    next_id: u64,
+    /// From dropped checksum lists, each with the name of its list.
+    expected: Vec<(String, ExpectedChecksum)>,
+    pasted: String,
+}
+impl IntegrityPanel {
+    pub fn new() -> Self {
+        Self {
+            algorithms: HashAlgorithm::ALL.to_vec(),
+            files: Vec::new(),
+            next_id: 0,
+            expected: Vec::new(),
+            pasted: String::new(),
+        }
+    }
+
+    pub fn algorithms(&self) -> &[HashAlgorithm] {
+        &self.algorithms
+    }
+    /// Computes the checksums of all files again if that changes which
+    /// algorithms are used.
+    pub fn set_algorithm(&mut self, algorithm: HashAlgorithm, enabled: bool) {
+        if self.algorithms.contains(&algorithm) == enabled {
+            return;
+        }
+        self.algorithms = HashAlgorithm::ALL
+            .into_iter()
+            .filter(|candidate| {
+                if *candidate == algorithm {
+                    enabled
+                } else {
+                    self.algorithms.contains(candidate)
+                }
+            })
+            .collect();
+        for file in &mut self.files {
+            if let Some(bytes) = &file.bytes {
+                file.task = ChecksumTask::spawn(bytes.clone(), &self.algorithms);
+            }
+        }
+    }
+
+    /// Starts computing the checksums of `file` and returns what to look
+    /// them up by, or if it is a checksum list remembers what it expects of
+    /// other files.
+    pub fn add(&mut self, file: &DroppedFile) -> Option<u64> {
+        if is_checksum_list(&file.name) {
+            let text = String::from_utf8_lossy(&file.bytes);
+            let expected = parse_checksum_list(&file.name, &text);
+            if expected.is_empty() {
+                log::warn!("'{}' holds no checksums", file.name);
+            }
+            self.expected.extend(
+                expected
+                    .into_iter()
+                    .map(|expected| (file.name.clone(), expected)),
+            );
+            return None;
+        }
+        let task = ChecksumTask::spawn(file.bytes.clone(), &self.algorithms);
+        Some(self.push(&file.name, Some(file.bytes.clone()), None, task))
+    }
+
+    /// Hashes the chunks of files too large to be added whole, as they are
+    /// read; returns the id of their checksums when they start.
+    pub fn on_file_event(&mut self, event: &FileEvent) -> Option<u64> {
+        if let FileEvent::Started {
+            id,
+            name,
+            len,
+            assembled,
+            ..
+        } = event
+        {
+            if *assembled {
+                return None;
+            }
+            // Natively files are named by their path.
+            let path = std::path::Path::new(name);
+            let name = path
+                .file_name()
+                .map_or(name.clone(), |name| name.to_string_lossy().into_owned());
+            let task = ChecksumTask::fed(*len, &self.algorithms);
+            return Some(self.push(&name, None, Some(*id), task));
+        }
+        let file = self
+            .files
+            .iter_mut()
+            .find(|file| file.reading == Some(event.id()))?;
+        match event {
+            FileEvent::Started { .. } => (),
+            FileEvent::Chunk(chunk) => file.task.update(&chunk.bytes),
+            FileEvent::Finished { .. } => file.task.finish(),
+            FileEvent::Cancelled { .. } => file.task.fail("reading was cancelled".to_string()),
+            FileEvent::Failed { error, .. } => file.task.fail(error.to_string()),
+        }
+        if !matches!(event, FileEvent::Chunk(_)) {
+            file.reading = None;
+        }
+        None
+    }
+
+    fn push(
+        &mut self,
+        name: &str,
+        bytes: Option<Arc<[u8]>>,
+        reading: Option<FileId>,
+        task: ChecksumTask,
+    ) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.files.push(FileChecksums {
+            id,
+            name: name.to_string(),
+            bytes,
+            reading,
+            task,
+        });
+        id
+    }
+
+    /// `None` while they are being computed, or for unknown files.
+    pub fn checksums(&self, id: u64) -> Option<Vec<Checksum>> {
+        let file = self.files.iter().find(|file| file.id == id)?;
+        file.task.checksums()
+    }
+    pub fn is_busy(&self) -> bool {
+        self.files.iter().any(|file| !file.task.is_done())
+    }
+
+    pub fn set_pasted(&mut self, pasted: &str) {
+        self.pasted = pasted.to_string();
+    }
+
+    /// Forgets the checksums `add` or `on_file_event` returned `id` for.
+    pub fn remove(&mut self, id: u64) {
+        self.files.retain(|file| file.id != id);
+    }
+
+    pub fn clear(&mut self) {
+        self.files.clear();
+        self.expected.clear();
+    }
+
+    /// What the checksum lists say about the file called `name`, given its
+    /// `checksums`.
+    fn verify(&self, name: &str, checksums: &[Checksum]) -> Vec<(bool, String)> {
+        self.expected
+            .iter()
+            .filter(|(_, expected)| expected.is_for(name))
+            .map(|(list, expected)| {
+                match checksums.iter().find(|checksum| expected.matches(checksum)) {
+                    Some(checksum) => (true, format!("matches {list} ({})", checksum.algorithm)),
+                    None => (false, format!("does not match {list}")),
+                }
+            })
+            .collect()
+    }
+
+    /// What the pasted hash matches, if anything was pasted.
+    fn pasted_verdict(&self) -> Option<(bool, String)> {
+        if self.pasted.trim().is_empty() {
+            return None;
+        }
+        // Also takes a line of `sha256sum` output.
+        let Some(expected) = parse_checksum_list("", &self.pasted).into_iter().next() else {
+            return Some((false, "not a CRC32, MD5, SHA-1 or 256 bit hash".to_string()));
+        };
+        let mut candidates = self
+            .files
+            .iter()
+            .filter(|file| expected.name.is_none() || expected.is_for(&file.name));
+        let found = candidates.find_map(|file| {
+            let checksums = file.task.checksums()?;
+            let checksum = checksums
+                .into_iter()
+                .find(|checksum| expected.matches(checksum))?;
+            Some((checksum.algorithm, file.name.as_str()))
+        });
+        Some(match found {
+            Some((algorithm, name)) => (true, format!("matches the {algorithm} of {name}")),
+            None => (false, "matches none of the files".to_string()),
+        })
+    }
+
+    pub fn ui(&mut self, ctx: &egui::Context) {
+        if self.files.is_empty() && self.expected.is_empty() {
+            return;
+        }
+        if self.is_busy() {
+            // Nothing else wakes the event loop when the hashing is done.
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+        egui::Window::new("File integrity")
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    for algorithm in HashAlgorithm::ALL {
+                        let mut enabled = self.algorithms.contains(&algorithm);
+                        if ui.checkbox(&mut enabled, algorithm.name()).changed() {
+                            self.set_algorithm(algorithm, enabled);
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.pasted)
+                            .hint_text("paste an expected hash")
+                            .desired_width(300.0),
+                    );
+                    if ui.button("clear").clicked() {
+                        self.clear();
+                    }
+                });
+                let verdict_label = |ui: &mut egui::Ui, (ok, text): (bool, String)| {
+                    let color = if ok {
+                        ui.visuals().selection.stroke.color
+                    } else {
+                        ui.visuals().error_fg_color
+                    };
+                    ui.colored_label(color, text);
+                };
+                if let Some(verdict) = self.pasted_verdict() {
+                    verdict_label(ui, verdict);
+                }
+                if !self.expected.is_empty() {
+                    ui.label(format!(
+                        "{} expected checksums from checksum lists",
+                        self.expected.len()
+                    ));
+                }
+
+                egui::ScrollArea::vertical()
+                    .max_height(320.0)
+                    .show(ui, |ui| {
+                        for file in &self.files {
+                            ui.separator();
+                            ui.strong(file.name.as_str());
+                            if let Some(error) = file.task.error() {
+                                ui.colored_label(
+                                    ui.visuals().error_fg_color,
+                                    format!("cannot compute checksums: {error}"),
+                                );
+                                continue;
+                            }
+                            let Some(checksums) = file.task.checksums() else {
+                                ui.add(
+                                    egui::ProgressBar::new(file.task.progress())
+                                        .desired_width(200.0)
+                                        .text("computing checksums"),
+                                );
+                                continue;
+                            };
+                            egui::Grid::new(("checksums", file.id)).show(ui, |ui| {
+                                for checksum in &checksums {
+                                    ui.label(checksum.algorithm.name());
+                                    ui.monospace(checksum.hex.as_str());
+                                    if ui.small_button("copy").clicked() {
+                                        ui.output_mut(|output| {
+                                            output.copied_text = checksum.hex.clone();
+                                        });
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                            let is_stale = checksums
+                                .iter()
+                                .map(|checksum| checksum.algorithm)
+                                .ne(self.algorithms.iter().copied());
+                            if file.bytes.is_none() {
+                                ui.weak(if is_stale {
+                                    "too large to keep, drop it again for other algorithms"
+                                } else {
+                                    "too large to keep for the file manager and viewers"
+                                });
+                            }
+                            for verdict in self.verify(&file.name, &checksums) {
+                                verdict_label(ui, verdict);
+                            }
+                        }
+                    });
+            });
+    }
+}
+impl Default for IntegrityPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,189 @@
+//
+// The drop-zone overlay shown while files are dragged over the app
+//
+// Natively egui-winit collects the hovered files from winit's `HoveredFile`
+// events, on the web the drag and drop listeners send them as
+// `UserEvent::HoveredFiles`; either way they end up in egui's input, which is
+// all the overlay looks at.
+//
+
+/// A kind of file the app can open. While files are dragged, natively their
+/// paths are known and on the web only their MIME types, so a file type
+/// should list both.
+#[derive(Clone, Debug)]
+pub struct FileType {
+    pub name: String,
+    /// Lower case, without the dot.
+    pub extensions: Vec<String>,
+    /// eg. `"image/png"`, or a whole family as `"image/*"`.
+    pub mime_types: Vec<String>,
+}
+impl FileType {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            extensions: Vec::new(),
+            mime_types: Vec::new(),
+        }
+    }
+    pub fn extension(mut self, extension: &str) -> Self {
+        self.extensions.push(extension.to_lowercase());
+        self
+    }
+    pub fn mime_type(mut self, mime_type: &str) -> Self {
+        self.mime_types.push(mime_type.to_string());
+        self
+    }
+
+    fn matches(&self, file: &egui::HoveredFile) -> bool {
+        let extension = file
+            .path
+            .as_deref()
+            .and_then(|path| path.extension())
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        let matches_extension =
+            extension.is_some_and(|extension| self.extensions.contains(&extension));
+        let matches_mime_type = !file.mime.is_empty()
+            && self
+                .mime_types
+                .iter()
+                .any(|mime_type| match mime_type.strip_suffix("/*") {
+                    Some(family) => file
+                        .mime
+                        .split_once('/')
+                        .is_some_and(|(file_family, _)| file_family == family),
+                    None => *mime_type == file.mime,
+                });
+        matches_extension || matches_mime_type
+    }
+}
+
+/// Whether the app can open a file that is being dragged over it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileAcceptance {
+    Accepted,
+    Rejected,
+    /// The browser does not know the type before the file is dropped.
+    Unknown,
+}
+
+/// The file types registered with the app, see
+/// [`AppConfig::file_types`](crate::AppConfig::file_types).
+/// Without any, every file is accepted.
+#[derive(Clone, Debug, Default)]
+pub struct FileTypes(Vec<FileType>);
+impl FileTypes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn with(mut self, file_type: FileType) -> Self {
+        self.register(file_type);
+        self
+    }
+    pub fn register(&mut self, file_type: FileType) {
+        self.0.push(file_type);
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &FileType> {
+        self.0.iter()
+    }
+
+    /// The first registered type `file` matches.
+    pub fn find(&self, file: &egui::HoveredFile) -> Option<&FileType> {
+        self.0.iter().find(|file_type| file_type.matches(file))
+    }
+
+    pub fn check(&self, file: &egui::HoveredFile) -> FileAcceptance {
+        let is_dir = file.path.as_deref().is_some_and(std::path::Path::is_dir);
+        if self.0.is_empty() || is_dir || self.find(file).is_some() {
+            // The files in a directory are checked once they are dropped, and
+            // those of other types skipped.
+            FileAcceptance::Accepted
+        } else if file.path.is_none() && file.mime.is_empty() {
+            FileAcceptance::Unknown
+        } else {
+            FileAcceptance::Rejected
+        }
+    }
+
+    /// For a file that was dropped: `name` is its path (or just its name on
+    /// the web), `mime` is empty if unknown.
+    pub fn accepts(&self, name: &str, mime: &str) -> bool {
+        let file = egui::HoveredFile {
+            path: Some(name.into()),
+            mime: mime.to_string(),
+        };
+        self.check(&file) != FileAcceptance::Rejected
+    }
+}
+
+/// Dims the app and lists the files being dragged over it, with whether they
+/// can be opened; does nothing while no files are dragged.
+pub(crate) fn show(ctx: &egui::Context, file_types: &FileTypes) {
+    let hovered_files = ctx.input(|input| input.raw.hovered_files.clone());
+    if hovered_files.is_empty() {
+        return;
+    }
+    let checks: Vec<_> = hovered_files
+        .iter()
+        .map(|file| (file, file_types.check(file)))
+        .collect();
+    let accepted = checks
+        .iter()
+        .any(|(_, acceptance)| *acceptance != FileAcceptance::Rejected);
+    let visuals = ctx.style().visuals.clone();
+    let highlight = if accepted {
+        visuals.selection.stroke.color
+    } else {
+        visuals.error_fg_color
+    };
+
+    let screen_rect = ctx.screen_rect();
+    let painter = ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Foreground,
+        egui::Id::new("drop zone"),
+    ));
+    painter.rect_filled(screen_rect, 0.0, egui::Color32::from_black_alpha(160));
+    painter.rect_stroke(
+        screen_rect.shrink(4.0),
+        8.0,
+        egui::Stroke::new(4.0, highlight),
+    );
+
+    egui::Area::new(egui::Id::new("drop zone files"))
+        .order(egui::Order::Tooltip)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.heading(if accepted {
+                    "Drop to open"
+                } else {
+                    "These files cannot be opened"
+                });
+                for (file, acceptance) in checks {
+                    let name = match (&file.path, file.mime.as_str()) {
+                        (Some(path), _) => path
+                            .file_name()
+                            .unwrap_or(path.as_os_str())
+                            .to_string_lossy()
+                            .into_owned(),
+                        (None, "") => "file".to_string(),
+                        (None, mime) => format!("file ({mime})"),
+                    };
+                    match (acceptance, file_types.find(file)) {
+                        (FileAcceptance::Accepted, Some(file_type)) => {
+                            ui.label(format!("{name}: {}", file_type.name))
+                        }
+                        (FileAcceptance::Accepted, None) => ui.label(name),
+                        (FileAcceptance::Rejected, _) => ui.colored_label(
+                            visuals.error_fg_color,
+                            format!("{name}: not supported"),
+                        ),
+                        (FileAcceptance::Unknown, _) => {
+                            ui.weak(format!("{name}: checked once dropped"))
+                        }
+                    };
+                }
+            });
+        });
+}
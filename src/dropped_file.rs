@@ -0,0 +1,201 @@
+//
+// Dropped files: their contents plus what is known about them
+//
+
+use crate::*;
+use std::path::{Path, PathBuf};
+
+/// Where a [`DroppedFile`] came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileSource {
+    /// Dropped onto the native window.
+    Native,
+    /// Dropped onto the web page (or handed over with `on_file_drop`).
+    Web,
+    /// Replayed from a recorded [`Session`](crate::Session).
+    Replay,
+}
+impl FileSource {
+    /// The platform the app runs on.
+    pub fn current() -> Self {
+        if cfg!(target_family = "wasm") {
+            Self::Web
+        } else {
+            Self::Native
+        }
+    }
+}
+impl std::fmt::Display for FileSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Native => "native window",
+            Self::Web => "web page",
+            Self::Replay => "replayed session",
+        })
+    }
+}
+
+/// A file dropped onto the window (native) or the web page (wasm).
+#[derive(Clone)]
+pub struct DroppedFile {
+    /// Just the file name, without its directories.
+    pub name: String,
+    /// Where the file is on disk; only known natively.
+    pub path: Option<PathBuf>,
+    /// Shared by everything that keeps the file, rather than copied.
+    pub bytes: Arc<[u8]>,
+    /// Sniffed from the contents, see [`sniff_mime_type`].
+    pub mime_type: &'static str,
+    pub dropped_at: chrono::DateTime<chrono::Local>,
+    pub source: FileSource,
+}
+impl DroppedFile {
+    pub fn new(name: &str, bytes: &[u8]) -> Self {
+        Self::from_bytes(name.to_string(), bytes.to_vec())
+    }
+
+    /// A file dropped just now on this platform, without a known path.
+    pub fn from_bytes(name: String, bytes: Vec<u8>) -> Self {
+        Self {
+            name,
+            path: None,
+            mime_type: sniff_mime_type(&bytes),
+            bytes: bytes.into(),
+            dropped_at: chrono::Local::now(),
+            source: FileSource::current(),
+        }
+    }
+
+    /// A file read from `path` on disk.
+    pub fn from_path(path: PathBuf, bytes: Vec<u8>) -> Self {
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => path.to_string_lossy().into_owned(),
+        };
+        Self {
+            path: Some(path),
+            source: FileSource::Native,
+            ..Self::from_bytes(name, bytes)
+        }
+    }
+
+    pub fn with_source(mut self, source: FileSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Size in bytes.
+    pub fn len(&self) -> u64 {
+        self.bytes.len() as u64
+    }
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+impl std::fmt::Debug for DroppedFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DroppedFile")
+            .field("name", &self.name)
+            .field("path", &self.path)
+            .field("len", &self.bytes.len())
+            .field("mime_type", &self.mime_type)
+            .field("dropped_at", &self.dropped_at)
+            .field("source", &self.source)
+            .finish()
+    }
+}
+
+/// Magic bytes at the start of a file, and the MIME type they stand for.
+const MAGIC_BYTES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"BM", "image/bmp"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"\0asm", "application/wasm"),
+    (b"\x7fELF", "application/x-executable"),
+];
+
+/// The known file signature `bytes` start with: its length, and the MIME
+/// type it stands for.
+pub fn file_signature(bytes: &[u8]) -> Option<(usize, &'static str)> {
+    if let Some((magic, mime_type)) = MAGIC_BYTES
+        .iter()
+        .find(|(magic, _)| bytes.starts_with(magic))
+    {
+        return Some((magic.len(), mime_type));
+    }
+    let is_webp = bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP";
+    is_webp.then_some((12, "image/webp"))
+}
+
+/// The MIME type of `bytes`, judged by their first few bytes: a known file
+/// signature, else `"text/plain"` for UTF-8 text and
+/// `"application/octet-stream"` for anything else.
+pub fn sniff_mime_type(bytes: &[u8]) -> &'static str {
+    if let Some((_, mime_type)) = file_signature(bytes) {
+        return mime_type;
+    }
+    let head = &bytes[..bytes.len().min(1024)];
+    let is_text = match std::str::from_utf8(head) {
+        Ok(text) => !text.contains('\0'),
+        // Cut off in the middle of a character.
+        Err(err) => err.error_len().is_none() && !head.contains(&0),
+    };
+    if is_text && !bytes.is_empty() {
+        "text/plain"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// Stores `bytes`: natively as the file at `path`, on the web as a download
+/// with the same file name.
+pub fn save_file(path: &Path, bytes: &[u8]) -> Result<()> {
+    #[cfg(not(target_family = "wasm"))]
+    return std::fs::write(path, bytes).map_err(|source| Error::FileIo {
+        path: path.to_path_buf(),
+        source,
+    });
+    #[cfg(target_family = "wasm")]
+    {
+        let file_name = path.file_name().and_then(|name| name.to_str());
+        wasm::download(file_name.unwrap_or("download"), bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    #[test]
+    fn mime_types_are_sniffed_from_the_contents() {
+        assert_eq!(sniff_mime_type(PNG), "image/png");
+        assert_eq!(sniff_mime_type(b"\xff\xd8\xff\xe0"), "image/jpeg");
+        assert_eq!(sniff_mime_type(b"RIFF\x10\0\0\0WEBPVP8 "), "image/webp");
+        assert_eq!(sniff_mime_type(b"%PDF-1.7"), "application/pdf");
+        assert_eq!(sniff_mime_type("grüße".as_bytes()), "text/plain");
+        // Cut off in the middle of the 'ü'.
+        assert_eq!(sniff_mime_type(&"grü".as_bytes()[..3]), "text/plain");
+        assert_eq!(sniff_mime_type(b"\0\x01\x02"), "application/octet-stream");
+        assert_eq!(sniff_mime_type(b""), "application/octet-stream");
+    }
+
+    #[test]
+    fn dropped_files_know_their_name_and_type() {
+        let file = DroppedFile::new("photo.png", PNG);
+        assert_eq!(file.mime_type, "image/png");
+        assert_eq!(file.len(), PNG.len() as u64);
+        assert_eq!(file.path, None);
+        assert_eq!(file.source, FileSource::Native);
+
+        let file = DroppedFile::from_path("/photos/photo.png".into(), PNG.to_vec());
+        assert_eq!(file.name, "photo.png");
+        assert_eq!(file.path, Some("/photos/photo.png".into()));
+    }
+}
@@ -0,0 +1,826 @@
+//
+// Reading dropped files in chunks, without blocking the event loop
+//
+// Natively a background thread reads the file, on the web `Blob.slice()`
+// hands out one chunk at a time. Either way the chunks arrive as
+// `UserEvent::File`s, and a reader pauses while `MAX_CHUNKS_IN_FLIGHT` of its
+// chunks are still alive, so a huge file never has to fit into memory.
+// Whatever goes wrong with a file ends up as its `FileEvent::Failed`.
+//
+
+use crate::*;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+/// Size of the chunks files are read in.
+pub const FILE_CHUNK_SIZE: usize = 1 << 20;
+
+/// Dropped files up to this size are also delivered whole, see
+/// [`AppConfig::max_assembled_file_size`].
+pub const MAX_ASSEMBLED_FILE_SIZE: u64 = 64 << 20;
+
+/// How many chunks of one file may be waiting for (or held by) the app logic
+/// before the reader pauses.
+const MAX_CHUNKS_IN_FLIGHT: usize = 8;
+
+/// Tells apart the files being read at the same time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FileId(u64);
+impl FileId {
+    fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Shared between a reader and its chunks.
+#[derive(Debug, Default)]
+struct ReadState {
+    cancelled: AtomicBool,
+    chunks_in_flight: AtomicUsize,
+}
+impl ReadState {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+    /// The reader has to wait until some chunks are dropped.
+    fn is_throttled(&self) -> bool {
+        self.chunks_in_flight.load(Ordering::Acquire) >= MAX_CHUNKS_IN_FLIGHT
+    }
+}
+
+/// Stops reading a file; the reader then sends [`FileEvent::Cancelled`].
+#[derive(Clone, Debug)]
+pub struct CancelHandle(Arc<ReadState>);
+impl CancelHandle {
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Release);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.0.is_cancelled()
+    }
+}
+
+/// `bytes` of a file, starting at `offset`. The reader gets to read the next
+/// chunks once this one is dropped (or its bytes have been moved out).
+pub struct FileChunk {
+    pub id: FileId,
+    pub offset: u64,
+    pub bytes: Vec<u8>,
+    _credit: ChunkCredit,
+}
+impl FileChunk {
+    fn new(id: FileId, offset: u64, bytes: Vec<u8>, state: &Arc<ReadState>) -> Self {
+        state.chunks_in_flight.fetch_add(1, Ordering::AcqRel);
+        Self {
+            id,
+            offset,
+            bytes,
+            _credit: ChunkCredit(state.clone()),
+        }
+    }
+}
+impl std::fmt::Debug for FileChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileChunk")
+            .field("id", &self.id)
+            .field("offset", &self.offset)
+            .field("len", &self.bytes.len())
+            .finish()
+    }
+}
+
+struct ChunkCredit(Arc<ReadState>);
+impl Drop for ChunkCredit {
+    fn drop(&mut self) {
+        self.0.chunks_in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// The life of a file being read: `Started`, any number of `Chunk`s in
+/// order, then one of `Finished`, `Cancelled` or `Failed`.
+#[derive(Debug)]
+pub enum FileEvent {
+    Started {
+        id: FileId,
+        name: String,
+        len: u64,
+        cancel: CancelHandle,
+        /// Whether the file also reaches [`AppLogic::on_file_dropped`] in one
+        /// piece once read, as it is no larger than
+        /// [`AppConfig::max_assembled_file_size`] (unless it grows while it
+        /// is read).
+        assembled: bool,
+    },
+    Chunk(FileChunk),
+    Finished {
+        id: FileId,
+    },
+    Cancelled {
+        id: FileId,
+    },
+    Failed {
+        id: FileId,
+        error: Error,
+    },
+}
+impl FileEvent {
+    pub fn id(&self) -> FileId {
+        match self {
+            Self::Started { id, .. }
+            | Self::Finished { id }
+            | Self::Cancelled { id }
+            | Self::Failed { id, .. } => *id,
+            Self::Chunk(chunk) => chunk.id,
+        }
+    }
+}
+impl From<FileEvent> for UserEvent {
+    fn from(value: FileEvent) -> Self {
+        Self::File(value)
+    }
+}
+
+/// What is read of a directory dropped onto the window (recursively, in
+/// file name order) before the rest is skipped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DirectoryLimits {
+    pub max_files: usize,
+    /// In bytes, over all files.
+    pub max_total_size: u64,
+}
+impl Default for DirectoryLimits {
+    fn default() -> Self {
+        Self {
+            max_files: 1000,
+            max_total_size: 1 << 30,
+        }
+    }
+}
+
+/// Reads the file at `path` in a background thread and sends its
+/// [`FileEvent`]s through `proxy`; problems (eg. a missing file) arrive as
+/// [`FileEvent::Failed`].
+#[cfg(not(target_family = "wasm"))]
+pub fn read_file(path: std::path::PathBuf, proxy: &AppProxy) -> FileId {
+    let id = FileId::next();
+    let proxy = proxy.clone();
+    spawn_reader(move || send_file(id, &path, &proxy));
+    id
+}
+
+/// Like [`read_file`], but directories are read with all the files in them,
+/// up to `limits`. Files that are none of `file_types` are skipped; those in
+/// a directory are reported as [`Error::UnsupportedFileType`] failures.
+#[cfg(not(target_family = "wasm"))]
+pub fn read_dropped_path(
+    path: std::path::PathBuf,
+    limits: DirectoryLimits,
+    file_types: &FileTypes,
+    proxy: &AppProxy,
+) {
+    if !path.is_dir() {
+        if file_types.accepts(&path.to_string_lossy(), "") {
+            read_file(path, proxy);
+        } else {
+            log::info!("ignoring '{}': not a supported file type", path.display());
+        }
+        return;
+    }
+    let (file_types, proxy) = (file_types.clone(), proxy.clone());
+    spawn_reader(move || {
+        for file in list_files(&path, limits, &file_types, &proxy) {
+            send_file(FileId::next(), &file, &proxy);
+        }
+    });
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn spawn_reader(reader: impl FnOnce() + Send + 'static) {
+    let spawned = std::thread::Builder::new()
+        .name("file reader".to_string())
+        .spawn(reader);
+    if let Err(err) = spawned {
+        log::error!("cannot start a file reader thread: {err}");
+    }
+}
+
+/// The files of `file_types` in `dir` and its subdirectories, as far as
+/// `limits` allow; unreadable directories and skipped files are reported as
+/// failures.
+#[cfg(not(target_family = "wasm"))]
+fn list_files(
+    root: &std::path::Path,
+    limits: DirectoryLimits,
+    file_types: &FileTypes,
+    proxy: &AppProxy,
+) -> Vec<std::path::PathBuf> {
+    let mut files = Vec::new();
+    let mut total_size = 0;
+    // Depth first, in name order; symlinked directories are not followed so
+    // there can be no cycles.
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(source) => {
+                send_failure(
+                    &dir,
+                    Error::FileIo {
+                        path: dir.clone(),
+                        source,
+                    },
+                    proxy,
+                );
+                continue;
+            }
+        };
+        let mut entries: Vec<_> = entries.filter_map(|entry| entry.ok()).collect();
+        entries.sort_by_key(|entry| entry.file_name());
+        let mut subdirs = Vec::new();
+        for entry in entries {
+            let path = entry.path();
+            if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                subdirs.push(path);
+                continue;
+            }
+            let len = match std::fs::metadata(&path) {
+                Ok(metadata) if metadata.is_file() => metadata.len(),
+                // Symlinked directories, sockets...
+                Ok(_) => continue,
+                // Broken symlinks, or gone already; reading it tells why.
+                Err(_) => 0,
+            };
+            if !file_types.accepts(&path.to_string_lossy(), "") {
+                send_failure(&path, Error::UnsupportedFileType(path.clone()), proxy);
+                continue;
+            }
+            total_size += len;
+            if files.len() == limits.max_files || total_size > limits.max_total_size {
+                let error = Error::DirectoryLimit {
+                    path: root.to_path_buf(),
+                    limits,
+                };
+                send_failure(root, error, proxy);
+                return files;
+            }
+            files.push(path);
+        }
+        pending.extend(subdirs.into_iter().rev());
+    }
+    files
+}
+
+/// A failure that is not about reading a particular file.
+#[cfg(not(target_family = "wasm"))]
+fn send_failure(path: &std::path::Path, error: Error, proxy: &AppProxy) {
+    let id = FileId::next();
+    proxy.send(FileEvent::Started {
+        id,
+        name: path.to_string_lossy().into_owned(),
+        len: 0,
+        cancel: CancelHandle(Default::default()),
+        assembled: false,
+    });
+    proxy.send(FileEvent::Failed { id, error });
+}
+
+/// Sends all events of the file at `path`, from `Started` to how it ended.
+#[cfg(not(target_family = "wasm"))]
+fn send_file(id: FileId, path: &std::path::Path, proxy: &AppProxy) {
+    let io_error = |source| Error::FileIo {
+        path: path.to_path_buf(),
+        source,
+    };
+    let opened = match path.to_str() {
+        Some(_) => std::fs::File::open(path)
+            .and_then(|file| Ok((file.metadata()?.len(), file)))
+            .map_err(io_error),
+        None => Err(Error::NonUtf8Path(path.to_path_buf())),
+    };
+    let state = Arc::new(ReadState::default());
+    proxy.send(FileEvent::Started {
+        id,
+        name: path.to_string_lossy().into_owned(),
+        len: opened.as_ref().map_or(0, |(len, _)| *len),
+        cancel: CancelHandle(state.clone()),
+        assembled: false,
+    });
+    let result = opened.and_then(|(_, file)| read_chunks(file, path, id, &state, proxy));
+    proxy.send(match result {
+        Ok(true) => FileEvent::Finished { id },
+        Ok(false) => FileEvent::Cancelled { id },
+        Err(error) => FileEvent::Failed { id, error },
+    });
+}
+
+/// Returns `false` when reading was cancelled.
+#[cfg(not(target_family = "wasm"))]
+fn read_chunks(
+    file: std::fs::File,
+    path: &std::path::Path,
+    id: FileId,
+    state: &Arc<ReadState>,
+    proxy: &AppProxy,
+) -> Result<bool> {
+    use std::io::Read;
+    let mut reader = std::io::BufReader::with_capacity(FILE_CHUNK_SIZE, file);
+    let mut offset = 0;
+    loop {
+        while state.is_throttled() && !state.is_cancelled() {
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        if state.is_cancelled() {
+            return Ok(false);
+        }
+        let mut bytes = Vec::with_capacity(FILE_CHUNK_SIZE);
+        (&mut reader)
+            .take(FILE_CHUNK_SIZE as u64)
+            .read_to_end(&mut bytes)
+            .map_err(|source| Error::FileIo {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        if bytes.is_empty() {
+            return Ok(true);
+        }
+        let len = bytes.len() as u64;
+        // The event loop is gone, nobody is waiting for the rest.
+        if !proxy.send(FileEvent::Chunk(FileChunk::new(id, offset, bytes, state))) {
+            return Ok(false);
+        }
+        offset += len;
+    }
+}
+
+/// Reads `file` with `Blob.slice()` and sends its [`FileEvent`]s through
+/// `proxy`.
+#[cfg(target_family = "wasm")]
+pub(crate) fn read_blob(file: web_sys::File, proxy: AppProxy) -> FileId {
+    let (id, state) = (FileId::next(), Arc::new(ReadState::default()));
+    proxy.send(FileEvent::Started {
+        id,
+        name: file.name(),
+        len: file.size() as u64,
+        cancel: CancelHandle(state.clone()),
+        assembled: false,
+    });
+    wasm_bindgen_futures::spawn_local(async move {
+        let event = match read_blob_chunks(&file, id, &state, &proxy).await {
+            Ok(true) => FileEvent::Finished { id },
+            Ok(false) => FileEvent::Cancelled { id },
+            Err(error) => FileEvent::Failed { id, error },
+        };
+        proxy.send(event);
+    });
+    id
+}
+
+#[cfg(target_family = "wasm")]
+async fn read_blob_chunks(
+    file: &web_sys::File,
+    id: FileId,
+    state: &Arc<ReadState>,
+    proxy: &AppProxy,
+) -> Result<bool> {
+    let len = file.size() as u64;
+    let mut offset = 0;
+    while offset < len {
+        while state.is_throttled() && !state.is_cancelled() {
+            sleep(2).await;
+        }
+        if state.is_cancelled() {
+            return Ok(false);
+        }
+        let end = len.min(offset + FILE_CHUNK_SIZE as u64);
+        let blob = file
+            .slice_with_f64_and_f64(offset as f64, end as f64)
+            .map_err(|_| Error::WebDom("cannot slice a dropped file"))?;
+        let buffer = wasm_bindgen_futures::JsFuture::from(blob.array_buffer())
+            .await
+            .map_err(|_| Error::WebDom("cannot read a dropped file"))?;
+        let bytes = js_sys::Uint8Array::new(&buffer).to_vec();
+        if !proxy.send(FileEvent::Chunk(FileChunk::new(id, offset, bytes, state))) {
+            return Ok(false);
+        }
+        offset = end;
+    }
+    Ok(true)
+}
+
+#[cfg(target_family = "wasm")]
+pub(crate) async fn sleep(millis: i32) {
+    let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+        let timeout = web_sys::window().map(|window| {
+            window.set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, millis)
+        });
+        if !matches!(timeout, Some(Ok(_))) {
+            let _ = resolve.call0(&wasm_bindgen::JsValue::NULL);
+        }
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
+/// A file that is still being read.
+struct FileRead {
+    id: FileId,
+    name: String,
+    len: u64,
+    read: u64,
+    cancel: CancelHandle,
+    /// The contents so far, for files that are delivered whole.
+    bytes: Option<Vec<u8>>,
+}
+
+/// The files being read: for the progress window, and to put the small ones
+/// back together into [`DroppedFile`]s.
+pub(crate) struct FileReads {
+    max_assembled_size: u64,
+    reads: Vec<FileRead>,
+    /// Names and errors of the files that could not be read, until dismissed.
+    failures: Vec<(String, String)>,
+}
+impl FileReads {
+    pub fn new(max_assembled_size: u64) -> Self {
+        Self {
+            max_assembled_size,
+            reads: Vec::new(),
+            failures: Vec::new(),
+        }
+    }
+
+    /// Keeps track of `event` and tells whether it is `assembled`; returns
+    /// the whole file when it was the last one of a file that is small
+    /// enough.
+    pub fn on_event(&mut self, event: &mut FileEvent) -> Option<DroppedFile> {
+        let index = self.reads.iter().position(|read| read.id == event.id());
+        match (event, index) {
+            (
+                FileEvent::Started {
+                    id,
+                    name,
+                    len,
+                    cancel,
+                    assembled,
+                },
+                _,
+            ) => {
+                *assembled = *len <= self.max_assembled_size;
+                let bytes = assembled.then(|| Vec::with_capacity(*len as usize));
+                self.reads.push(FileRead {
+                    id: *id,
+                    name: name.clone(),
+                    len: *len,
+                    read: 0,
+                    cancel: cancel.clone(),
+                    bytes,
+                });
+                None
+            }
+            (FileEvent::Chunk(chunk), Some(index)) => {
+                let read = &mut self.reads[index];
+                read.read += chunk.bytes.len() as u64;
+                if let Some(bytes) = &mut read.bytes {
+                    if read.read <= self.max_assembled_size {
+                        bytes.extend_from_slice(&chunk.bytes);
+                    } else {
+                        // The file grew since it was dropped.
+                        read.bytes = None;
+                    }
+                }
+                None
+            }
+            (FileEvent::Finished { .. }, Some(index)) => {
+                let read = self.reads.remove(index);
+                // Natively files are named by their path.
+                #[cfg(not(target_family = "wasm"))]
+                let dropped_file = |bytes| DroppedFile::from_path(read.name.into(), bytes);
+                #[cfg(target_family = "wasm")]
+                let dropped_file = |bytes| DroppedFile::from_bytes(read.name, bytes);
+                read.bytes.map(dropped_file)
+            }
+            (FileEvent::Failed { error, .. }, Some(index)) => {
+                let read = self.reads.remove(index);
+                log::warn!("failed to read dropped file '{}': {error}", read.name);
+                self.failures.push((read.name, error.to_string()));
+                None
+            }
+            (FileEvent::Cancelled { .. }, Some(index)) => {
+                let read = self.reads.remove(index);
+                log::info!("stopped reading '{}'", read.name);
+                None
+            }
+            (_, None) => None,
+        }
+    }
+
+    pub fn ui(&mut self, ctx: &egui::Context) {
+        if self.reads.is_empty() && self.failures.is_empty() {
+            return;
+        }
+        egui::Window::new("Reading files")
+            .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -8.0])
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                for read in &self.reads {
+                    ui.horizontal(|ui| {
+                        let progress = match read.len {
+                            0 => 1.0,
+                            len => read.read as f32 / len as f32,
+                        };
+                        ui.add(
+                            egui::ProgressBar::new(progress)
+                                .desired_width(200.0)
+                                .text(read.name.as_str()),
+                        );
+                        let cancelled = read.cancel.is_cancelled();
+                        if ui
+                            .add_enabled(!cancelled, egui::Button::new("cancel"))
+                            .clicked()
+                        {
+                            read.cancel.cancel();
+                        }
+                    });
+                }
+                if self.failures.is_empty() {
+                    return;
+                }
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(160.0)
+                    .show(ui, |ui| {
+                        for (name, error) in &self.failures {
+                            ui.colored_label(ui.visuals().error_fg_color, name.as_str());
+                            ui.label(error.as_str());
+                        }
+                    });
+                if ui.button("dismiss").clicked() {
+                    self.failures.clear();
+                }
+            });
+    }
+}
+
+#[cfg(all(test, not(target_family = "wasm")))]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Instant;
+
+    /// Hands out the events of the readers to `FileReads`, like `App` does,
+    /// and keeps everything, including the chunks (which stalls a reader once
+    /// enough of them are held).
+    struct Collector {
+        proxy: AppProxy,
+        reads: FileReads,
+        started: Vec<(FileId, String, u64, CancelHandle, bool)>,
+        chunks: Vec<FileChunk>,
+        finished: usize,
+        cancelled: bool,
+        /// With the name the file was started with.
+        failures: Vec<(String, Error)>,
+        dropped_files: Vec<DroppedFile>,
+    }
+    impl Collector {
+        fn new() -> Self {
+            Self {
+                proxy: AppProxy::queued(),
+                reads: FileReads::new(MAX_ASSEMBLED_FILE_SIZE),
+                started: Vec::new(),
+                chunks: Vec::new(),
+                finished: 0,
+                cancelled: false,
+                failures: Vec::new(),
+                dropped_files: Vec::new(),
+            }
+        }
+
+        fn deliver(&mut self) {
+            for event in self.proxy.take_queued() {
+                let UserEvent::File(mut event) = event else {
+                    panic!("only file events expected");
+                };
+                if let Some(dropped_file) = self.reads.on_event(&mut event) {
+                    self.dropped_files.push(dropped_file);
+                }
+                match event {
+                    FileEvent::Started {
+                        id,
+                        name,
+                        len,
+                        cancel,
+                        assembled,
+                    } => self.started.push((id, name, len, cancel, assembled)),
+                    FileEvent::Chunk(chunk) => self.chunks.push(chunk),
+                    FileEvent::Finished { .. } => self.finished += 1,
+                    FileEvent::Cancelled { .. } => self.cancelled = true,
+                    FileEvent::Failed { id, error } => {
+                        let started = self.started.iter().find(|started| started.0 == id);
+                        let name = started.expect("failed before it started").1.clone();
+                        self.failures.push((name, error));
+                    }
+                }
+            }
+        }
+
+        fn run_until(&mut self, done: impl Fn(&Self) -> bool) {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            loop {
+                self.deliver();
+                if done(self) {
+                    return;
+                }
+                assert!(Instant::now() < deadline, "timed out");
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        fn contents(&self) -> Vec<u8> {
+            let mut offset = 0;
+            let mut contents = Vec::new();
+            for chunk in &self.chunks {
+                assert_eq!(chunk.offset, offset, "chunks arrive in order");
+                offset += chunk.bytes.len() as u64;
+                contents.extend_from_slice(&chunk.bytes);
+            }
+            contents
+        }
+    }
+
+    /// A fresh directory per test.
+    fn tmp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("wasm_winit_wgpu-{}", std::process::id()))
+            .join(test);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_test_file(path: &std::path::Path, len: usize) -> Vec<u8> {
+        let contents: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+        std::fs::write(path, &contents).unwrap();
+        contents
+    }
+
+    #[test]
+    fn files_arrive_in_chunks_and_whole() {
+        let mut collector = Collector::new();
+        let path = tmp_dir("chunks").join("chunks.bin");
+        let contents = write_test_file(&path, FILE_CHUNK_SIZE * 5 / 2);
+
+        read_file(path.clone(), &collector.proxy);
+        collector.run_until(|collector| collector.finished == 1);
+
+        assert_eq!(collector.started.len(), 1);
+        assert_eq!(collector.started[0].1, path.to_str().unwrap());
+        assert_eq!(collector.started[0].2, contents.len() as u64);
+        assert!(collector.started[0].4);
+        assert_eq!(collector.chunks.len(), 3);
+        assert!(collector.contents() == contents);
+        // Small enough to be put back together as well.
+        assert_eq!(collector.dropped_files.len(), 1);
+        assert!(*collector.dropped_files[0].bytes == *contents);
+        assert_eq!(collector.dropped_files[0].name, "chunks.bin");
+        assert_eq!(collector.dropped_files[0].path.as_ref(), Some(&path));
+    }
+
+    #[test]
+    fn large_files_are_not_put_back_together() {
+        let mut collector = Collector::new();
+        collector.reads = FileReads::new(FILE_CHUNK_SIZE as u64);
+        let path = tmp_dir("large").join("large.bin");
+        write_test_file(&path, FILE_CHUNK_SIZE + 1);
+
+        read_file(path, &collector.proxy);
+        collector.run_until(|collector| collector.finished == 1);
+        assert!(!collector.started[0].4);
+        assert_eq!(collector.chunks.len(), 2);
+        assert!(collector.dropped_files.is_empty());
+    }
+
+    #[test]
+    fn reading_pauses_while_chunks_are_held_and_can_be_cancelled() {
+        let mut collector = Collector::new();
+        let path = tmp_dir("cancel").join("cancel.bin");
+        write_test_file(&path, FILE_CHUNK_SIZE * 32);
+
+        read_file(path, &collector.proxy);
+        collector.run_until(|collector| collector.chunks.len() >= MAX_CHUNKS_IN_FLIGHT);
+        std::thread::sleep(Duration::from_millis(50));
+        collector.deliver();
+        assert_eq!(
+            collector.chunks.len(),
+            MAX_CHUNKS_IN_FLIGHT,
+            "the reader waits for chunks to be dropped"
+        );
+
+        collector.started[0].3.cancel();
+        collector.chunks.clear();
+        collector.run_until(|collector| collector.cancelled);
+        assert_eq!(collector.finished, 0);
+        assert!(collector.dropped_files.is_empty());
+    }
+
+    #[test]
+    fn missing_files_are_reported() {
+        let mut collector = Collector::new();
+        let path = tmp_dir("missing").join("does_not_exist.bin");
+
+        read_file(path.clone(), &collector.proxy);
+        collector.run_until(|collector| !collector.failures.is_empty());
+        let (name, error) = &collector.failures[0];
+        assert_eq!(name, path.to_str().unwrap());
+        assert!(matches!(error, Error::FileIo { .. }), "{error}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_paths_are_reported() {
+        use std::os::unix::ffi::OsStrExt;
+        let mut collector = Collector::new();
+        let path = tmp_dir("non_utf8").join(std::ffi::OsStr::from_bytes(b"caf\xe9.txt"));
+        std::fs::write(&path, b"bytes").unwrap();
+
+        read_file(path, &collector.proxy);
+        collector.run_until(|collector| !collector.failures.is_empty());
+        let (name, error) = &collector.failures[0];
+        assert!(name.ends_with("caf\u{fffd}.txt"), "{name}");
+        assert!(matches!(error, Error::NonUtf8Path(_)), "{error}");
+        assert_eq!(collector.finished, 0);
+    }
+
+    #[test]
+    fn directories_are_read_recursively_up_to_the_limits() {
+        let mut collector = Collector::new();
+        let dir = tmp_dir("dropped_dir");
+        std::fs::create_dir_all(dir.join("b")).unwrap();
+        for (name, contents) in [
+            ("a.txt", "a"),
+            ("b/c.txt", "c"),
+            ("b/d.txt", "d"),
+            ("e.txt", "e"),
+        ] {
+            std::fs::write(dir.join(name), contents).unwrap();
+        }
+
+        let limits = DirectoryLimits {
+            max_files: 3,
+            max_total_size: 1024,
+        };
+        read_dropped_path(dir.clone(), limits, &FileTypes::new(), &collector.proxy);
+        collector.run_until(|collector| collector.finished == 3 && !collector.failures.is_empty());
+
+        // Files first, then subdirectories, each in name order.
+        let names: Vec<_> = collector
+            .dropped_files
+            .iter()
+            .map(|file| file.path.clone().expect("read from disk"))
+            .collect();
+        assert_eq!(
+            names,
+            ["a.txt", "e.txt", "b/c.txt"].map(|name| dir.join(name))
+        );
+        let (name, error) = &collector.failures[0];
+        assert_eq!(name, dir.to_str().unwrap());
+        assert!(matches!(error, Error::DirectoryLimit { .. }), "{error}");
+    }
+
+    #[test]
+    fn files_of_other_types_in_directories_are_skipped() {
+        let mut collector = Collector::new();
+        let dir = tmp_dir("filtered_dir");
+        std::fs::create_dir_all(dir.join("b")).unwrap();
+        for name in ["a.txt", "b/c.TXT", "b/d.png", "e.bin"] {
+            std::fs::write(dir.join(name), name).unwrap();
+        }
+
+        let file_types = FileTypes::new().with(FileType::new("text").extension("txt"));
+        read_dropped_path(
+            dir.clone(),
+            DirectoryLimits::default(),
+            &file_types,
+            &collector.proxy,
+        );
+        collector.run_until(|collector| collector.finished == 2 && collector.failures.len() == 2);
+
+        let names: Vec<_> = collector
+            .dropped_files
+            .iter()
+            .map(|file| file.path.clone().expect("read from disk"))
+            .collect();
+        assert_eq!(names, ["a.txt", "b/c.TXT"].map(|name| dir.join(name)));
+        let rejected: Vec<_> = collector
+            .failures
+            .iter()
+            .map(|(name, error)| {
+                assert!(matches!(error, Error::UnsupportedFileType(_)), "{error}");
+                name.clone()
+            })
+            .collect();
+        assert_eq!(
+            rejected,
+            ["e.bin", "b/d.png"].map(|name| dir.join(name).to_string_lossy().into_owned())
+        );
+    }
+}
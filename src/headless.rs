@@ -0,0 +1,539 @@
+//
+// Headless rendering: run `AppLogic` without a window or surface
+//
+
+use crate::*;
+
+/// A texture to render into instead of a `SurfaceTexture`, which can be read
+/// back from the GPU.
+pub struct OffscreenTarget {
+    texture: Texture,
+    view: TextureView,
+    size: PhysicalSize<u32>,
+}
+
+impl OffscreenTarget {
+    /// Always `Rgba8Unorm`, so read back pixels can be written out as-is.
+    pub const FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+
+    pub fn new(device: &Device, size: PhysicalSize<u32>) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("offscreen target"),
+            size: Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        Self {
+            texture,
+            view,
+            size,
+        }
+    }
+
+    pub fn size(&self) -> PhysicalSize<u32> {
+        self.size
+    }
+
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+
+    /// Copies the target back from the GPU as tightly packed RGBA8 rows;
+    /// blocks until the GPU is done.
+    pub fn read_rgba(&self, gpu: &GpuState) -> Result<Vec<u8>> {
+        let PhysicalSize { width, height } = self.size;
+        let unpadded_bytes_per_row = width * 4;
+        let bytes_per_row = unpadded_bytes_per_row.div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT)
+            * COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = gpu.device().create_buffer(&BufferDescriptor {
+            label: Some("offscreen readback"),
+            size: bytes_per_row as u64 * height as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = gpu
+            .device()
+            .create_command_encoder(&CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        gpu.queue().submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        gpu.device().poll(Maintain::Wait);
+        receiver
+            .recv()
+            .map_err(|_| Error::Readback(BufferAsyncError))?
+            .map_err(Error::Readback)?;
+
+        let mapped = slice.get_mapped_range();
+        let pixels = mapped
+            .chunks_exact(bytes_per_row as usize)
+            .flat_map(|row| &row[..unpadded_bytes_per_row as usize])
+            .copied()
+            .collect();
+        drop(mapped);
+        buffer.unmap();
+        Ok(pixels)
+    }
+}
+
+/// Drives an [`AppLogic`] frame by frame into an [`OffscreenTarget`], with
+/// full control over the egui input of every frame. The frames are the ones
+/// an app instance renders into its window, framework windows included.
+pub struct HeadlessApp<L: AppLogic> {
+    gpu: GpuState,
+    target: OffscreenTarget,
+    context: egui::Context,
+    painter: EguiPainter,
+    proxy: AppProxy,
+    /// Messages the app posted to the host.
+    posted: Vec<Message>,
+    core: InstanceCore<L>,
+    /// Empty: without a surface there is nothing to recover from.
+    recovery: RecoveryLog,
+    pixels_per_point: f32,
+    frame_index: u64,
+}
+
+impl<L: AppLogic> HeadlessApp<L> {
+    pub fn new(
+        gpu: GpuState,
+        mut logic: L,
+        size: PhysicalSize<u32>,
+        pixels_per_point: f32,
+    ) -> Self {
+        let target = OffscreenTarget::new(gpu.device(), size);
+        let painter = EguiPainter::new(gpu.device(), OffscreenTarget::FORMAT);
+        let proxy = AppProxy::queued();
+        logic.init(&gpu, proxy.clone());
+        let config = AppConfig {
+            clock: Box::new(FixedStepClock::from_fps(60)),
+            ..AppConfig::default()
+        };
+        Self {
+            gpu,
+            target,
+            context: egui::Context::default(),
+            painter,
+            proxy,
+            posted: Vec::new(),
+            core: InstanceCore::new(config, Box::new(logic)),
+            recovery: RecoveryLog::default(),
+            pixels_per_point,
+            frame_index: 0,
+        }
+    }
+
+    pub fn gpu(&self) -> &GpuState {
+        &self.gpu
+    }
+    pub fn context(&self) -> &egui::Context {
+        &self.context
+    }
+    pub fn logic(&self) -> &L {
+        &self.core.logic
+    }
+    pub fn logic_mut(&mut self) -> &mut L {
+        &mut self.core.logic
+    }
+    pub fn target(&self) -> &OffscreenTarget {
+        &self.target
+    }
+    /// Events posted here are delivered at the start of the next frame.
+    pub fn proxy(&self) -> &AppProxy {
+        &self.proxy
+    }
+    /// Messages the app posted to the host since the last call.
+    pub fn take_posted(&mut self) -> Vec<Message> {
+        self.deliver_queued();
+        std::mem::take(&mut self.posted)
+    }
+    pub fn frame_index(&self) -> u64 {
+        self.frame_index
+    }
+    /// See [`AppConfig::file_types`].
+    pub fn set_file_types(&mut self, file_types: FileTypes) {
+        self.core.config.file_types = file_types;
+    }
+    /// See [`AppConfig::max_assembled_file_size`]; forgets the files being
+    /// read.
+    pub fn set_max_assembled_file_size(&mut self, size: u64) {
+        self.core.config.max_assembled_file_size = size;
+        self.core.file_reads = FileReads::new(size);
+    }
+    /// See [`AppConfig::show_surface_settings`]; there are no present
+    /// settings without a surface, only the render mode and the session.
+    pub fn set_show_surface_settings(&mut self, show: bool) {
+        self.core.config.show_surface_settings = show;
+    }
+    /// Replaces the default clock, which advances 1/60 s per frame.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.core.config.clock = Box::new(clock);
+    }
+    pub fn clock(&self) -> &dyn Clock {
+        self.core.config.clock.as_ref()
+    }
+
+    /// Input for an otherwise idle frame at the current time of the clock:
+    /// the screen rect and scale factor are filled in, there are no events.
+    pub fn raw_input(&self) -> egui::RawInput {
+        let size = self.target.size();
+        let screen_size = egui::vec2(size.width as f32, size.height as f32) / self.pixels_per_point;
+        let mut raw_input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(egui::Pos2::ZERO, screen_size)),
+            time: Some(self.core.config.clock.now().as_secs_f64()),
+            focused: true,
+            max_texture_side: Some(self.gpu.device().limits().max_texture_dimension_2d as usize),
+            ..Default::default()
+        };
+        raw_input
+            .viewports
+            .entry(egui::ViewportId::ROOT)
+            .or_default()
+            .native_pixels_per_point = Some(self.pixels_per_point);
+        raw_input
+    }
+
+    /// Renders one frame (app background plus egui) with the given input and
+    /// returns egui's platform output (cursor, copied text...) for inspection.
+    pub fn render_frame(&mut self, raw_input: egui::RawInput) -> egui::PlatformOutput {
+        self.deliver_queued();
+        let mut encoder = self
+            .gpu
+            .device()
+            .create_command_encoder(&CommandEncoderDescriptor { label: None });
+        let output = self.core.frame(
+            &self.gpu,
+            RenderTarget {
+                encoder: &mut encoder,
+                view: self.target.view(),
+                format: OffscreenTarget::FORMAT,
+                size: self.target.size(),
+            },
+            &self.context,
+            &mut self.painter,
+            raw_input,
+            self.pixels_per_point,
+            &self.recovery,
+            None,
+            None,
+        );
+        self.gpu.queue().submit(std::iter::once(encoder.finish()));
+        self.frame_index += 1;
+        output.platform_output
+    }
+
+    /// Hands the events posted through the proxy to the app logic, like the
+    /// event loop does between frames, until there are no more (handlers
+    /// may post further events).
+    fn deliver_queued(&mut self) {
+        loop {
+            let events = self.proxy.take_queued();
+            if events.is_empty() {
+                return;
+            }
+            for event in events {
+                match event {
+                    UserEvent::PostToHost(message) => self.posted.push(message),
+                    event => self.core.user_event(event),
+                }
+            }
+        }
+    }
+
+    /// The last rendered frame as tightly packed RGBA8 rows.
+    pub fn read_rgba(&self) -> Result<Vec<u8>> {
+        self.target.read_rgba(&self.gpu)
+    }
+}
+
+impl<L: AppLogic> Drop for HeadlessApp<L> {
+    fn drop(&mut self) {
+        self.core.shutdown();
+    }
+}
+
+/// A widget as seen through egui's accessibility tree.
+#[derive(Clone, Debug)]
+pub struct Widget {
+    pub id: egui::accesskit::NodeId,
+    /// Empty for widgets without a label, eg. text fields.
+    pub label: String,
+    pub role: egui::accesskit::Role,
+    /// In points.
+    pub rect: egui::Rect,
+}
+
+/// Scripted input for a [`HeadlessApp`]: queue pointer, keyboard and text
+/// events, run frames and look up widgets by their label, eg. to test a UI
+/// without a window:
+///
+/// ```no_run
+/// # use wasm_winit_wgpu::{demo::DemoApp, headless::*, GpuConfig, GpuState};
+/// # let gpu = GpuState::headless(&GpuConfig::default()).unwrap();
+/// let app = HeadlessApp::new(gpu, DemoApp::new(), (800, 600).into(), 1.0);
+/// let mut driver = UiDriver::new(app);
+/// driver.click("I dare you!");
+/// assert!(driver.find("You've clicked the button 1 time(s)").is_some());
+/// ```
+pub struct UiDriver<L: AppLogic> {
+    app: HeadlessApp<L>,
+    events: Vec<egui::Event>,
+    modifiers: egui::Modifiers,
+    pointer: egui::Pos2,
+    hovered_files: Vec<egui::HoveredFile>,
+    widgets: Vec<Widget>,
+}
+
+impl<L: AppLogic> UiDriver<L> {
+    /// Runs two frames first, so windows have been laid out and are visible.
+    pub fn new(app: HeadlessApp<L>) -> Self {
+        app.context().enable_accesskit();
+        let mut driver = Self {
+            app,
+            events: Vec::new(),
+            modifiers: egui::Modifiers::NONE,
+            pointer: egui::Pos2::ZERO,
+            hovered_files: Vec::new(),
+            widgets: Vec::new(),
+        };
+        driver.run_frames(2);
+        driver
+    }
+
+    pub fn app(&self) -> &HeadlessApp<L> {
+        &self.app
+    }
+    pub fn app_mut(&mut self) -> &mut HeadlessApp<L> {
+        &mut self.app
+    }
+    pub fn logic(&self) -> &L {
+        self.app.logic()
+    }
+    pub fn logic_mut(&mut self) -> &mut L {
+        self.app.logic_mut()
+    }
+
+    /// Renders one frame with all events queued since the last one.
+    pub fn step(&mut self) -> egui::PlatformOutput {
+        let mut raw_input = self.app.raw_input();
+        raw_input.events = std::mem::take(&mut self.events);
+        raw_input.modifiers = self.modifiers;
+        raw_input.hovered_files = self.hovered_files.clone();
+        let platform_output = self.app.render_frame(raw_input);
+        if let Some(update) = &platform_output.accesskit_update {
+            self.widgets = update
+                .nodes
+                .iter()
+                .filter_map(|(id, node)| {
+                    let bounds = node.bounds()?;
+                    Some(Widget {
+                        id: *id,
+                        label: node.name().unwrap_or_default().to_string(),
+                        role: node.role(),
+                        rect: egui::Rect::from_min_max(
+                            egui::pos2(bounds.x0 as f32, bounds.y0 as f32),
+                            egui::pos2(bounds.x1 as f32, bounds.y1 as f32),
+                        ),
+                    })
+                })
+                .collect();
+        }
+        platform_output
+    }
+
+    pub fn run_frames(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.step();
+        }
+    }
+
+    /// The widgets of the last frame, in no particular order.
+    pub fn widgets(&self) -> &[Widget] {
+        &self.widgets
+    }
+
+    /// The first widget whose label is `label`, or starts with it.
+    pub fn find(&self, label: &str) -> Option<&Widget> {
+        self.widgets
+            .iter()
+            .find(|widget| widget.label == label)
+            .or_else(|| {
+                self.widgets
+                    .iter()
+                    .find(|widget| widget.label.starts_with(label))
+            })
+    }
+
+    /// Like [`UiDriver::find`], but panics (listing all labels) if there is
+    /// no such widget.
+    #[track_caller]
+    pub fn get(&self, label: &str) -> &Widget {
+        match self.find(label) {
+            Some(widget) => widget,
+            None => {
+                let labels: Vec<&str> = self.widgets.iter().map(|w| w.label.as_str()).collect();
+                panic!("no widget labelled '{label}', found: {labels:?}")
+            }
+        }
+    }
+
+    /// Drags `files` over the app until called again (empty to stop).
+    pub fn hover_files(&mut self, files: Vec<egui::HoveredFile>) {
+        self.hovered_files = files;
+    }
+
+    pub fn set_modifiers(&mut self, modifiers: egui::Modifiers) {
+        self.modifiers = modifiers;
+    }
+
+    pub fn move_pointer(&mut self, pos: egui::Pos2) {
+        self.pointer = pos;
+        self.events.push(egui::Event::PointerMoved(pos));
+    }
+
+    pub fn press(&mut self, button: egui::PointerButton) {
+        self.pointer_button(button, true);
+    }
+
+    pub fn release(&mut self, button: egui::PointerButton) {
+        self.pointer_button(button, false);
+    }
+
+    fn pointer_button(&mut self, button: egui::PointerButton, pressed: bool) {
+        self.events.push(egui::Event::PointerButton {
+            pos: self.pointer,
+            button,
+            pressed,
+            modifiers: self.modifiers,
+        });
+    }
+
+    /// Presses and releases the primary button at `pos`, one frame each.
+    pub fn click_at(&mut self, pos: egui::Pos2) {
+        self.move_pointer(pos);
+        self.press(egui::PointerButton::Primary);
+        self.step();
+        self.release(egui::PointerButton::Primary);
+        self.step();
+    }
+
+    /// Clicks the center of the widget labelled `label` (see
+    /// [`UiDriver::find`]) and runs one more frame, so the UI reflects the
+    /// click.
+    #[track_caller]
+    pub fn click(&mut self, label: &str) {
+        let center = self.get(label).rect.center();
+        self.click_at(center);
+        self.step();
+    }
+
+    /// Moves the keyboard focus to the widget labelled `label` (see
+    /// [`UiDriver::find`]) with the next frame.
+    #[track_caller]
+    pub fn focus(&mut self, label: &str) {
+        let id = self.get(label).id;
+        self.focus_widget(id);
+    }
+
+    pub fn focus_widget(&mut self, id: egui::accesskit::NodeId) {
+        self.events.push(egui::Event::AccessKitActionRequest(
+            egui::accesskit::ActionRequest {
+                action: egui::accesskit::Action::Focus,
+                target: id,
+                data: None,
+            },
+        ));
+    }
+
+    /// Queues a press and release of `key`.
+    pub fn key(&mut self, key: egui::Key) {
+        for pressed in [true, false] {
+            self.events.push(egui::Event::Key {
+                key,
+                physical_key: None,
+                pressed,
+                repeat: false,
+                modifiers: self.modifiers,
+            });
+        }
+    }
+
+    /// Queues `text` as typed into the focused widget.
+    pub fn type_text(&mut self, text: &str) {
+        self.events.push(egui::Event::Text(text.to_string()));
+    }
+}
+
+/// Writes tightly packed RGBA8 pixels (as returned by
+/// [`OffscreenTarget::read_rgba`]) to a PNG file.
+pub fn write_png(path: &std::path::Path, size: PhysicalSize<u32>, rgba: &[u8]) -> Result<()> {
+    let io_err = |source| Error::FileIo {
+        path: path.to_path_buf(),
+        source,
+    };
+    let file = std::fs::File::create(path).map_err(io_err)?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), size.width, size.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(rgba))
+        .map_err(|err| io_err(std::io::Error::other(err)))
+}
+
+/// Reads an 8-bit RGBA PNG, eg. one written by [`write_png`].
+pub fn read_png(path: &std::path::Path) -> Result<(PhysicalSize<u32>, Vec<u8>)> {
+    let io_err = |source| Error::FileIo {
+        path: path.to_path_buf(),
+        source,
+    };
+    let file = std::fs::File::open(path).map_err(io_err)?;
+    let mut reader = png::Decoder::new(std::io::BufReader::new(file))
+        .read_info()
+        .map_err(|err| io_err(std::io::Error::other(err)))?;
+    let mut rgba = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut rgba)
+        .map_err(|err| io_err(std::io::Error::other(err)))?;
+    if (info.color_type, info.bit_depth) != (png::ColorType::Rgba, png::BitDepth::Eight) {
+        return Err(io_err(std::io::Error::other(format!(
+            "expected 8-bit RGBA, found {:?} {:?}",
+            info.bit_depth, info.color_type
+        ))));
+    }
+    rgba.truncate(info.buffer_size());
+    Ok((PhysicalSize::new(info.width, info.height), rgba))
+}
@@ -2,38 +2,233 @@ use std::sync::Arc;
 use wgpu::*;
 use winit::{application::*, dpi::PhysicalSize, event::*, event_loop::*, window::*};
 
+mod checksum;
+mod clock;
+pub mod demo;
+mod drop_zone;
+mod dropped_file;
+mod file_read;
+#[cfg(not(target_family = "wasm"))]
+pub mod headless;
+mod scheduler;
+mod session;
+mod settings;
+mod shader;
+mod timeline;
+
+pub use checksum::{
+    is_checksum_list, parse_checksum_list, Checksum, ChecksumTask, ExpectedChecksum, HashAlgorithm,
+    Hasher,
+};
+pub use clock::{Clock, FixedStepClock, PausableClock, RealClock};
+pub use drop_zone::{FileAcceptance, FileType, FileTypes};
+pub use dropped_file::{file_signature, save_file, sniff_mime_type, DroppedFile, FileSource};
+use file_read::FileReads;
+#[cfg(not(target_family = "wasm"))]
+pub use file_read::{read_dropped_path, read_file};
+pub use file_read::{
+    CancelHandle, DirectoryLimits, FileChunk, FileEvent, FileId, FILE_CHUNK_SIZE,
+    MAX_ASSEMBLED_FILE_SIZE,
+};
+pub use scheduler::RenderMode;
+use scheduler::RenderScheduler;
+use session::SessionState;
+pub use session::{
+    RecordedFile, RecordedFrame, RecordedUserEvent, Session, SessionMode, MAX_RECORDED_FILE_SIZE,
+};
+pub use shader::{
+    check_wgsl, validated, ComputePipelineId, RenderPipelineId, ShaderError, ShaderRegistry,
+    ShaderSources,
+};
+use std::time::Duration;
+pub use timeline::{Animation, Easing, Keyframe, Repeat, Timeline};
+
+//
+// Errors
+//
+
+/// Everything that can go wrong while bringing up or running the app.
+#[derive(Debug)]
+pub enum Error {
+    /// The event loop could not be created or failed while running.
+    EventLoop(winit::error::EventLoopError),
+    /// The OS (or the browser) refused to create a window.
+    Window(winit::error::OsError),
+    /// A `wgpu::Surface` could not be created for the window.
+    Surface(CreateSurfaceError),
+    /// The surface supports no formats at all on the chosen adapter.
+    IncompatibleSurface,
+    /// No adapter matched the requested options.
+    AdapterNotFound,
+    /// The adapter lacks some of the features that were marked as required.
+    UnsupportedFeatures(Features),
+    /// The GPU ran out of memory while acquiring a frame.
+    OutOfMemory,
+    /// The adapter refused to hand out a device.
+    Device(RequestDeviceError),
+    /// Mapping a buffer to read back rendered pixels failed.
+    Readback(BufferAsyncError),
+    /// Reading or writing a file failed.
+    FileIo {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+    /// A path could not be represented as UTF-8.
+    NonUtf8Path(std::path::PathBuf),
+    /// A dropped directory holds more than [`DirectoryLimits`] allow; the
+    /// rest of it was skipped.
+    DirectoryLimit {
+        path: std::path::PathBuf,
+        limits: DirectoryLimits,
+    },
+    /// A file in a dropped directory is none of the [`AppConfig::file_types`];
+    /// it was skipped.
+    UnsupportedFileType(std::path::PathBuf),
+    /// A recorded session could not be encoded or decoded as MessagePack.
+    Session(Box<dyn std::error::Error + Send + Sync>),
+    /// The payload of a [`Message`] does not have the expected shape.
+    InvalidMessage {
+        kind: String,
+        source: serde_json::Error,
+    },
+    /// A DOM lookup on the web page failed (eg. a missing element).
+    WebDom(&'static str),
+    /// A dropped image could not be decoded.
+    Image(image::ImageError),
+    /// A request to the page's origin failed, with the HTTP status if there
+    /// was a response.
+    Fetch { url: String, status: Option<u16> },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EventLoop(err) => write!(f, "event loop error: {err}"),
+            Self::Window(err) => write!(f, "failed to create window: {err}"),
+            Self::Surface(err) => write!(f, "failed to create surface: {err}"),
+            Self::IncompatibleSurface => write!(f, "surface is incompatible with the adapter"),
+            Self::AdapterNotFound => write!(f, "no suitable GPU adapter found"),
+            Self::OutOfMemory => write!(f, "out of GPU memory"),
+            Self::UnsupportedFeatures(features) => {
+                write!(
+                    f,
+                    "adapter does not support required features: {features:?}"
+                )
+            }
+            Self::Device(err) => write!(f, "failed to request device: {err}"),
+            Self::Readback(err) => write!(f, "failed to read back from the GPU: {err}"),
+            Self::FileIo { path, source } => {
+                write!(f, "file I/O failed for '{}': {source}", path.display())
+            }
+            Self::NonUtf8Path(path) => write!(f, "path is not valid UTF-8: {}", path.display()),
+            Self::DirectoryLimit { path, limits } => write!(
+                f,
+                "'{}' holds more than {} files or {} bytes, the rest was skipped",
+                path.display(),
+                limits.max_files,
+                limits.max_total_size
+            ),
+            Self::UnsupportedFileType(path) => {
+                write!(f, "not a supported file type: {}", path.display())
+            }
+            Self::Session(err) => write!(f, "invalid session recording: {err}"),
+            Self::InvalidMessage { kind, source } => {
+                write!(f, "invalid payload for message '{kind}': {source}")
+            }
+            Self::WebDom(what) => write!(f, "web DOM lookup failed: {what}"),
+            Self::Image(err) => write!(f, "failed to decode image: {err}"),
+            Self::Fetch { url, status: None } => write!(f, "failed to fetch '{url}'"),
+            Self::Fetch {
+                url,
+                status: Some(status),
+            } => write!(f, "failed to fetch '{url}': HTTP status {status}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::EventLoop(err) => Some(err),
+            Self::Window(err) => Some(err),
+            Self::Surface(err) => Some(err),
+            Self::Device(err) => Some(err),
+            Self::Readback(err) => Some(err),
+            Self::FileIo { source, .. } => Some(source),
+            Self::Session(err) => Some(err.as_ref()),
+            Self::InvalidMessage { source, .. } => Some(source),
+            Self::Image(err) => Some(err),
+            Self::IncompatibleSurface
+            | Self::AdapterNotFound
+            | Self::OutOfMemory
+            | Self::UnsupportedFeatures(_)
+            | Self::NonUtf8Path(_)
+            | Self::DirectoryLimit { .. }
+            | Self::UnsupportedFileType(_)
+            | Self::WebDom(_)
+            | Self::Fetch { .. } => None,
+        }
+    }
+}
+
+impl From<winit::error::EventLoopError> for Error {
+    fn from(value: winit::error::EventLoopError) -> Self {
+        Self::EventLoop(value)
+    }
+}
+impl From<winit::error::OsError> for Error {
+    fn from(value: winit::error::OsError) -> Self {
+        Self::Window(value)
+    }
+}
+impl From<CreateSurfaceError> for Error {
+    fn from(value: CreateSurfaceError) -> Self {
+        Self::Surface(value)
+    }
+}
+impl From<RequestDeviceError> for Error {
+    fn from(value: RequestDeviceError) -> Self {
+        Self::Device(value)
+    }
+}
+
 //
 // Irrelevant utility shizzle
 //
+
+/// Opt-in logging of the outcome of a fallible call; the value is passed
+/// through unchanged (errors are converted into [`Error`]) so it can be `?`-ed.
 pub trait LogResult {
     type Result;
     fn log_result(self, msg: &str) -> Self::Result;
 }
-impl<T, E: std::fmt::Debug> LogResult for Result<T, E> {
-    type Result = T;
+impl<T, E: Into<Error>> LogResult for std::result::Result<T, E> {
+    type Result = Result<T>;
     fn log_result(self, msg: &str) -> Self::Result {
         match self {
             Ok(x) => {
                 log::info!("{msg} succeeded ({}:{})", file!(), line!());
-                x
+                Ok(x)
             }
             Err(err) => {
-                log::error!("{msg} failed: {err:?} ({}:{})", file!(), line!());
-                panic!("panic!() caused by unexpected (and unhandled) error: {err:?}");
+                let err = err.into();
+                log::error!("{msg} failed: {err} ({}:{})", file!(), line!());
+                Err(err)
             }
         }
     }
 }
 impl<T> LogResult for Option<T> {
-    type Result = T;
+    type Result = Option<T>;
     fn log_result(self, msg: &str) -> Self::Result {
-        if let Some(x) = self {
+        if self.is_some() {
             log::info!("{msg} succeeded ({}:{})", file!(), line!());
-            x
         } else {
             log::error!("{msg} returned `None` ({}:{})", file!(), line!());
-            panic!("panic!() caused by unexpected (and unhandled) None");
         }
+        self
     }
 }
 
@@ -52,22 +247,82 @@ fn system_now() -> String {
 // Relevant code starts here!
 //
 
-struct DroppedFile {
-    name: String,
-    bytes: Vec<u8>,
+/// A typed message between the host (eg. the web page) and the app: what it
+/// is about, plus a JSON payload.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Message {
+    pub kind: String,
+    pub payload: serde_json::Value,
 }
-impl DroppedFile {
-    #[allow(dead_code)]
-    fn new(name: &str, bytes: &[u8]) -> Self {
-        Self {
-            name: name.to_string(),
-            bytes: bytes.iter().copied().collect(),
+impl Message {
+    pub fn new(kind: &str, payload: impl serde::Serialize) -> Result<Self> {
+        let payload = serde_json::to_value(payload).map_err(|source| Error::InvalidMessage {
+            kind: kind.to_string(),
+            source,
+        })?;
+        Ok(Self {
+            kind: kind.to_string(),
+            payload,
+        })
+    }
+
+    /// Deserializes the payload into whatever type `kind` stands for.
+    pub fn payload<T: serde::de::DeserializeOwned>(&self) -> Result<T> {
+        T::deserialize(&self.payload).map_err(|source| Error::InvalidMessage {
+            kind: self.kind.clone(),
+            source,
+        })
+    }
+}
+
+/// The host's callbacks for messages the app posts with
+/// [`AppProxy::post_to_host`]; on the web JS functions are added with
+/// `subscribe`.
+#[derive(Clone, Default)]
+pub struct MessageSubscribers {
+    #[allow(clippy::type_complexity)]
+    callbacks: std::rc::Rc<std::cell::RefCell<Vec<Box<dyn FnMut(&Message)>>>>,
+}
+impl MessageSubscribers {
+    pub fn subscribe(&self, callback: impl FnMut(&Message) + 'static) {
+        self.callbacks.borrow_mut().push(Box::new(callback));
+    }
+
+    fn notify(&self, message: &Message) {
+        // Taken out while running, so callbacks may subscribe themselves.
+        let mut callbacks = std::mem::take(&mut *self.callbacks.borrow_mut());
+        if callbacks.is_empty() {
+            log::debug!("no subscribers for message '{}'", message.kind);
+        }
+        for callback in &mut callbacks {
+            callback(message);
         }
+        let mut current = self.callbacks.borrow_mut();
+        callbacks.append(&mut current);
+        *current = callbacks;
     }
 }
 
-enum UserEvent {
+pub enum UserEvent {
     OnFileDropped(DroppedFile),
+    /// Progress of a file being read in chunks.
+    File(FileEvent),
+    /// Files are being dragged over the drop target; empty once the drag has
+    /// left it or ended. Sent by the framework on the web only, natively winit
+    /// reports this as `WindowEvent::HoveredFile`.
+    HoveredFiles(Vec<egui::HoveredFile>),
+    /// A message from the host for the app logic.
+    Message(Message),
+    /// A message from the app logic for the host's [`MessageSubscribers`].
+    PostToHost(Message),
+    /// Sent by the framework when the GPU device was lost.
+    DeviceLost,
+    /// Sent by the framework when a replacement GPU device is ready (web only,
+    /// natively the device is rebuilt synchronously).
+    DeviceRestored,
+    /// Sent by a [`ShaderRegistry`] when shader code was reloaded or fetched,
+    /// just to get a frame in which its `update` picks that up.
+    ShadersChanged,
 }
 impl From<DroppedFile> for UserEvent {
     fn from(value: DroppedFile) -> Self {
@@ -75,59 +330,367 @@ impl From<DroppedFile> for UserEvent {
     }
 }
 
-const SWAPCHAIN_FORMAT: TextureFormat = TextureFormat::Bgra8Unorm;
+/// How the surface (swapchain) format is picked from the formats the surface
+/// actually supports on the chosen adapter.
+#[derive(Clone, Debug)]
+pub struct SurfaceFormatConfig {
+    /// Formats to try, most preferred first.
+    pub preferred: Vec<TextureFormat>,
+    /// When none of the `preferred` formats is supported: pick an sRGB format
+    /// (`true`) or a linear one (`false`).
+    pub srgb: bool,
+    /// Also allow views in the sRGB/linear counterpart of the chosen format,
+    /// where the platform supports that.
+    pub srgb_view_format: bool,
+}
+impl Default for SurfaceFormatConfig {
+    fn default() -> Self {
+        Self {
+            preferred: vec![TextureFormat::Bgra8Unorm, TextureFormat::Rgba8Unorm],
+            srgb: false,
+            srgb_view_format: true,
+        }
+    }
+}
+impl SurfaceFormatConfig {
+    fn negotiate(
+        &self,
+        capabilities: &SurfaceCapabilities,
+        downlevel: DownlevelFlags,
+    ) -> Option<(TextureFormat, Vec<TextureFormat>)> {
+        let supported = &capabilities.formats;
+        let format = self
+            .preferred
+            .iter()
+            .find(|format| supported.contains(format))
+            .or_else(|| {
+                supported
+                    .iter()
+                    .find(|format| format.is_srgb() == self.srgb)
+            })
+            .or_else(|| supported.first())
+            .copied()?;
+        let counterpart = if format.is_srgb() {
+            format.remove_srgb_suffix()
+        } else {
+            format.add_srgb_suffix()
+        };
+        let view_formats = if self.srgb_view_format
+            && counterpart != format
+            && downlevel.contains(DownlevelFlags::SURFACE_VIEW_FORMATS)
+        {
+            vec![counterpart]
+        } else {
+            Vec::new()
+        };
+        log::info!(
+            "surface format: {format:?} (supported: {supported:?}, view formats: {view_formats:?})"
+        );
+        Some((format, view_formats))
+    }
+}
+
+/// The parts of the surface configuration that can be changed at runtime
+/// without recreating the window or the surface.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PresentSettings {
+    pub present_mode: PresentMode,
+    pub desired_maximum_frame_latency: u32,
+    pub alpha_mode: CompositeAlphaMode,
+}
+impl Default for PresentSettings {
+    fn default() -> Self {
+        Self {
+            present_mode: PresentMode::AutoVsync,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: CompositeAlphaMode::Auto,
+        }
+    }
+}
+impl PresentSettings {
+    pub const PRESENT_MODES: [PresentMode; 6] = [
+        PresentMode::AutoVsync,
+        PresentMode::AutoNoVsync,
+        PresentMode::Fifo,
+        PresentMode::FifoRelaxed,
+        PresentMode::Mailbox,
+        PresentMode::Immediate,
+    ];
+    pub const ALPHA_MODES: [CompositeAlphaMode; 5] = [
+        CompositeAlphaMode::Auto,
+        CompositeAlphaMode::Opaque,
+        CompositeAlphaMode::PreMultiplied,
+        CompositeAlphaMode::PostMultiplied,
+        CompositeAlphaMode::Inherit,
+    ];
+    pub const MAX_FRAME_LATENCY: u32 = 3;
+
+    /// Loads the settings saved by [`PresentSettings::save`], if any.
+    pub fn load() -> Option<Self> {
+        let present_mode = settings::load("present_mode")?;
+        let alpha_mode = settings::load("alpha_mode")?;
+        Some(Self {
+            present_mode: Self::PRESENT_MODES
+                .into_iter()
+                .find(|mode| format!("{mode:?}") == present_mode)?,
+            desired_maximum_frame_latency: settings::load("frame_latency")?.parse().ok()?,
+            alpha_mode: Self::ALPHA_MODES
+                .into_iter()
+                .find(|mode| format!("{mode:?}") == alpha_mode)?,
+        })
+    }
+    pub fn save(&self) {
+        settings::save("present_mode", &format!("{:?}", self.present_mode));
+        settings::save(
+            "frame_latency",
+            &self.desired_maximum_frame_latency.to_string(),
+        );
+        settings::save("alpha_mode", &format!("{:?}", self.alpha_mode));
+    }
+
+    /// Replaces whatever the surface does not support by the automatic
+    /// variants, which are always supported.
+    fn validated(self, capabilities: &SurfaceCapabilities) -> Self {
+        let present_mode = match self.present_mode {
+            PresentMode::AutoVsync | PresentMode::AutoNoVsync => self.present_mode,
+            mode if capabilities.present_modes.contains(&mode) => mode,
+            mode => {
+                log::warn!("present mode {mode:?} is not supported, using AutoVsync");
+                PresentMode::AutoVsync
+            }
+        };
+        let alpha_mode = match self.alpha_mode {
+            CompositeAlphaMode::Auto => CompositeAlphaMode::Auto,
+            mode if capabilities.alpha_modes.contains(&mode) => mode,
+            mode => {
+                log::warn!("alpha mode {mode:?} is not supported, using Auto");
+                CompositeAlphaMode::Auto
+            }
+        };
+        Self {
+            present_mode,
+            desired_maximum_frame_latency: self
+                .desired_maximum_frame_latency
+                .clamp(1, Self::MAX_FRAME_LATENCY),
+            alpha_mode,
+        }
+    }
+
+    /// Settings panel; only offers what the surface supports. Returns `true`
+    /// when the user changed something.
+    fn ui(&mut self, ui: &mut egui::Ui, capabilities: &SurfaceCapabilities) -> bool {
+        let before = *self;
+        egui::ComboBox::from_label("present mode")
+            .selected_text(format!("{:?}", self.present_mode))
+            .show_ui(ui, |ui| {
+                for mode in Self::PRESENT_MODES {
+                    let supported =
+                        matches!(mode, PresentMode::AutoVsync | PresentMode::AutoNoVsync)
+                            || capabilities.present_modes.contains(&mode);
+                    ui.add_enabled_ui(supported, |ui| {
+                        ui.selectable_value(&mut self.present_mode, mode, format!("{mode:?}"));
+                    });
+                }
+            });
+        ui.add(
+            egui::Slider::new(
+                &mut self.desired_maximum_frame_latency,
+                1..=Self::MAX_FRAME_LATENCY,
+            )
+            .text("max. frame latency"),
+        );
+        egui::ComboBox::from_label("alpha mode")
+            .selected_text(format!("{:?}", self.alpha_mode))
+            .show_ui(ui, |ui| {
+                for mode in Self::ALPHA_MODES {
+                    let supported = mode == CompositeAlphaMode::Auto
+                        || capabilities.alpha_modes.contains(&mode);
+                    ui.add_enabled_ui(supported, |ui| {
+                        ui.selectable_value(&mut self.alpha_mode, mode, format!("{mode:?}"));
+                    });
+                }
+            });
+        *self != before
+    }
+}
 
 struct SurfaceState {
     window: Arc<Window>,
     surface: Surface<'static>,
     size: PhysicalSize<u32>,
+    format: TextureFormat,
+    view_formats: Vec<TextureFormat>,
+    capabilities: SurfaceCapabilities,
+    present_settings: PresentSettings,
+    needs_configure: bool,
 }
 impl SurfaceState {
-    fn new(instance: &Instance, window: Arc<Window>) -> Self {
-        let surface = log_result!(instance.create_surface(window.clone()));
-        Self::from_existing(window, surface)
+    fn new(
+        gpu: &GpuState,
+        window: Arc<Window>,
+        config: &SurfaceFormatConfig,
+        present_settings: PresentSettings,
+    ) -> Result<Self> {
+        let surface = log_result!(gpu.instance.create_surface(window.clone()))?;
+        Self::from_existing(gpu, window, surface, config, present_settings)
     }
-    fn from_existing(window: Arc<Window>, surface: Surface<'static>) -> Self {
-        Self {
+    fn from_existing(
+        gpu: &GpuState,
+        window: Arc<Window>,
+        surface: Surface<'static>,
+        config: &SurfaceFormatConfig,
+        present_settings: PresentSettings,
+    ) -> Result<Self> {
+        let capabilities = surface.get_capabilities(&gpu.adapter);
+        let downlevel = gpu.adapter.get_downlevel_capabilities().flags;
+        let (format, view_formats) = config
+            .negotiate(&capabilities, downlevel)
+            .ok_or(Error::IncompatibleSurface)?;
+        let present_settings = present_settings.validated(&capabilities);
+        Ok(Self {
             window,
             surface,
             size: PhysicalSize::new(0, 0),
-        }
+            format,
+            view_formats,
+            capabilities,
+            present_settings,
+            needs_configure: true,
+        })
+    }
+    fn present_settings(&self) -> PresentSettings {
+        self.present_settings
+    }
+    /// Applies the settings (minus what the surface does not support) at the
+    /// next frame; returns what was actually applied.
+    fn set_present_settings(&mut self, present_settings: PresentSettings) -> PresentSettings {
+        self.present_settings = present_settings.validated(&self.capabilities);
+        self.needs_configure = true;
+        self.present_settings
+    }
+    /// The negotiated surface format; everything that renders into the
+    /// surface (egui included) must be built for this format.
+    fn format(&self) -> TextureFormat {
+        self.format
     }
     fn configure(&mut self, device: &Device) -> bool {
         let size = self.window.inner_size();
         let is_ready = size.width > 0 && size.height > 0;
-        if is_ready && self.size != size {
+        if is_ready && (self.size != size || self.needs_configure) {
             self.surface.configure(
                 device,
                 &SurfaceConfiguration {
                     usage: TextureUsages::RENDER_ATTACHMENT,
-                    format: SWAPCHAIN_FORMAT,
+                    format: self.format,
                     width: size.width,
                     height: size.height,
-                    present_mode: PresentMode::AutoVsync,
-                    desired_maximum_frame_latency: 2,
-                    alpha_mode: CompositeAlphaMode::Auto,
-                    view_formats: Vec::new(),
+                    present_mode: self.present_settings.present_mode,
+                    desired_maximum_frame_latency: self
+                        .present_settings
+                        .desired_maximum_frame_latency,
+                    alpha_mode: self.present_settings.alpha_mode,
+                    view_formats: self.view_formats.clone(),
                 },
             );
+            self.needs_configure = false;
         }
         self.size = size;
         is_ready
     }
-    fn current_texture(&mut self, device: &Device) -> Option<SurfaceTexture> {
-        if self.configure(device) {
-            match self.surface.get_current_texture() {
-                Ok(surface_texture) => Some(surface_texture),
-                Err(SurfaceError::OutOfMemory) => panic!("SurfaceError::OutOfMemory"),
-                _ => {
-                    self.size = PhysicalSize::new(0, 0);
-                    None
+    /// Returns `Ok(None)` when there is nothing to render to this frame (eg.
+    /// a minimized window, or a surface that is still recovering).
+    fn current_texture(
+        &mut self,
+        device: &Device,
+        recovery: &mut RecoveryLog,
+    ) -> Result<Option<SurfaceTexture>> {
+        if !self.configure(device) {
+            return Ok(None);
+        }
+        match self.surface.get_current_texture() {
+            Ok(surface_texture) => Ok(Some(surface_texture)),
+            Err(SurfaceError::Timeout) => {
+                recovery.record_skipped_frame("surface timed out");
+                Ok(None)
+            }
+            Err(err @ (SurfaceError::Outdated | SurfaceError::Lost)) => {
+                // Force a reconfigure and give it one more go this frame.
+                recovery.record_surface_retry(&format!("surface {err}"));
+                self.size = PhysicalSize::new(0, 0);
+                if !self.configure(device) {
+                    return Ok(None);
+                }
+                match self.surface.get_current_texture() {
+                    Ok(surface_texture) => Ok(Some(surface_texture)),
+                    Err(SurfaceError::OutOfMemory) => Err(Error::OutOfMemory),
+                    Err(err) => {
+                        recovery.record_skipped_frame(&format!("surface still {err}"));
+                        self.size = PhysicalSize::new(0, 0);
+                        Ok(None)
+                    }
                 }
             }
-        } else {
-            None
+            Err(SurfaceError::OutOfMemory) => Err(Error::OutOfMemory),
+        }
+    }
+}
+
+/// Keeps track of the surface errors and lost devices the app recovered
+/// from, so they show up in the UI instead of only in the log.
+#[derive(Default)]
+struct RecoveryLog {
+    surface_retries: usize,
+    skipped_frames: usize,
+    device_recoveries: usize,
+    entries: std::collections::VecDeque<String>,
+}
+impl RecoveryLog {
+    const MAX_ENTRIES: usize = 32;
+
+    fn push(&mut self, msg: String) {
+        log::warn!("{msg}");
+        if self.entries.len() == Self::MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(format!("{}: {msg}", system_now()));
+    }
+    fn record_surface_retry(&mut self, reason: &str) {
+        self.surface_retries += 1;
+        self.push(format!("{reason}, reconfiguring"));
+    }
+    fn record_skipped_frame(&mut self, reason: &str) {
+        self.skipped_frames += 1;
+        self.push(format!("{reason}, skipping frame"));
+    }
+    fn record_device_recovery(&mut self, reason: &str) {
+        self.device_recoveries += 1;
+        self.push(format!("device lost ({reason}), rebuilding GPU state"));
+    }
+    fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn ui(&self, ctx: &egui::Context) {
+        if self.is_empty() {
+            return;
         }
+        egui::Window::new("GPU recovery")
+            .anchor(egui::Align2::LEFT_BOTTOM, [8.0, -8.0])
+            .default_open(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "surface retries: {}, skipped frames: {}, device recoveries: {}",
+                    self.surface_retries, self.skipped_frames, self.device_recoveries
+                ));
+                egui::ScrollArea::vertical()
+                    .max_height(160.0)
+                    .show(ui, |ui| {
+                        for entry in self.entries.iter().rev() {
+                            ui.label(entry.as_str());
+                        }
+                    });
+            });
     }
 }
 
@@ -143,152 +706,398 @@ fn log_adapter_info(adapter: &Adapter) {
     log::info!("adapter: {name} - {device_type:?} - {backend:?}\nfeatures: {features:?}\nlimits: {limits:?}");
 }
 
-struct GpuState {
+/// Which `wgpu::Limits` to request from the adapter.
+#[derive(Clone, Debug)]
+pub enum LimitsPreset {
+    /// `Limits::default()`: what every WebGPU / Vulkan / Metal / DX12 device supports.
+    Default,
+    /// `Limits::downlevel_defaults()`: works on GLES3 / DX11 class hardware.
+    Downlevel,
+    /// `Limits::downlevel_webgl2_defaults()`: works on (almost) every browser.
+    DownlevelWebGl2,
+    /// Whatever the chosen adapter supports.
+    Adapter,
+    Custom(Limits),
+}
+impl LimitsPreset {
+    fn resolve(&self, adapter: &Adapter) -> Limits {
+        match self {
+            Self::Default => Limits::default(),
+            Self::Downlevel => Limits::downlevel_defaults(),
+            Self::DownlevelWebGl2 => Limits::downlevel_webgl2_defaults(),
+            Self::Adapter => adapter.limits(),
+            Self::Custom(limits) => limits.clone(),
+        }
+    }
+}
+
+/// Everything that goes into picking an adapter and creating a device.
+///
+/// ```no_run
+/// # use wasm_winit_wgpu::*;
+/// let gpu = GpuConfig::new()
+///     .power_preference(wgpu::PowerPreference::LowPower)
+///     .optional_features(wgpu::Features::TIMESTAMP_QUERY)
+///     .limits(LimitsPreset::DownlevelWebGl2);
+/// ```
+#[derive(Clone, Debug)]
+pub struct GpuConfig {
+    backends: Backends,
+    power_preference: PowerPreference,
+    required_features: Features,
+    optional_features: Features,
+    limits: LimitsPreset,
+    force_fallback_adapter: bool,
+    software_fallback: bool,
+    instance_flags: InstanceFlags,
+}
+impl Default for GpuConfig {
+    fn default() -> Self {
+        Self {
+            backends: Backends::PRIMARY,
+            power_preference: PowerPreference::HighPerformance,
+            required_features: Features::empty(),
+            optional_features: Features::empty(),
+            limits: LimitsPreset::Default,
+            force_fallback_adapter: false,
+            software_fallback: true,
+            instance_flags: InstanceFlags::debugging(),
+        }
+    }
+}
+impl GpuConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn backends(mut self, backends: Backends) -> Self {
+        self.backends = backends;
+        self
+    }
+    pub fn power_preference(mut self, power_preference: PowerPreference) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+    /// Features without which initialization fails.
+    pub fn required_features(mut self, features: Features) -> Self {
+        self.required_features = features;
+        self
+    }
+    /// Features that are enabled only when the adapter supports them; check
+    /// [`GpuState::granted_features`] to see which ones made it.
+    pub fn optional_features(mut self, features: Features) -> Self {
+        self.optional_features = features;
+        self
+    }
+    pub fn limits(mut self, limits: LimitsPreset) -> Self {
+        self.limits = limits;
+        self
+    }
+    pub fn force_fallback_adapter(mut self, force_fallback_adapter: bool) -> Self {
+        self.force_fallback_adapter = force_fallback_adapter;
+        self
+    }
+    /// When no adapter matches, retry with `force_fallback_adapter` and then
+    /// with the GL backend (llvmpipe, lavapipe...) before giving up. On by default.
+    pub fn software_fallback(mut self, software_fallback: bool) -> Self {
+        self.software_fallback = software_fallback;
+        self
+    }
+    pub fn instance_flags(mut self, instance_flags: InstanceFlags) -> Self {
+        self.instance_flags = instance_flags;
+        self
+    }
+
+    /// The configuration as given, followed by the progressively more
+    /// forgiving ones to try when `software_fallback` is enabled.
+    fn attempts(&self) -> Vec<GpuConfig> {
+        let mut attempts = vec![self.clone()];
+        if self.software_fallback {
+            if !self.force_fallback_adapter {
+                attempts.push(self.clone().force_fallback_adapter(true));
+            }
+            if self.backends != Backends::GL {
+                attempts.push(
+                    self.clone()
+                        .backends(Backends::GL)
+                        .force_fallback_adapter(false),
+                );
+            }
+        }
+        attempts
+    }
+
+    /// Required features plus the optional ones the adapter supports.
+    fn negotiate_features(&self, adapter: &Adapter) -> Result<Features> {
+        let supported = adapter.features();
+        let missing = self.required_features - supported;
+        if !missing.is_empty() {
+            return Err(Error::UnsupportedFeatures(missing));
+        }
+        let granted = self.optional_features & supported;
+        let denied = self.optional_features - supported;
+        if !self.optional_features.is_empty() {
+            log::info!("optional features granted: {granted:?}, denied: {denied:?}");
+        }
+        Ok(self.required_features | granted)
+    }
+}
+
+/// Rough classification of the adapter, so UI code can turn off expensive
+/// effects when everything is rasterized on the CPU.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AdapterTier {
+    Hardware,
+    Software,
+}
+impl AdapterTier {
+    fn of(info: &AdapterInfo) -> Self {
+        match info.device_type {
+            DeviceType::Cpu => Self::Software,
+            _ => Self::Hardware,
+        }
+    }
+}
+
+pub struct GpuState {
     instance: Instance,
+    adapter: Adapter,
     device: Device,
     queue: Queue,
+    granted_features: Features,
+    tier: AdapterTier,
+    lost: Arc<std::sync::atomic::AtomicBool>,
 }
 impl GpuState {
-    fn instance() -> Instance {
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+    pub fn queue(&self) -> &Queue {
+        &self.queue
+    }
+    pub fn adapter_info(&self) -> AdapterInfo {
+        self.adapter.get_info()
+    }
+    /// The features the device was created with: all required features plus
+    /// the optional ones the adapter turned out to support.
+    pub fn granted_features(&self) -> Features {
+        self.granted_features
+    }
+    pub fn tier(&self) -> AdapterTier {
+        self.tier
+    }
+    /// `true` once the driver reported the device as lost; everything created
+    /// from it has to be rebuilt.
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(std::sync::atomic::Ordering::Acquire)
+    }
+
+    /// Flags the device as lost (and wakes up the event loop) when the driver
+    /// resets, the GPU is unplugged, the machine wakes from sleep...
+    fn watch_device_lost(&self, proxy: AppProxy) {
+        let lost = self.lost.clone();
+        self.device.set_device_lost_callback(move |reason, msg| {
+            if matches!(
+                reason,
+                DeviceLostReason::Unknown | DeviceLostReason::DeviceInvalid
+            ) {
+                log::error!("device lost: {reason:?} {msg}");
+                lost.store(true, std::sync::atomic::Ordering::Release);
+                proxy.send(UserEvent::DeviceLost);
+            }
+        });
+    }
+
+    fn instance(config: &GpuConfig) -> Instance {
         Instance::new(InstanceDescriptor {
-            backends: Backends::PRIMARY,
-            flags: InstanceFlags::debugging(),
+            backends: config.backends,
+            flags: config.instance_flags,
             dx12_shader_compiler: Dx12Compiler::default(),
             gles_minor_version: Gles3MinorVersion::default(),
         })
     }
 
-    #[allow(dead_code)]
-    fn from_window(window: Arc<Window>) -> (Self, SurfaceState) {
-        let instance = Self::instance();
-        let surface = log_result!(instance.create_surface(window.clone()));
-        let adapter = log_result!(pollster::block_on(instance.request_adapter(
-            &RequestAdapterOptions {
-                power_preference: PowerPreference::HighPerformance,
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            }
-        )));
-        log_adapter_info(&adapter);
-        let (device, queue) = log_result!(pollster::block_on(adapter.request_device(
-            &DeviceDescriptor {
-                label: None,
-                required_features: Features::default(),
-                required_limits: Limits::default(),
-            },
-            None
-        )));
-        let gpu_state = Self {
-            instance,
-            device,
-            queue,
-        };
-        (gpu_state, SurfaceState::from_existing(window, surface))
+    /// The one place that picks an adapter and creates a device; used both by
+    /// the (blocking) native path and the async web path. Walks through
+    /// [`GpuConfig::attempts`] and returns the first error if none succeed.
+    async fn init(
+        config: &GpuConfig,
+        window: Option<Arc<Window>>,
+    ) -> Result<(Self, Option<Surface<'static>>)> {
+        let mut first_err = None;
+        for (i, attempt) in config.attempts().iter().enumerate() {
+            if i > 0 {
+                log::warn!(
+                    "retrying GPU initialization: backends = {:?}, force_fallback_adapter = {}",
+                    attempt.backends,
+                    attempt.force_fallback_adapter
+                );
+            }
+            let instance = Self::instance(attempt);
+            let result = match &window {
+                Some(window) => match log_result!(instance.create_surface(window.clone())) {
+                    Ok(surface) => Self::init_with(instance, attempt, Some(&surface))
+                        .await
+                        .map(|gpu_state| (gpu_state, Some(surface))),
+                    Err(err) => Err(err),
+                },
+                None => Self::init_with(instance, attempt, None)
+                    .await
+                    .map(|gpu_state| (gpu_state, None)),
+            };
+            match result {
+                Ok((gpu_state, surface)) => {
+                    log::info!("using {:?} adapter", gpu_state.tier);
+                    return Ok((gpu_state, surface));
+                }
+                Err(err) => {
+                    first_err.get_or_insert(err);
+                }
+            }
+        }
+        Err(first_err.unwrap_or(Error::AdapterNotFound))
     }
 
-    #[allow(dead_code)]
-    async fn init_async() -> Self {
-        let instance = Self::instance();
+    async fn init_with(
+        instance: Instance,
+        config: &GpuConfig,
+        compatible_surface: Option<&Surface<'_>>,
+    ) -> Result<Self> {
         let adapter = log_result!(
             instance
                 .request_adapter(&RequestAdapterOptions {
-                    power_preference: PowerPreference::HighPerformance,
-                    force_fallback_adapter: false,
-                    compatible_surface: None,
+                    power_preference: config.power_preference,
+                    force_fallback_adapter: config.force_fallback_adapter,
+                    compatible_surface,
                 })
                 .await
-        );
+        )
+        .ok_or(Error::AdapterNotFound)?;
         log_adapter_info(&adapter);
+        let granted_features = config.negotiate_features(&adapter)?;
         let (device, queue) = log_result!(
             adapter
                 .request_device(
                     &DeviceDescriptor {
                         label: None,
-                        required_features: Features::default(),
-                        required_limits: Limits::default(),
+                        required_features: granted_features,
+                        required_limits: config.limits.resolve(&adapter),
                     },
                     None
                 )
                 .await
-        );
-        Self {
+        )?;
+        let tier = AdapterTier::of(&adapter.get_info());
+        Ok(Self {
             instance,
+            adapter,
             device,
             queue,
-        }
+            granted_features,
+            tier,
+            lost: Default::default(),
+        })
     }
 
-    fn create_surface(&self, window: Arc<Window>) -> SurfaceState {
-        SurfaceState::new(&self.instance, window)
+    #[allow(dead_code)]
+    fn from_window(
+        config: &GpuConfig,
+        surface_config: &SurfaceFormatConfig,
+        present_settings: PresentSettings,
+        window: Arc<Window>,
+    ) -> Result<(Self, SurfaceState)> {
+        let (gpu_state, surface) = pollster::block_on(Self::init(config, Some(window.clone())))?;
+        let surface = surface.ok_or(Error::IncompatibleSurface)?;
+        let surface = SurfaceState::from_existing(
+            &gpu_state,
+            window,
+            surface,
+            surface_config,
+            present_settings,
+        )?;
+        Ok((gpu_state, surface))
+    }
+
+    #[allow(dead_code)]
+    async fn init_async(config: &GpuConfig) -> Result<Self> {
+        Ok(Self::init(config, None).await?.0)
+    }
+
+    /// A GPU without any surface, for offscreen rendering.
+    pub async fn new_headless(config: &GpuConfig) -> Result<Self> {
+        Self::init_async(config).await
+    }
+    #[cfg(not(target_family = "wasm"))]
+    pub fn headless(config: &GpuConfig) -> Result<Self> {
+        pollster::block_on(Self::new_headless(config))
+    }
+
+    fn create_surface(
+        &self,
+        window: Arc<Window>,
+        config: &SurfaceFormatConfig,
+        present_settings: PresentSettings,
+    ) -> Result<SurfaceState> {
+        SurfaceState::new(self, window, config, present_settings)
     }
 }
 
-pub struct EguiState {
-    pub context: egui::Context,
-    state: egui_winit::State,
+/// The window-independent half of [`EguiState`]: turns egui's output into
+/// draw calls on a color target of a fixed format.
+pub struct EguiPainter {
     renderer: egui_wgpu::Renderer,
+    format: TextureFormat,
+    /// See [`NativeTextures::generation`].
+    generation: u64,
 }
 
-impl EguiState {
-    pub fn new(device: &Device, window: &Window) -> Self {
-        use egui::*;
-        use egui_wgpu::*;
-        use egui_winit::*;
-        let context = Context::default();
-        let viewport_id = ViewportId::ROOT;
-        let native_pixels_per_point = Some(window.scale_factor() as f32);
-        let max_texture_side = device.limits().max_texture_dimension_2d.min(2048);
-        let max_texture_side = Some(max_texture_side as usize);
-        let state = State::new(
-            context.clone(),
-            viewport_id,
-            &window,
-            native_pixels_per_point,
-            max_texture_side,
-        );
-        let renderer = Renderer::new(device, SWAPCHAIN_FORMAT, None, 1);
-
+impl EguiPainter {
+    pub fn new(device: &Device, format: TextureFormat) -> Self {
+        static NEXT_GENERATION: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
         Self {
-            context,
-            state,
-            renderer,
+            renderer: egui_wgpu::Renderer::new(device, format, None, 1),
+            format,
+            generation: NEXT_GENERATION.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
         }
     }
 
-    pub fn handle_input(&mut self, window: &Window, event: &WindowEvent) {
-        let _ = self.state.on_window_event(window, event);
+    /// The color format the renderer was built for.
+    pub fn format(&self) -> TextureFormat {
+        self.format
     }
 
-    pub fn draw(
+    /// Lets wgpu textures be shown in egui, see [`AppLogic::update_textures`].
+    pub fn native_textures<'a>(&'a mut self, device: &'a Device) -> NativeTextures<'a> {
+        NativeTextures {
+            renderer: &mut self.renderer,
+            device,
+            generation: self.generation,
+        }
+    }
+
+    /// Draws `full_output` on top of whatever is in `view` and returns how
+    /// long egui says it can wait before the next repaint.
+    #[allow(clippy::too_many_arguments)]
+    pub fn paint(
         &mut self,
         device: &Device,
         queue: &Queue,
         encoder: &mut CommandEncoder,
-        window: &Window,
-        window_surface_view: &TextureView,
+        view: &TextureView,
         screen_descriptor: egui_wgpu::ScreenDescriptor,
-        run_ui: impl FnOnce(&egui::Context),
-    ) {
-        let raw_input = self.state.take_egui_input(&window);
-        let full_output = self.context.run(raw_input, |ui| {
-            run_ui(ui);
-        });
-        self.state
-            .handle_platform_output(window, full_output.platform_output);
-        let pixels_per_point = window.scale_factor() as f32;
-        let tris = self
-            .context
-            .tessellate(full_output.shapes, pixels_per_point);
+        context: &egui::Context,
+        full_output: egui::FullOutput,
+    ) -> std::time::Duration {
+        let tris = context.tessellate(full_output.shapes, screen_descriptor.pixels_per_point);
         for (id, image_delta) in &full_output.textures_delta.set {
             self.renderer
-                .update_texture(&device, &queue, *id, &image_delta);
+                .update_texture(device, queue, *id, image_delta);
         }
         self.renderer
-            .update_buffers(&device, &queue, encoder, &tris, &screen_descriptor);
+            .update_buffers(device, queue, encoder, &tris, &screen_descriptor);
         let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("egui RenderPass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &window_surface_view,
+                view,
                 resolve_target: None,
                 ops: wgpu::Operations {
                     load: wgpu::LoadOp::Load,
@@ -304,294 +1113,1112 @@ impl EguiState {
         for x in &full_output.textures_delta.free {
             self.renderer.free_texture(x)
         }
+        full_output
+            .viewport_output
+            .get(&egui::ViewportId::ROOT)
+            .map_or(std::time::Duration::MAX, |output| output.repaint_delay)
     }
 }
 
-struct UiState {
-    num_clicks: usize,
-    checked: bool,
-    num_checks: usize,
-    dropped_files: Vec<(String, Vec<u8>, usize)>,
+/// The textures of the egui renderer that the app logic created itself, to
+/// show with eg. [`egui::Image`] or [`egui::Painter::image`].
+pub struct NativeTextures<'a> {
+    renderer: &'a mut egui_wgpu::Renderer,
+    device: &'a Device,
+    generation: u64,
 }
-impl UiState {
-    fn new() -> Self {
-        Self {
-            num_clicks: 0,
-            checked: false,
-            num_checks: 0,
-            dropped_files: Vec::new(),
-        }
+
+impl NativeTextures<'_> {
+    /// Makes `view` available to egui under the returned id; the view must
+    /// be of a `Rgba8UnormSrgb` texture with premultiplied alpha, like
+    /// egui's own.
+    pub fn register(&mut self, view: &TextureView, filter: FilterMode) -> egui::TextureId {
+        self.renderer
+            .register_native_texture(self.device, view, filter)
     }
-    fn run_egui(&mut self, ctx: &egui::Context) {
-        egui::Window::new("Test egui window")
-            .resizable([true, true])
-            .show(ctx, |ui| {
-                let button_text = match self.num_clicks {
-                    0 => "I dare you! I double-dare you!".to_string(),
-                    1 => "Oo-ooh! Now you've done it!".to_string(),
-                    2 => "Oo-ooh! Now you've done it! Twice! >:[".to_string(),
-                    _ => format!(
-                        "Oo-ooh! Now you've done it! {} times already... m(_ _)m",
-                        self.num_clicks
-                    ),
-                };
-                if ui.button(button_text).clicked() {
-                    self.num_clicks += 1;
-                }
-                if self.num_clicks > 0 {
-                    ui.label(format!(
-                        "You've clicked the button {} time(s)",
-                        self.num_clicks
-                    ));
-                }
-                if ui.checkbox(&mut self.checked, "Some checkbox").changed() {
-                    if self.checked {
-                        self.num_checks += 1;
-                    }
-                }
-                let label_text = if self.checked {
-                    "The checkbox *is* checked"
-                } else {
-                    "The checkbox is *not* checked"
-                };
-                ui.label(label_text);
-                ui.label(format!(
-                    "The checkbox has been checked {} time(s)",
-                    self.num_checks
-                ));
 
-                if !self.dropped_files.is_empty() {
-                    egui::Grid::new("dropped files").show(ui, |ui| {
-                        ui.label("filename");
-                        ui.label("size (bytes)");
-                        ui.end_row();
-                        for (name, _bytes, sum) in &self.dropped_files {
-                            ui.label(name.as_str());
-                            ui.label(format!("{}", *sum));
-                            ui.end_row();
-                        }
-                    });
-                }
-            });
+    /// Points `id` at another texture view.
+    pub fn update(&mut self, id: egui::TextureId, view: &TextureView, filter: FilterMode) {
+        self.renderer
+            .update_egui_texture_from_wgpu_texture(self.device, view, filter, id);
     }
-    fn drop_file(&mut self, name: String, bytes: Vec<u8>) {
-        let sum: usize = bytes.iter().map(|b| *b as usize).sum();
-        self.dropped_files.push((name, bytes, sum));
+
+    pub fn free(&mut self, id: egui::TextureId) {
+        self.renderer.free_texture(&id);
     }
-}
 
-struct App {
+    /// Changes whenever the egui renderer is rebuilt (for a new surface
+    /// format or a lost device), which forgets every registered texture;
+    /// they have to be registered again then.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The largest width and height of a texture on this device.
+    pub fn max_texture_side(&self) -> u32 {
+        self.device.limits().max_texture_dimension_2d
+    }
+}
+
+pub struct EguiState {
+    pub context: egui::Context,
+    state: egui_winit::State,
+    painter: EguiPainter,
+}
+
+impl EguiState {
+    pub fn new(device: &Device, window: &Window, format: TextureFormat) -> Self {
+        use egui::*;
+        use egui_winit::*;
+        let context = Context::default();
+        let viewport_id = ViewportId::ROOT;
+        let native_pixels_per_point = Some(window.scale_factor() as f32);
+        let max_texture_side = device.limits().max_texture_dimension_2d.min(2048);
+        let max_texture_side = Some(max_texture_side as usize);
+        let state = State::new(
+            context.clone(),
+            viewport_id,
+            &window,
+            native_pixels_per_point,
+            max_texture_side,
+        );
+        let painter = EguiPainter::new(device, format);
+
+        Self {
+            context,
+            state,
+            painter,
+        }
+    }
+
+    /// The color format the renderer was built for.
+    pub fn format(&self) -> TextureFormat {
+        self.painter.format()
+    }
+
+    /// See [`EguiPainter::native_textures`].
+    pub fn native_textures<'a>(&'a mut self, device: &'a Device) -> NativeTextures<'a> {
+        self.painter.native_textures(device)
+    }
+
+    /// Returns `true` when egui wants to repaint because of this event.
+    pub fn handle_input(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.state.on_window_event(window, event).repaint
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        window: &Window,
+        window_surface_view: &TextureView,
+        screen_descriptor: egui_wgpu::ScreenDescriptor,
+        process_input: impl FnOnce(&mut egui::RawInput),
+        run_ui: impl FnOnce(&egui::Context),
+    ) -> std::time::Duration {
+        let mut raw_input = self.state.take_egui_input(window);
+        process_input(&mut raw_input);
+        let mut full_output = self.context.run(raw_input, |ui| {
+            run_ui(ui);
+        });
+        let platform_output = std::mem::take(&mut full_output.platform_output);
+        self.state.handle_platform_output(window, platform_output);
+        self.painter.paint(
+            device,
+            queue,
+            encoder,
+            window_surface_view,
+            screen_descriptor,
+            &self.context,
+            full_output,
+        )
+    }
+}
+
+//
+// Embedding API
+//
+
+/// Where an app instance renders on the web page.
+#[derive(Clone, Debug)]
+pub enum CanvasTarget {
+    /// The `<canvas>` element with this id.
+    Id(String),
+    /// A new `<canvas>` inside the element with this id.
+    ContainerId(String),
+    #[cfg(target_family = "wasm")]
+    Element(web_sys::HtmlCanvasElement),
+    /// A new `<canvas>` inside this element.
+    #[cfg(target_family = "wasm")]
+    Container(web_sys::Element),
+}
+impl Default for CanvasTarget {
+    fn default() -> Self {
+        Self::Id("rust_canvas".to_string())
+    }
+}
+
+/// Which element accepts dropped files on the web page; natively files can be
+/// dropped anywhere on the window.
+#[derive(Clone, Debug, Default)]
+pub enum FileDropTarget {
+    /// The canvas the app renders into.
+    #[default]
+    Canvas,
+    /// The element with this id.
+    Id(String),
+    /// This element, eg. the whole `<body>`.
+    #[cfg(target_family = "wasm")]
+    Element(web_sys::EventTarget),
+    /// No listeners are installed; the host page hands files over itself.
+    None,
+}
+
+/// Settings for [`run`] that are not part of the app logic itself.
+pub struct AppConfig {
+    /// Title of the native window (ignored on the web).
+    pub title: String,
+    /// The canvas to render into (ignored natively). Canvases created in a
+    /// container are removed again when the instance is destroyed.
+    pub canvas: CanvasTarget,
+    pub file_drop: FileDropTarget,
+    /// Dropped files up to this size are also delivered whole, to
+    /// [`AppLogic::on_file_dropped`]; larger ones only in chunks, to
+    /// [`AppLogic::on_file_event`].
+    pub max_assembled_file_size: u64,
+    /// For directories dropped onto the native window.
+    pub directory_limits: DirectoryLimits,
+    /// What the drop-zone overlay accepts; dropped files of other types are
+    /// ignored, also those in dropped directories.
+    pub file_types: FileTypes,
+    pub gpu: GpuConfig,
+    pub surface_format: SurfaceFormatConfig,
+    /// Used when no settings were saved by an earlier run.
+    pub present_settings: PresentSettings,
+    /// Show the built-in "Surface settings" panel.
+    pub show_surface_settings: bool,
+    pub render_mode: RenderMode,
+    /// Upper bound on the frame rate, on top of what the present mode does.
+    pub fps_cap: Option<u32>,
+    /// Time source handed to [`AppLogic::update`].
+    pub clock: Box<dyn Clock>,
+    /// Record the session from the start, or replay a recorded one.
+    pub session: SessionMode,
+    /// Receive the messages the app posts to the host.
+    pub subscribers: MessageSubscribers,
+}
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            title: "wasm_winit_wgpu".to_string(),
+            canvas: CanvasTarget::default(),
+            file_drop: FileDropTarget::default(),
+            max_assembled_file_size: MAX_ASSEMBLED_FILE_SIZE,
+            directory_limits: DirectoryLimits::default(),
+            file_types: FileTypes::default(),
+            gpu: GpuConfig::default(),
+            surface_format: SurfaceFormatConfig::default(),
+            present_settings: PresentSettings::default(),
+            show_surface_settings: true,
+            render_mode: RenderMode::Reactive,
+            fps_cap: None,
+            clock: Box::new(RealClock::new()),
+            session: SessionMode::Off,
+            subscribers: MessageSubscribers::default(),
+        }
+    }
+}
+
+/// Handle that lets the app logic (or any thread it spawns) post
+/// [`UserEvent`]s back into the event loop, addressed to its own app instance.
+#[derive(Clone)]
+pub struct AppProxy {
+    inner: AppProxyInner,
+}
+#[derive(Clone)]
+enum AppProxyInner {
+    EventLoop(EventLoopProxy<LoopEvent>, InstanceId),
+    /// Without an event loop (headless) events are queued until the next frame.
+    Queue(Arc<std::sync::Mutex<std::collections::VecDeque<UserEvent>>>),
+}
+impl AppProxy {
+    fn new(proxy: EventLoopProxy<LoopEvent>, instance: InstanceId) -> Self {
+        Self {
+            inner: AppProxyInner::EventLoop(proxy, instance),
+        }
+    }
+    #[allow(dead_code)]
+    fn queued() -> Self {
+        Self {
+            inner: AppProxyInner::Queue(Default::default()),
+        }
+    }
+    #[allow(dead_code)]
+    fn take_queued(&self) -> Vec<UserEvent> {
+        match &self.inner {
+            AppProxyInner::EventLoop(..) => Vec::new(),
+            AppProxyInner::Queue(queue) => match queue.lock() {
+                Ok(mut queue) => queue.drain(..).collect(),
+                Err(_) => Vec::new(),
+            },
+        }
+    }
+
+    /// Returns `false` if the event loop has already exited.
+    pub fn send(&self, event: impl Into<UserEvent>) -> bool {
+        match &self.inner {
+            AppProxyInner::EventLoop(proxy, instance) => proxy
+                .send_event(LoopEvent::User(*instance, event.into()))
+                .is_ok(),
+            AppProxyInner::Queue(queue) => match queue.lock() {
+                Ok(mut queue) => {
+                    queue.push_back(event.into());
+                    true
+                }
+                Err(_) => false,
+            },
+        }
+    }
+    /// Delivers `message` to [`AppLogic::on_user_message`].
+    pub fn send_message(&self, message: Message) -> bool {
+        self.send(UserEvent::Message(message))
+    }
+    /// Delivers `message` to the host's [`MessageSubscribers`].
+    pub fn post_to_host(&self, message: Message) -> bool {
+        self.send(UserEvent::PostToHost(message))
+    }
+    /// Shuts the app instance down (like closing its window); the event loop
+    /// exits with the last one. Does nothing without an event loop.
+    pub fn close(&self) -> bool {
+        match &self.inner {
+            AppProxyInner::EventLoop(proxy, instance) => {
+                proxy.send_event(LoopEvent::Destroy(*instance)).is_ok()
+            }
+            AppProxyInner::Queue(_) => false,
+        }
+    }
+}
+
+/// The color target of the current frame, handed to [`AppLogic::render`].
+/// Whatever is rendered here ends up underneath the egui layer.
+pub struct RenderTarget<'a> {
+    pub encoder: &'a mut CommandEncoder,
+    pub view: &'a TextureView,
+    pub format: TextureFormat,
+    pub size: PhysicalSize<u32>,
+}
+impl RenderTarget<'_> {
+    /// Clears the whole target to a single color.
+    pub fn clear(self, color: Color) {
+        let _render_pass = self.encoder.begin_render_pass(&RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: self.view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(color),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+    }
+}
+
+/// Everything an embedder has to provide to get their own UI and rendering
+/// on screen; the window, surface, GPU and egui plumbing is handled by `run`.
+pub trait AppLogic: 'static {
+    /// Called once, as soon as the GPU is available and before the first frame.
+    fn init(&mut self, _gpu: &GpuState, _proxy: AppProxy) {}
+    /// Called at the start of every frame, before [`AppLogic::ui`], with the
+    /// current time of the app's [`Clock`].
+    fn update(&mut self, _gpu: &GpuState, _now: Duration) {}
+    /// Keeps frames coming while `true`; in [`RenderMode::Reactive`] nothing
+    /// is rendered otherwise until input or an event arrives.
+    fn is_animating(&self) -> bool {
+        false
+    }
+    /// Builds the egui UI for the current frame.
+    fn ui(&mut self, ctx: &egui::Context);
+    /// Called every frame before [`AppLogic::ui`], to register (or free) the
+    /// wgpu textures the UI shows.
+    fn update_textures(&mut self, _gpu: &GpuState, _textures: &mut NativeTextures<'_>) {}
+    /// Renders the background underneath the egui layer. The target must be
+    /// cleared (or fully overwritten); the default clears it to black.
+    fn render(&mut self, _gpu: &GpuState, target: RenderTarget<'_>) {
+        target.clear(Color::BLACK);
+    }
+    /// Called after the GPU device was lost and replaced; everything created
+    /// from the old device (buffers, textures, pipelines...) must be rebuilt.
+    fn on_device_recreated(&mut self, _gpu: &GpuState) {}
+    fn on_file_dropped(&mut self, _dropped_file: DroppedFile) {}
+    /// A dropped file as it is being read, one chunk at a time; lets files of
+    /// any size be processed without holding all of their bytes.
+    fn on_file_event(&mut self, _event: FileEvent) {}
+    /// A message from the host; answers go back through
+    /// [`AppProxy::post_to_host`].
+    fn on_user_message(&mut self, _message: Message) {}
+    /// Called once when the event loop is about to exit.
+    fn shutdown(&mut self) {}
+}
+
+/// Tells apart the app instances sharing one event loop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct InstanceId(u64);
+impl InstanceId {
+    /// For events that concern every instance (eg. a lost device).
+    const ALL: Self = Self(0);
+
+    fn next() -> Self {
+        static NEXT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+        Self(NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed))
+    }
+}
+
+/// What travels through the event loop shared by all app instances.
+enum LoopEvent {
+    User(InstanceId, UserEvent),
+    /// An instance started by a later call to `run` (web only).
+    #[cfg(target_family = "wasm")]
+    Spawn(Box<AppInstance>),
+    Destroy(InstanceId),
+}
+
+/// The window-independent half of an [`AppInstance`]: the app logic with the
+/// framework state around it, and the frames they render. Also what
+/// `headless::HeadlessApp` runs, so headless frames are the real thing.
+struct InstanceCore<L: AppLogic + ?Sized> {
+    logic: Box<L>,
+    config: AppConfig,
+    scheduler: RenderScheduler,
+    session: SessionState,
+    /// From [`UserEvent::HoveredFiles`], handed to egui with every frame.
+    hovered_files: Vec<egui::HoveredFile>,
+    file_reads: FileReads,
+}
+
+/// What [`InstanceCore::frame`] leaves to the window (or headless app).
+struct FrameOutput {
+    platform_output: egui::PlatformOutput,
+    /// In the "Surface settings" window.
+    present_settings_changed: bool,
+}
+
+impl<L: AppLogic + ?Sized> InstanceCore<L> {
+    fn new(mut config: AppConfig, logic: Box<L>) -> Self {
+        let scheduler = RenderScheduler::new(config.render_mode, config.fps_cap);
+        let session = SessionState::new(std::mem::replace(&mut config.session, SessionMode::Off));
+        let file_reads = FileReads::new(config.max_assembled_file_size);
+        Self {
+            logic,
+            config,
+            scheduler,
+            session,
+            hovered_files: Vec::new(),
+            file_reads,
+        }
+    }
+
+    /// Renders one frame into `target`: the app logic updates and renders,
+    /// then egui draws the app's UI and the framework's own windows on top.
+    /// `window` is resized for session replays; the present settings are
+    /// only offered with a surface.
+    #[allow(clippy::too_many_arguments)]
+    fn frame(
+        &mut self,
+        gpu_state: &GpuState,
+        target: RenderTarget<'_>,
+        context: &egui::Context,
+        painter: &mut EguiPainter,
+        mut raw_input: egui::RawInput,
+        pixels_per_point: f32,
+        recovery: &RecoveryLog,
+        window: Option<&Window>,
+        mut surface_settings: Option<(&mut PresentSettings, &SurfaceCapabilities)>,
+    ) -> FrameOutput {
+        let RenderTarget {
+            encoder,
+            view,
+            format,
+            size,
+        } = target;
+        let replay_frame = self.session.next_replay_frame(window);
+        let now = match &replay_frame {
+            Some(frame) => {
+                for event in &frame.user_events {
+                    match event {
+                        RecordedUserEvent::FileDropped(file) => {
+                            self.logic.on_file_dropped(file.to_dropped_file())
+                        }
+                        RecordedUserEvent::Message(message) => {
+                            self.logic.on_user_message(message.clone())
+                        }
+                    }
+                }
+                Duration::from_secs_f64(frame.time)
+            }
+            None => self.config.clock.now(),
+        };
+        self.logic.update(gpu_state, now);
+        self.logic.render(
+            gpu_state,
+            RenderTarget {
+                encoder: &mut *encoder,
+                view,
+                format,
+                size,
+            },
+        );
+        self.logic
+            .update_textures(gpu_state, &mut painter.native_textures(&gpu_state.device));
+
+        raw_input
+            .hovered_files
+            .extend(self.hovered_files.iter().cloned());
+        let mut recorded_frame = None;
+        match replay_frame {
+            Some(frame) => frame.apply_to(&mut raw_input),
+            None => {
+                // egui runs on the app's clock too, so replays match.
+                raw_input.time = Some(now.as_secs_f64());
+                if self.session.is_recording() {
+                    recorded_frame = Some(RecordedFrame::from_input(&raw_input));
+                }
+            }
+        }
+        let Self {
+            logic,
+            config,
+            scheduler,
+            session,
+            file_reads,
+            ..
+        } = self;
+        let mut present_settings_changed = false;
+        let mut full_output = context.run(raw_input, |ctx| {
+            logic.ui(ctx);
+            recovery.ui(ctx);
+            file_reads.ui(ctx);
+            drop_zone::show(ctx, &config.file_types);
+            if config.show_surface_settings {
+                egui::Window::new("Surface settings")
+                    .anchor(egui::Align2::RIGHT_TOP, [-8.0, 8.0])
+                    .default_open(false)
+                    .resizable(false)
+                    .show(ctx, |ui| {
+                        if let Some((present_settings, capabilities)) = &mut surface_settings {
+                            present_settings_changed = present_settings.ui(ui, capabilities);
+                            ui.separator();
+                        }
+                        scheduler.ui(ui);
+                        ui.separator();
+                        session.ui(ui);
+                    });
+            }
+        });
+        let platform_output = std::mem::take(&mut full_output.platform_output);
+        let repaint_delay = painter.paint(
+            &gpu_state.device,
+            &gpu_state.queue,
+            encoder,
+            view,
+            egui_wgpu::ScreenDescriptor {
+                size_in_pixels: [size.width, size.height],
+                pixels_per_point,
+            },
+            context,
+            full_output,
+        );
+        if let Some(frame) = recorded_frame {
+            self.session.record_frame(frame);
+        }
+        self.config.clock.tick();
+        self.scheduler.frame_rendered(
+            repaint_delay,
+            self.logic.is_animating() || self.session.is_replaying(),
+        );
+        FrameOutput {
+            platform_output,
+            present_settings_changed,
+        }
+    }
+
+    fn on_file_dropped(&mut self, dropped_file: DroppedFile) {
+        if self.session.on_file_dropped(&dropped_file) {
+            self.logic.on_file_dropped(dropped_file);
+        }
+        self.scheduler.request_now();
+    }
+
+    fn on_file_event(&mut self, mut event: FileEvent) {
+        if self.session.is_replaying() {
+            // Like other live input; replays bring their own files.
+            if let FileEvent::Started { name, cancel, .. } = &event {
+                log::info!("ignoring '{name}' while replaying a session");
+                cancel.cancel();
+            }
+            return;
+        }
+        let dropped_file = self.file_reads.on_event(&mut event);
+        self.logic.on_file_event(event);
+        if let Some(dropped_file) = dropped_file {
+            self.on_file_dropped(dropped_file);
+        }
+    }
+
+    fn user_event(&mut self, event: UserEvent) {
+        use UserEvent as UE;
+        match event {
+            UE::OnFileDropped(dropped_file) => self.on_file_dropped(dropped_file),
+            UE::File(event) => self.on_file_event(event),
+            UE::HoveredFiles(hovered_files) => self.hovered_files = hovered_files,
+            UE::Message(message) => {
+                if self.session.on_message(&message) {
+                    self.logic.on_user_message(message);
+                }
+            }
+            UE::PostToHost(message) => self.config.subscribers.notify(&message),
+            // Handled by `App`, for all instances at once.
+            UE::DeviceLost | UE::DeviceRestored => (),
+            UE::ShadersChanged => (),
+        }
+        self.scheduler.request_now();
+    }
+
+    fn shutdown(&mut self) {
+        self.session.finish();
+        self.logic.shutdown();
+    }
+}
+
+/// One window (natively) or canvas (on the web) with its own app logic,
+/// surface and egui state.
+struct AppInstance {
+    id: InstanceId,
     window: Option<Arc<winit::window::Window>>,
     surface: Option<SurfaceState>,
-    gpu_state: Option<GpuState>,
     egui_state: Option<EguiState>,
-    ui_state: UiState,
-    start_millis: i64,
+    core: InstanceCore<dyn AppLogic>,
+    proxy: AppProxy,
+    is_initialized: bool,
+    /// What the user picked in the settings panel (or what was saved).
+    present_settings: PresentSettings,
+    /// egui memory saved while the egui renderer is being rebuilt.
+    egui_memory: Option<egui::Memory>,
+    /// Resolved from `config.canvas` when the window is first created; the
+    /// flag is set when the canvas was created by us (and has to go again).
+    #[cfg(target_family = "wasm")]
+    canvas: Option<(web_sys::HtmlCanvasElement, bool)>,
+    /// Removed again when dropped.
+    #[cfg(target_family = "wasm")]
+    file_drop: Option<wasm::FileDropListeners>,
 }
-impl App {
+impl AppInstance {
+    fn new(config: AppConfig, logic: Box<dyn AppLogic>, proxy: EventLoopProxy<LoopEvent>) -> Self {
+        let id = InstanceId::next();
+        let present_settings = PresentSettings::load().unwrap_or(config.present_settings);
+        Self {
+            id,
+            window: None,
+            surface: None,
+            egui_state: None,
+            core: InstanceCore::new(config, logic),
+            proxy: AppProxy::new(proxy, id),
+            is_initialized: false,
+            present_settings,
+            egui_memory: None,
+            #[cfg(target_family = "wasm")]
+            canvas: None,
+            #[cfg(target_family = "wasm")]
+            file_drop: None,
+        }
+    }
+
     #[cfg(target_family = "wasm")]
-    fn create_window(event_loop: &ActiveEventLoop) -> Window {
-        use wasm_bindgen::prelude::*;
-        let window = log_result!(web_sys::window());
-        let document = log_result!(window.document());
-        let canvas = log_result!(document.get_element_by_id("rust_canvas"));
-        let canvas: web_sys::HtmlCanvasElement =
-            log_result!(canvas.dyn_into::<web_sys::HtmlCanvasElement>());
+    fn create_window(&mut self, event_loop: &ActiveEventLoop) -> Result<Window> {
+        let canvas = match &self.canvas {
+            Some((canvas, _)) => canvas.clone(),
+            None => {
+                let (canvas, created) = wasm::canvas_element(&self.core.config.canvas)?;
+                self.file_drop = wasm::FileDropListeners::install(
+                    &self.core.config.file_drop,
+                    &canvas,
+                    self.core.config.file_types.clone(),
+                    self.proxy.clone(),
+                )?;
+                self.canvas = Some((canvas.clone(), created));
+                canvas
+            }
+        };
         use winit::platform::web::WindowAttributesExtWebSys;
         log_result!(event_loop.create_window(Window::default_attributes().with_canvas(Some(canvas))))
     }
 
     #[cfg(not(target_family = "wasm"))]
-    fn create_window(event_loop: &ActiveEventLoop) -> Window {
-        log_result!(event_loop.create_window(Window::default_attributes()))
+    fn create_window(&mut self, event_loop: &ActiveEventLoop) -> Result<Window> {
+        let attributes = Window::default_attributes().with_title(self.core.config.title.as_str());
+        log_result!(event_loop.create_window(attributes))
     }
 
-    fn new() -> Self {
+    fn window_or_create(&mut self, event_loop: &ActiveEventLoop) -> Result<Arc<Window>> {
+        if let Some(window) = &self.window {
+            return Ok(window.clone());
+        }
+        let window = Arc::new(self.create_window(event_loop)?);
+        self.window = Some(window.clone());
+        Ok(window)
+    }
+
+    fn create_surface(&mut self, gpu_state: &GpuState) -> Result<()> {
+        if let (Some(window), None) = (&self.window, &self.surface) {
+            self.surface = Some(gpu_state.create_surface(
+                window.clone(),
+                &self.core.config.surface_format,
+                self.present_settings,
+            )?);
+            // Frames skipped without a surface are not retried on their own.
+            self.core.scheduler.request_now();
+        }
+        Ok(())
+    }
+
+    fn set_gpu_state(&mut self, gpu_state: &GpuState) {
+        if self.is_initialized {
+            self.core.logic.on_device_recreated(gpu_state);
+        } else {
+            self.core.logic.init(gpu_state, self.proxy.clone());
+            self.is_initialized = true;
+        }
+    }
+
+    /// Drops everything that was created from the current device; the egui
+    /// memory (window positions etc.) survives.
+    fn drop_gpu_resources(&mut self) {
+        if let Some(egui_state) = self.egui_state.take() {
+            self.egui_memory = Some(egui_state.context.memory(|memory| memory.clone()));
+        }
+        self.surface = None;
+    }
+
+    fn redraw(&mut self, gpu_state: &GpuState, recovery: &mut RecoveryLog) -> Result<()> {
+        let (Some(window), Some(surface_state)) = (&self.window, &mut self.surface) else {
+            // `create_surface` requests a frame once there is a surface.
+            self.core.scheduler.frame_skipped(None);
+            return Ok(());
+        };
+        let surface_texture = match surface_state.current_texture(&gpu_state.device, recovery) {
+            Ok(Some(surface_texture)) => surface_texture,
+            result => {
+                // Try again in a bit, unless there is nothing to draw on at
+                // all (minimized); a `Resized` event will wake us up then.
+                let size = window.inner_size();
+                let retry =
+                    (size.width > 0 && size.height > 0).then_some(Duration::from_millis(100));
+                self.core.scheduler.frame_skipped(retry);
+                return result.map(|_| ());
+            }
+        };
+        let format = surface_state.format();
+        let view = surface_texture.texture.create_view(&TextureViewDescriptor {
+            label: None,
+            format: Some(format),
+            dimension: Some(TextureViewDimension::D2),
+            aspect: TextureAspect::All,
+            base_mip_level: 0,
+            mip_level_count: Some(1),
+            base_array_layer: 0,
+            array_layer_count: Some(1),
+        });
+        let mut encoder = gpu_state
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor { label: None });
+
+        // A new surface may have negotiated a different format than the one
+        // the egui renderer was built for.
+        if let Some(egui_state) = &self.egui_state {
+            if egui_state.format() != format {
+                self.egui_memory = Some(egui_state.context.memory(|memory| memory.clone()));
+                self.egui_state = None;
+            }
+        }
+        let egui_memory = &mut self.egui_memory;
+        let egui_state = self.egui_state.get_or_insert_with(|| {
+            let egui_state = EguiState::new(&gpu_state.device, window, format);
+            if let Some(memory) = egui_memory.take() {
+                egui_state.context.memory_mut(|m| *m = memory);
+            }
+            egui_state
+        });
+        let raw_input = egui_state.state.take_egui_input(window);
+        let mut present_settings = surface_state.present_settings();
+        let output = self.core.frame(
+            gpu_state,
+            RenderTarget {
+                encoder: &mut encoder,
+                view: &view,
+                format,
+                size: window.inner_size(),
+            },
+            &egui_state.context,
+            &mut egui_state.painter,
+            raw_input,
+            window.scale_factor() as f32,
+            recovery,
+            Some(window),
+            Some((&mut present_settings, &surface_state.capabilities)),
+        );
+        egui_state
+            .state
+            .handle_platform_output(window, output.platform_output);
+        if output.present_settings_changed {
+            // Takes effect (reconfigure) with the next frame.
+            self.present_settings = surface_state.set_present_settings(present_settings);
+            self.present_settings.save();
+        }
+
+        let command_buffer = encoder.finish();
+        gpu_state.queue.submit(std::iter::once(command_buffer));
+        drop(view);
+        surface_texture.present();
+        Ok(())
+    }
+
+    /// Everything but closing and redrawing, which concern the whole `App`.
+    fn window_event(&mut self, event: &WindowEvent) {
+        if let (Some(egui_state), Some(window)) = (&mut self.egui_state, &self.window) {
+            if egui_state.handle_input(window, event) {
+                self.core.scheduler.request_now();
+            }
+        }
+        use WindowEvent as WE;
+        match event {
+            WE::Destroyed => {
+                log::debug!("WindowEvent::Destroyed");
+            }
+            WE::DroppedFile(path) => read_dropped_path(
+                path.clone(),
+                self.core.config.directory_limits,
+                &self.core.config.file_types,
+                &self.proxy,
+            ),
+            WE::Resized(client_area) => {
+                self.core.scheduler.request_now();
+                log::debug!(
+                    "WindowEvent::Resized : width = {}, height = {}",
+                    client_area.width,
+                    client_area.height
+                );
+            }
+            _ => (),
+        }
+    }
+
+    fn user_event(&mut self, event: UserEvent) {
+        self.core.user_event(event);
+    }
+
+    /// Releases the window, the surface and the egui renderer, and removes
+    /// the canvas again if it was created for this instance.
+    fn shutdown(&mut self) {
+        self.core.shutdown();
+        self.egui_state = None;
+        self.surface = None;
+        self.window = None;
+        #[cfg(target_family = "wasm")]
+        {
+            self.file_drop = None;
+            if let Some((canvas, true)) = self.canvas.take() {
+                canvas.remove();
+            }
+        }
+    }
+}
+
+/// All app instances on the event loop, and the `GpuState` they share.
+struct App {
+    instances: Vec<AppInstance>,
+    gpu_state: Option<GpuState>,
+    /// Taken from the config of the first instance.
+    gpu_config: GpuConfig,
+    proxy: EventLoopProxy<LoopEvent>,
+    failure: Option<Error>,
+    recovery: RecoveryLog,
+    #[cfg(target_family = "wasm")]
+    pending_gpu_state: std::rc::Rc<std::cell::RefCell<Option<Result<GpuState>>>>,
+}
+impl App {
+    fn new(config: AppConfig, logic: Box<dyn AppLogic>, proxy: EventLoopProxy<LoopEvent>) -> Self {
+        let gpu_config = config.gpu.clone();
         Self {
-            window: None,
-            surface: None,
+            instances: vec![AppInstance::new(config, logic, proxy.clone())],
             gpu_state: None,
-            egui_state: None,
-            ui_state: UiState::new(),
-            start_millis: chrono::Local::now().timestamp_millis(),
+            gpu_config,
+            proxy,
+            failure: None,
+            recovery: RecoveryLog::default(),
+            #[cfg(target_family = "wasm")]
+            pending_gpu_state: Default::default(),
         }
     }
 
-    fn current_color(&self) -> Color {
-        use palette::{FromColor, Hsl, Srgb};
-        let millis = (chrono::Local::now().timestamp_millis() - self.start_millis).abs();
-        let t = (millis % 5000) as f64 / 5000.0;
-        let hue = (360.0 * t) as f32;
-        let hsl = Hsl::new(hue, 0.5, 0.5);
-        let rgb: Srgb = Srgb::from_color(hsl);
-        Color {
-            r: rgb.red as f64,
-            g: rgb.green as f64,
-            b: rgb.blue as f64,
-            a: 1.0,
-        }
+    fn instance_mut(&mut self, id: InstanceId) -> Option<&mut AppInstance> {
+        self.instances.iter_mut().find(|instance| instance.id == id)
     }
 
     #[allow(dead_code)]
-    async fn init_async(&mut self) {
-        self.gpu_state = Some(GpuState::init_async().await)
+    async fn init_async(&mut self) -> Result<()> {
+        let gpu_state = GpuState::init_async(&self.gpu_config).await?;
+        self.set_gpu_state(gpu_state);
+        Ok(())
     }
 
-    #[cfg(not(target_family = "wasm"))]
-    fn resumed_impl(&mut self, event_loop: &ActiveEventLoop) {
-        let window = self
-            .window
-            .get_or_insert_with(|| Arc::new(Self::create_window(event_loop)))
-            .clone();
+    fn resumed_impl(&mut self, event_loop: &ActiveEventLoop) -> Result<()> {
+        for instance in &mut self.instances {
+            instance.window_or_create(event_loop)?;
+        }
+        // Natively the adapter has to be able to present to the (first)
+        // window. On the web the `GpuState` exists up front; without one a
+        // replacement device is still being requested and the surfaces get
+        // created once it arrives.
+        #[cfg(not(target_family = "wasm"))]
+        if self.gpu_state.is_none() {
+            if let Some(first) = self.instances.first_mut() {
+                let window = first.window_or_create(event_loop)?;
+                let (gpu_state, surface) = GpuState::from_window(
+                    &self.gpu_config,
+                    &first.core.config.surface_format,
+                    first.present_settings,
+                    window,
+                )?;
+                first.surface = Some(surface);
+                self.set_gpu_state(gpu_state);
+            }
+        }
         if let Some(gpu_state) = &self.gpu_state {
-            if self.surface.is_none() {
-                self.surface = Some(gpu_state.create_surface(window));
+            for instance in &mut self.instances {
+                instance.create_surface(gpu_state)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_gpu_state(&mut self, gpu_state: GpuState) {
+        gpu_state.watch_device_lost(AppProxy::new(self.proxy.clone(), InstanceId::ALL));
+        for instance in &mut self.instances {
+            instance.set_gpu_state(&gpu_state);
+        }
+        self.gpu_state = Some(gpu_state);
+    }
+
+    /// Drops everything that was created from the lost device and requests a
+    /// new one.
+    fn recover_from_device_loss(&mut self, event_loop: &ActiveEventLoop) {
+        if !self.gpu_state.as_ref().is_some_and(GpuState::is_lost) {
+            return;
+        }
+        self.recovery.record_device_recovery("reported by driver");
+        for instance in &mut self.instances {
+            instance.drop_gpu_resources();
+        }
+        self.gpu_state = None;
+        self.restore_gpu_state(event_loop);
+    }
+    #[cfg(not(target_family = "wasm"))]
+    fn restore_gpu_state(&mut self, event_loop: &ActiveEventLoop) {
+        // Without a window (suspended) the next `resumed()` takes care of it.
+        if self
+            .instances
+            .iter()
+            .any(|instance| instance.window.is_some())
+        {
+            if let Err(err) = self.resumed_impl(event_loop) {
+                self.fail(event_loop, err);
             }
-        } else {
-            let (gpu_state, surface) = GpuState::from_window(window);
-            self.gpu_state = Some(gpu_state);
-            self.surface = Some(surface);
         }
     }
     #[cfg(target_family = "wasm")]
-    fn resumed_impl(&mut self, event_loop: &ActiveEventLoop) {
-        let window = self
-            .window
-            .get_or_insert_with(|| Arc::new(Self::create_window(event_loop)))
-            .clone();
-        assert!(self.gpu_state.is_some());
-        if let Some(gpu_state) = &self.gpu_state {
-            if self.surface.is_none() {
-                self.surface = Some(gpu_state.create_surface(window));
+    fn restore_gpu_state(&mut self, _event_loop: &ActiveEventLoop) {
+        let pending_gpu_state = self.pending_gpu_state.clone();
+        let config = self.gpu_config.clone();
+        let proxy = AppProxy::new(self.proxy.clone(), InstanceId::ALL);
+        wasm_bindgen_futures::spawn_local(async move {
+            *pending_gpu_state.borrow_mut() = Some(GpuState::init_async(&config).await);
+            proxy.send(UserEvent::DeviceRestored);
+        });
+    }
+    #[cfg(target_family = "wasm")]
+    fn on_device_restored(&mut self, event_loop: &ActiveEventLoop) {
+        let result = self.pending_gpu_state.borrow_mut().take();
+        let result = match result {
+            Some(Ok(gpu_state)) => {
+                self.set_gpu_state(gpu_state);
+                self.resumed_impl(event_loop)
             }
+            Some(Err(err)) => Err(err),
+            None => Ok(()),
+        };
+        if let Err(err) = result {
+            self.fail(event_loop, err);
         }
     }
 
-    fn on_file_dropped(&mut self, dropped_file: DroppedFile) {
-        self.ui_state
-            .drop_file(dropped_file.name, dropped_file.bytes);
-        if let Some(window) = &self.window {
-            window.request_redraw();
+    /// Adds an instance to the running event loop; if it cannot be brought up
+    /// only that instance fails.
+    #[cfg(target_family = "wasm")]
+    fn spawn(&mut self, event_loop: &ActiveEventLoop, mut instance: AppInstance) {
+        let result = instance.window_or_create(event_loop).and_then(|_| {
+            if let Some(gpu_state) = &self.gpu_state {
+                instance.create_surface(gpu_state)?;
+                instance.set_gpu_state(gpu_state);
+            }
+            Ok(())
+        });
+        match result {
+            Ok(()) => {
+                instance.core.scheduler.request_now();
+                self.instances.push(instance);
+            }
+            Err(err) => {
+                log::error!("failed to start an app instance: {err}");
+                wasm::show_failure_screen(&instance.core.config.canvas, &err);
+                instance.shutdown();
+            }
         }
     }
+
+    /// Shuts one instance down; once the last one is gone the GPU is released
+    /// and the event loop exits.
+    fn destroy(&mut self, event_loop: &ActiveEventLoop, id: InstanceId) {
+        let Some(index) = self.instances.iter().position(|instance| instance.id == id) else {
+            return;
+        };
+        let mut instance = self.instances.remove(index);
+        instance.shutdown();
+        if self.instances.is_empty() {
+            self.gpu_state = None;
+            event_loop.exit();
+        }
+    }
+
+    /// Stops the app after an unrecoverable error; the error is kept so it can
+    /// be handed back to the embedder once the event loop has exited. Only the
+    /// web page shows it in place of the app: natively there is no window left
+    /// to show it in, `run` returns it instead.
+    fn fail(&mut self, event_loop: &ActiveEventLoop, err: Error) {
+        log::error!("unrecoverable error: {err}");
+        for instance in &mut self.instances {
+            #[cfg(target_family = "wasm")]
+            wasm::show_failure_screen(&instance.core.config.canvas, &err);
+            instance.drop_gpu_resources();
+        }
+        self.failure = Some(err);
+        event_loop.exit();
+    }
 }
 
-impl ApplicationHandler<UserEvent> for App {
+impl ApplicationHandler<LoopEvent> for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         // This method is called eg. when the application starts, when the user
         // browses 'back' to the webpage, when the OS resumes the application...
         log::info!("ApplicationHandler::resumed() for App");
-        self.resumed_impl(event_loop);
+        if let Err(err) = self.resumed_impl(event_loop) {
+            self.fail(event_loop, err);
+        }
     }
     fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
         // This method is called eg. when the user browses away from the
         // webpage, when the OS suspends the application...
         log::info!("ApplicationHandler::suspended() for App");
-        self.window = None;
-        self.surface = None;
+        for instance in &mut self.instances {
+            instance.window = None;
+            instance.surface = None;
+        }
     }
 
-    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
-        if let (Some(egui_state), Some(window)) = (&mut self.egui_state, &self.window) {
-            egui_state.handle_input(&window, &event);
-        }
-        use WindowEvent as WE;
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        let Some(index) = self.instances.iter().position(|instance| {
+            instance
+                .window
+                .as_ref()
+                .is_some_and(|window| window.id() == id)
+        }) else {
+            return;
+        };
+        self.instances[index].window_event(&event);
         match event {
-            WE::CloseRequested => {
+            WindowEvent::CloseRequested => {
                 log::debug!("WindowEvent::CloseRequested");
-                event_loop.exit();
-            }
-            WE::Destroyed => {
-                log::debug!("WindowEvent::Destroyed");
+                self.destroy(event_loop, self.instances[index].id);
             }
-            WE::DroppedFile(path) => {
-                let bytes = log_result!(std::fs::read(&path));
-                self.on_file_dropped(DroppedFile {
-                    name: log_result!(path.into_os_string().into_string()),
-                    bytes,
-                });
-            }
-            WE::RedrawRequested => {
-                if let (Some(window), Some(surface_state), Some(gpu_state)) =
-                    (&self.window, &mut self.surface, &self.gpu_state)
-                {
-                    if let Some(surface_texture) = surface_state.current_texture(&gpu_state.device)
-                    {
-                        let view = surface_texture.texture.create_view(&TextureViewDescriptor {
-                            label: None,
-                            format: Some(SWAPCHAIN_FORMAT),
-                            dimension: Some(TextureViewDimension::D2),
-                            aspect: TextureAspect::All,
-                            base_mip_level: 0,
-                            mip_level_count: Some(1),
-                            base_array_layer: 0,
-                            array_layer_count: Some(1),
-                        });
-                        let mut encoder = gpu_state
-                            .device
-                            .create_command_encoder(&CommandEncoderDescriptor { label: None });
-                        {
-                            let _render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                                label: None,
-                                color_attachments: &[Some(RenderPassColorAttachment {
-                                    view: &view,
-                                    resolve_target: None,
-                                    ops: Operations {
-                                        load: LoadOp::Clear(self.current_color()),
-                                        store: StoreOp::Store,
-                                    },
-                                })],
-                                depth_stencil_attachment: None,
-                                timestamp_writes: None,
-                                occlusion_query_set: None,
-                            });
-                        }
-
-                        let egui_state = self
-                            .egui_state
-                            .get_or_insert_with(|| EguiState::new(&gpu_state.device, &window));
-                        let size = window.inner_size();
-                        let screen_descriptor = egui_wgpu::ScreenDescriptor {
-                            size_in_pixels: [size.width, size.height],
-                            pixels_per_point: window.scale_factor() as f32,
-                        };
-                        egui_state.draw(
-                            &gpu_state.device,
-                            &gpu_state.queue,
-                            &mut encoder,
-                            &window,
-                            &view,
-                            screen_descriptor,
-                            |ctx| self.ui_state.run_egui(ctx),
-                        );
-
-                        let command_buffer = encoder.finish();
-                        gpu_state.queue.submit(std::iter::once(command_buffer));
-                        drop(view);
-                        surface_texture.present();
+            WindowEvent::RedrawRequested => {
+                self.recover_from_device_loss(event_loop);
+                if let Some(gpu_state) = &self.gpu_state {
+                    let result = self.instances[index].redraw(gpu_state, &mut self.recovery);
+                    if let Err(err) = result {
+                        self.fail(event_loop, err);
                     }
-                    window.request_redraw();
                 }
             }
-            WE::Resized(client_area) => {
-                log::debug!(
-                    "WindowEvent::Resized : width = {}, height = {}",
-                    client_area.width,
-                    client_area.height
-                );
-            }
             _ => (),
         }
     }
 
-    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: UserEvent) {
-        use UserEvent as UE;
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: LoopEvent) {
         match event {
-            UE::OnFileDropped(dropped_file) => {
-                self.ui_state
-                    .drop_file(dropped_file.name, dropped_file.bytes);
+            LoopEvent::User(_, UserEvent::DeviceLost) => {
+                self.recover_from_device_loss(event_loop);
+                self.instances
+                    .iter_mut()
+                    .for_each(|instance| instance.core.scheduler.request_now());
+            }
+            #[cfg(target_family = "wasm")]
+            LoopEvent::User(_, UserEvent::DeviceRestored) => {
+                self.on_device_restored(event_loop);
+                self.instances
+                    .iter_mut()
+                    .for_each(|instance| instance.core.scheduler.request_now());
+            }
+            LoopEvent::User(id, event) => {
+                if let Some(instance) = self.instance_mut(id) {
+                    instance.user_event(event);
+                }
+            }
+            #[cfg(target_family = "wasm")]
+            LoopEvent::Spawn(instance) => self.spawn(event_loop, *instance),
+            LoopEvent::Destroy(id) => self.destroy(event_loop, id),
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let mut control_flow = ControlFlow::Wait;
+        for instance in &self.instances {
+            let (redraw_now, instance_control_flow) = instance.core.scheduler.poll();
+            if let (true, Some(window)) = (redraw_now, &instance.window) {
+                window.request_redraw();
             }
+            // Wake up for whichever instance is due first.
+            control_flow = match (control_flow, instance_control_flow) {
+                (ControlFlow::WaitUntil(a), ControlFlow::WaitUntil(b)) => {
+                    ControlFlow::WaitUntil(a.min(b))
+                }
+                (ControlFlow::Wait, other) | (other, _) => other,
+            };
+        }
+        event_loop.set_control_flow(control_flow);
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        log::info!("ApplicationHandler::exiting() for App");
+        for instance in &mut self.instances {
+            instance.shutdown();
         }
+        self.gpu_state = None;
+        #[cfg(target_family = "wasm")]
+        wasm::forget_event_loop();
     }
 }
 
@@ -601,21 +2228,271 @@ pub mod wasm {
     use wasm_bindgen::prelude::*;
     use wasm_bindgen_futures::*;
 
+    thread_local! {
+        /// winit allows a single event loop per page: the first `run` creates
+        /// it, later ones add their instance to it.
+        static EVENT_LOOP: std::cell::RefCell<Option<EventLoopProxy<LoopEvent>>> =
+            const { std::cell::RefCell::new(None) };
+    }
+
+    /// Called once the event loop has exited; it cannot be created again.
+    pub(crate) fn forget_event_loop() {
+        EVENT_LOOP.set(None);
+    }
+
+    /// Handle to one app instance on the page.
     #[wasm_bindgen]
     pub struct UserEventManager {
-        event_loop: EventLoopProxy<UserEvent>,
+        proxy: AppProxy,
+        subscribers: MessageSubscribers,
     }
     impl UserEventManager {
-        fn new(event_loop: EventLoopProxy<UserEvent>) -> Self {
-            Self { event_loop }
+        fn new(proxy: AppProxy, subscribers: MessageSubscribers) -> Self {
+            Self { proxy, subscribers }
+        }
+        fn on_file_dropped(&self, name: String, bytes: Vec<u8>) {
+            self.proxy.send(DroppedFile::from_bytes(name, bytes));
+        }
+        fn post_message(&self, kind: &str, payload: JsValue) -> Result<(), JsValue> {
+            let payload = if payload.is_undefined() {
+                serde_json::Value::Null
+            } else {
+                let json = String::from(js_sys::JSON::stringify(&payload)?);
+                serde_json::from_str(&json).map_err(|source| Error::InvalidMessage {
+                    kind: kind.to_string(),
+                    source,
+                })?
+            };
+            let message = Message {
+                kind: kind.to_string(),
+                payload,
+            };
+            if self.proxy.send_message(message) {
+                Ok(())
+            } else {
+                Err(JsValue::from_str("the event loop has exited"))
+            }
+        }
+        fn subscribe(&self, callback: js_sys::Function) {
+            self.subscribers.subscribe(move |message| {
+                let payload =
+                    js_sys::JSON::parse(&message.payload.to_string()).unwrap_or(JsValue::NULL);
+                let kind = JsValue::from_str(&message.kind);
+                if let Err(err) = callback.call2(&JsValue::NULL, &kind, &payload) {
+                    log::warn!("message subscriber failed: {err:?}");
+                }
+            });
+        }
+    }
+    #[wasm_bindgen]
+    impl UserEventManager {
+        /// Stops this instance and releases its surface (and the canvas, if it
+        /// was created for it). Destroying the last instance on the page also
+        /// releases the GPU and ends the event loop; winit cannot start
+        /// another one after that.
+        pub fn destroy(&self) {
+            self.proxy.close();
+        }
+    }
+
+    /// The `dragenter`/`dragover`/`dragleave`/`drop` listeners behind
+    /// [`FileDropTarget`]; they are removed again when this is dropped.
+    pub(crate) struct FileDropListeners {
+        target: web_sys::EventTarget,
+        listeners: Vec<(&'static str, Closure<dyn FnMut(web_sys::DragEvent)>)>,
+    }
+    impl FileDropListeners {
+        pub fn install(
+            file_drop: &FileDropTarget,
+            canvas: &web_sys::HtmlCanvasElement,
+            file_types: FileTypes,
+            proxy: AppProxy,
+        ) -> Result<Option<Self>> {
+            let target: web_sys::EventTarget = match file_drop {
+                FileDropTarget::Canvas => canvas.clone().into(),
+                FileDropTarget::Id(id) => web_sys::window()
+                    .and_then(|window| window.document())
+                    .and_then(|document| document.get_element_by_id(id))
+                    .ok_or(Error::WebDom("file drop element"))?
+                    .into(),
+                FileDropTarget::Element(target) => target.clone(),
+                FileDropTarget::None => return Ok(None),
+            };
+            // `dragenter`/`dragleave` also fire when moving between child
+            // elements, so count them to tell when the drag really left.
+            let depth = std::rc::Rc::new(std::cell::Cell::new(0_u32));
+            let mut listeners = Self {
+                target,
+                listeners: Vec::new(),
+            };
+            listeners.add("dragenter", {
+                let (depth, proxy) = (depth.clone(), proxy.clone());
+                move |event| {
+                    event.prevent_default();
+                    depth.set(depth.get() + 1);
+                    if depth.get() == 1 {
+                        proxy.send(UserEvent::HoveredFiles(hovered_files(&event)));
+                    }
+                }
+            })?;
+            listeners.add("dragover", |event| {
+                // Without this the browser opens the file itself.
+                event.prevent_default();
+                if let Some(data_transfer) = event.data_transfer() {
+                    data_transfer.set_drop_effect("copy");
+                }
+            })?;
+            listeners.add("dragleave", {
+                let (depth, proxy) = (depth.clone(), proxy.clone());
+                move |_event| {
+                    depth.set(depth.get().saturating_sub(1));
+                    if depth.get() == 0 {
+                        proxy.send(UserEvent::HoveredFiles(Vec::new()));
+                    }
+                }
+            })?;
+            listeners.add("drop", move |event| {
+                event.prevent_default();
+                depth.set(0);
+                proxy.send(UserEvent::HoveredFiles(Vec::new()));
+                let Some(files) = event.data_transfer().and_then(|data| data.files()) else {
+                    return;
+                };
+                for file in (0..files.length()).filter_map(|index| files.get(index)) {
+                    if file_types.accepts(&file.name(), &file.type_()) {
+                        file_read::read_blob(file, proxy.clone());
+                    } else {
+                        log::info!("ignoring '{}': not a supported file type", file.name());
+                    }
+                }
+            })?;
+            Ok(Some(listeners))
+        }
+
+        fn add(
+            &mut self,
+            kind: &'static str,
+            listener: impl FnMut(web_sys::DragEvent) + 'static,
+        ) -> Result<()> {
+            let listener = Closure::<dyn FnMut(web_sys::DragEvent)>::new(listener);
+            self.target
+                .add_event_listener_with_callback(kind, listener.as_ref().unchecked_ref())
+                .map_err(|_| Error::WebDom("cannot add a drag and drop listener"))?;
+            self.listeners.push((kind, listener));
+            Ok(())
+        }
+    }
+    impl Drop for FileDropListeners {
+        fn drop(&mut self) {
+            for (kind, listener) in &self.listeners {
+                let _ = self
+                    .target
+                    .remove_event_listener_with_callback(kind, listener.as_ref().unchecked_ref());
+            }
+        }
+    }
+
+    /// What is being dragged; browsers reveal only the MIME types of the files
+    /// before they are dropped, not their names.
+    fn hovered_files(event: &web_sys::DragEvent) -> Vec<egui::HoveredFile> {
+        let Some(items) = event.data_transfer().map(|data| data.items()) else {
+            return Vec::new();
+        };
+        (0..items.length())
+            .filter_map(|index| items.get(index))
+            .filter(|item| item.kind() == "file")
+            .map(|item| egui::HoveredFile {
+                path: None,
+                mime: item.type_(),
+            })
+            .collect()
+    }
+
+    impl From<Error> for JsValue {
+        fn from(value: Error) -> Self {
+            JsValue::from_str(&value.to_string())
         }
-        fn on_file_dropped(&self, name: &str, bytes: &[u8]) {
-            let _ = self
-                .event_loop
-                .send_event(DroppedFile::new(name, bytes).into());
+    }
+
+    /// The `<canvas>` to render into; the flag is set when it had to be
+    /// created.
+    pub(crate) fn canvas_element(
+        target: &CanvasTarget,
+    ) -> Result<(web_sys::HtmlCanvasElement, bool)> {
+        let window = log_result!(web_sys::window()).ok_or(Error::WebDom("window"))?;
+        let document = log_result!(window.document()).ok_or(Error::WebDom("document"))?;
+        let container = match target {
+            CanvasTarget::Id(id) => {
+                let canvas = log_result!(document.get_element_by_id(id))
+                    .ok_or(Error::WebDom("canvas element"))?;
+                let canvas = canvas
+                    .dyn_into::<web_sys::HtmlCanvasElement>()
+                    .map_err(|_| Error::WebDom("canvas element is not a <canvas>"))?;
+                return Ok((canvas, false));
+            }
+            CanvasTarget::Element(canvas) => return Ok((canvas.clone(), false)),
+            CanvasTarget::ContainerId(id) => log_result!(document.get_element_by_id(id))
+                .ok_or(Error::WebDom("container element"))?,
+            CanvasTarget::Container(container) => container.clone(),
+        };
+        let canvas: web_sys::HtmlCanvasElement = document
+            .create_element("canvas")
+            .ok()
+            .and_then(|element| element.dyn_into().ok())
+            .ok_or(Error::WebDom("cannot create a <canvas> element"))?;
+        let _ = canvas.set_attribute("style", "width: 100%; height: 100%");
+        container
+            .append_child(&canvas)
+            .map_err(|_| Error::WebDom("cannot add a <canvas> to the container"))?;
+        Ok((canvas, true))
+    }
+
+    /// Replaces the canvas with a plain-text error message, so the user gets
+    /// to see *something* when the app cannot start.
+    pub(crate) fn show_failure_screen(target: &CanvasTarget, err: &Error) {
+        let Some(document) = web_sys::window().and_then(|window| window.document()) else {
+            return;
+        };
+        let (canvas, container): (Option<web_sys::Element>, _) = match target {
+            CanvasTarget::Id(id) => (document.get_element_by_id(id), None),
+            CanvasTarget::Element(canvas) => (Some(canvas.clone().into()), None),
+            CanvasTarget::ContainerId(id) => (None, document.get_element_by_id(id)),
+            CanvasTarget::Container(container) => (None, Some(container.clone())),
+        };
+        if let Some(canvas) = &canvas {
+            let _ = canvas.set_attribute("style", "display: none");
+        }
+        let parent = container
+            .or_else(|| canvas.and_then(|canvas| canvas.parent_element()))
+            .or_else(|| document.body().map(Into::into));
+        if let (Ok(element), Some(parent)) = (document.create_element("pre"), parent) {
+            element.set_text_content(Some(&format!("The application failed to start:\n{err}")));
+            let _ = parent.append_child(&element);
         }
     }
 
+    /// Offers `bytes` to the user as a file download named `file_name`.
+    pub(crate) fn download(file_name: &str, bytes: &[u8]) -> Result<()> {
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or(Error::WebDom("document"))?;
+        let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes));
+        let blob = web_sys::Blob::new_with_u8_array_sequence(&parts)
+            .map_err(|_| Error::WebDom("cannot create a Blob"))?;
+        let url = web_sys::Url::create_object_url_with_blob(&blob)
+            .map_err(|_| Error::WebDom("cannot create an object URL"))?;
+        let anchor: web_sys::HtmlAnchorElement = document
+            .create_element("a")
+            .ok()
+            .and_then(|element| element.dyn_into().ok())
+            .ok_or(Error::WebDom("cannot create an <a> element"))?;
+        anchor.set_href(&url);
+        anchor.set_download(file_name);
+        anchor.click();
+        let _ = web_sys::Url::revoke_object_url(&url);
+        Ok(())
+    }
+
     #[wasm_bindgen(start)]
     pub async fn wasm_main() -> Result<(), JsValue> {
         std::panic::set_hook(Box::new(console_error_panic_hook::hook));
@@ -625,42 +2502,166 @@ pub mod wasm {
         Ok(())
     }
 
-    #[wasm_bindgen]
-    pub async fn run_app() -> UserEventManager {
-        log::info!("entering run_app() at {}...", system_now());
-        let event_loop = log_result!(EventLoop::<UserEvent>::with_user_event().build());
+    /// Starts `logic` on the canvas from `config`; returns as soon as the
+    /// event loop has been handed to the browser. Every call adds another
+    /// instance to the same event loop, sharing one `GpuState` (the `gpu`
+    /// settings of the first call apply).
+    pub async fn run<L: AppLogic>(config: AppConfig, logic: L) -> Result<UserEventManager> {
+        let subscribers = config.subscribers.clone();
+        if let Some(proxy) = EVENT_LOOP.with_borrow(Clone::clone) {
+            let instance = AppInstance::new(config, Box::new(logic), proxy.clone());
+            let user_event_mgr = UserEventManager::new(instance.proxy.clone(), subscribers);
+            return match proxy.send_event(LoopEvent::Spawn(Box::new(instance))) {
+                Ok(()) => Ok(user_event_mgr),
+                Err(_) => Err(Error::EventLoop(
+                    winit::error::EventLoopError::RecreationAttempt,
+                )),
+            };
+        }
+        let event_loop = log_result!(EventLoop::<LoopEvent>::with_user_event().build())?;
         event_loop.set_control_flow(ControlFlow::Wait);
-        let user_event_mgr = UserEventManager::new(event_loop.create_proxy());
+        // Set right away: instances started while the GPU is being requested
+        // queue up on this event loop.
+        EVENT_LOOP.set(Some(event_loop.create_proxy()));
+        let mut app = App::new(config, Box::new(logic), event_loop.create_proxy());
+        let user_event_mgr = UserEventManager::new(app.instances[0].proxy.clone(), subscribers);
+        if let Err(err) = app.init_async().await {
+            forget_event_loop();
+            show_failure_screen(&app.instances[0].core.config.canvas, &err);
+            return Err(err);
+        }
         use winit::platform::web::EventLoopExtWebSys;
-        let mut app = App::new();
-        app.init_async().await;
         event_loop.spawn_app(app);
+        Ok(user_event_mgr)
+    }
+
+    /// `options[key]`, unless it is missing, `undefined` or `null`.
+    fn option(options: &JsValue, key: &str) -> Option<JsValue> {
+        if !options.is_object() {
+            return None;
+        }
+        js_sys::Reflect::get(options, &JsValue::from_str(key))
+            .ok()
+            .filter(|value| !value.is_undefined() && !value.is_null())
+    }
+
+    /// Where `run_app` renders, from its `options`: `{ canvas }` with a
+    /// `<canvas>` element or its id, or `{ container }` with an element (or
+    /// its id) to create a canvas in. Without either the canvas with the id
+    /// "rust_canvas" is used.
+    fn canvas_target(options: &JsValue) -> Result<CanvasTarget> {
+        if let Some(canvas) = option(options, "canvas") {
+            return match canvas.as_string() {
+                Some(id) => Ok(CanvasTarget::Id(id)),
+                None => canvas
+                    .dyn_into()
+                    .map(CanvasTarget::Element)
+                    .map_err(|_| Error::WebDom("'canvas' is neither an id nor a <canvas>")),
+            };
+        }
+        if let Some(container) = option(options, "container") {
+            return match container.as_string() {
+                Some(id) => Ok(CanvasTarget::ContainerId(id)),
+                None => container
+                    .dyn_into()
+                    .map(CanvasTarget::Container)
+                    .map_err(|_| Error::WebDom("'container' is neither an id nor an element")),
+            };
+        }
+        Ok(CanvasTarget::default())
+    }
+
+    /// Where files can be dropped, from `options.dropTarget`: an element or
+    /// its id, or `false` when the host page calls `on_file_drop` itself.
+    /// Defaults to the canvas.
+    fn file_drop_target(options: &JsValue) -> Result<FileDropTarget> {
+        let Some(target) = option(options, "dropTarget") else {
+            return Ok(FileDropTarget::Canvas);
+        };
+        if target.as_bool() == Some(false) {
+            return Ok(FileDropTarget::None);
+        }
+        match target.as_string() {
+            Some(id) => Ok(FileDropTarget::Id(id)),
+            None => target
+                .dyn_into()
+                .map(FileDropTarget::Element)
+                .map_err(|_| Error::WebDom("'dropTarget' is neither an id nor an element")),
+        }
+    }
+
+    #[wasm_bindgen]
+    pub async fn run_app(options: JsValue) -> Result<UserEventManager, JsValue> {
+        log::info!("entering run_app() at {}...", system_now());
+        let config = AppConfig {
+            canvas: canvas_target(&options)?,
+            file_drop: file_drop_target(&options)?,
+            ..AppConfig::default()
+        };
+        let user_event_mgr = run(config, demo::DemoApp::new()).await?;
         log::info!("...exiting run_app() at {}", system_now());
-        user_event_mgr
+        Ok(user_event_mgr)
     }
 
+    /// For host pages that handle drops themselves (`dropTarget: false`);
+    /// `bytes` are copied only once, out of the JavaScript array.
     #[wasm_bindgen]
-    pub fn on_file_drop(mgr: &UserEventManager, name: &str, bytes: &[u8]) {
+    pub fn on_file_drop(mgr: &UserEventManager, name: String, bytes: Vec<u8>) {
         mgr.on_file_dropped(name, bytes);
     }
+
+    /// Sends `{ kind, payload }` to the app; `payload` is anything
+    /// `JSON.stringify` accepts.
+    #[wasm_bindgen]
+    pub fn post_message(
+        mgr: &UserEventManager,
+        kind: &str,
+        payload: JsValue,
+    ) -> Result<(), JsValue> {
+        mgr.post_message(kind, payload)
+    }
+
+    /// Calls `callback(kind, payload)` for every message the app posts.
+    #[wasm_bindgen]
+    pub fn subscribe(mgr: &UserEventManager, callback: js_sys::Function) {
+        mgr.subscribe(callback);
+    }
 }
 
 #[cfg(not(target_family = "wasm"))]
 pub mod not_wasm {
     use super::*;
-    pub fn desktop_main() -> Result<(), Box<dyn std::error::Error>> {
+
+    /// Runs `logic` in a native window until the window is closed. An
+    /// unrecoverable error (eg. no GPU adapter) closes the window and is
+    /// returned; unlike on the web no failure screen is shown, telling the
+    /// user is up to the caller.
+    pub fn run<L: AppLogic>(config: AppConfig, logic: L) -> Result<()> {
+        let event_loop = log_result!(EventLoop::<LoopEvent>::with_user_event().build())?;
+        event_loop.set_control_flow(ControlFlow::Wait);
+        let mut app = App::new(config, Box::new(logic), event_loop.create_proxy());
+        log_result!(event_loop.run_app(&mut app))?;
+        match app.failure.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    pub fn desktop_main(config: AppConfig) -> Result<()> {
         env_logger::init();
         log::info!("entering desktop_main() at {}...", system_now());
-        let event_loop = log_result!(EventLoop::<UserEvent>::with_user_event().build());
-        event_loop.set_control_flow(ControlFlow::Wait);
-        let mut app = App::new();
-        log_result!(event_loop.run_app(&mut app));
+        run(config, demo::DemoApp::new())?;
         log::info!("...exiting desktop_main() at {}", system_now());
         Ok(())
     }
 }
 
+#[cfg(not(target_family = "wasm"))]
+pub use not_wasm::run;
+#[cfg(target_family = "wasm")]
+pub use wasm::run;
+
 #[cfg(not(target_family = "wasm"))]
 pub fn main() -> Result<(), Box<dyn std::error::Error>> {
-    not_wasm::desktop_main()
+    Ok(not_wasm::desktop_main(AppConfig::default())?)
 }
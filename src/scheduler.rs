@@ -0,0 +1,144 @@
+//
+// Render scheduling: only render when something changed
+//
+
+use std::time::Duration;
+use web_time::Instant;
+use winit::event_loop::ControlFlow;
+
+/// When frames are rendered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Only on input, on `UserEvent`s, when egui asks for a repaint and while
+    /// the app logic is animating.
+    Reactive,
+    /// Every frame, as fast as the present mode (and the FPS cap) allow.
+    Continuous,
+}
+
+/// Decides when the next frame is due; the event loop sleeps until then.
+pub(crate) struct RenderScheduler {
+    pub mode: RenderMode,
+    pub fps_cap: Option<u32>,
+    next_frame: Option<Instant>,
+    last_frame: Option<Instant>,
+}
+impl RenderScheduler {
+    pub fn new(mode: RenderMode, fps_cap: Option<u32>) -> Self {
+        Self {
+            mode,
+            fps_cap,
+            // The very first frame is always rendered.
+            next_frame: Some(Instant::now()),
+            last_frame: None,
+        }
+    }
+
+    /// Something changed; render as soon as possible.
+    pub fn request_now(&mut self) {
+        self.request_after(Duration::ZERO);
+    }
+
+    /// Renders after `delay`, unless a frame is already due before that.
+    pub fn request_after(&mut self, delay: Duration) {
+        let Some(at) = Instant::now().checked_add(delay) else {
+            // eg. egui's `Duration::MAX` for "no repaint needed"
+            return;
+        };
+        self.next_frame = Some(self.next_frame.map_or(at, |next| next.min(at)));
+    }
+
+    /// Called after a frame was rendered with what egui asked for and whether
+    /// the app logic is still animating.
+    pub fn frame_rendered(&mut self, repaint_delay: Duration, is_animating: bool) {
+        self.last_frame = Some(Instant::now());
+        self.next_frame = None;
+        if self.mode == RenderMode::Continuous || is_animating {
+            self.request_now();
+        } else {
+            self.request_after(repaint_delay);
+        }
+    }
+
+    /// Called instead of [`frame_rendered`](Self::frame_rendered) when a due
+    /// frame could not be rendered: try again after `retry`, or not before
+    /// something else requests a frame (`None`, eg. while minimized).
+    pub fn frame_skipped(&mut self, retry: Option<Duration>) {
+        self.next_frame = retry.and_then(|retry| Instant::now().checked_add(retry));
+    }
+
+    /// The next frame, respecting the FPS cap.
+    fn next_frame(&self) -> Option<Instant> {
+        let next_frame = self.next_frame?;
+        let min_interval = self
+            .fps_cap
+            .filter(|fps| *fps > 0)
+            .map(|fps| Duration::from_secs_f64(1.0 / fps as f64));
+        match (self.last_frame, min_interval) {
+            (Some(last_frame), Some(min_interval)) => {
+                Some(next_frame.max(last_frame + min_interval))
+            }
+            _ => Some(next_frame),
+        }
+    }
+
+    /// Returns whether a redraw has to be requested right now, and how long
+    /// the event loop may sleep otherwise.
+    pub fn poll(&self) -> (bool, ControlFlow) {
+        match self.next_frame() {
+            None => (false, ControlFlow::Wait),
+            Some(at) if at <= Instant::now() => (true, ControlFlow::Wait),
+            Some(at) => (false, ControlFlow::WaitUntil(at)),
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("rendering:");
+            ui.selectable_value(&mut self.mode, RenderMode::Reactive, "reactive");
+            ui.selectable_value(&mut self.mode, RenderMode::Continuous, "continuous");
+        });
+        let mut is_capped = self.fps_cap.is_some();
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut is_capped, "FPS cap");
+            let mut fps = self.fps_cap.unwrap_or(60);
+            ui.add_enabled(is_capped, egui::Slider::new(&mut fps, 1..=240));
+            self.fps_cap = is_capped.then_some(fps);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skipped_frames_are_retried_later() {
+        let mut scheduler = RenderScheduler::new(RenderMode::Reactive, None);
+        assert_eq!(scheduler.poll(), (true, ControlFlow::Wait));
+
+        scheduler.frame_skipped(Some(Duration::from_millis(100)));
+        assert!(matches!(
+            scheduler.poll(),
+            (false, ControlFlow::WaitUntil(_))
+        ));
+        scheduler.request_now();
+        assert_eq!(scheduler.poll(), (true, ControlFlow::Wait));
+
+        scheduler.frame_skipped(None);
+        assert_eq!(scheduler.poll(), (false, ControlFlow::Wait));
+    }
+
+    #[test]
+    fn continuous_mode_respects_the_fps_cap() {
+        let mut scheduler = RenderScheduler::new(RenderMode::Continuous, Some(10));
+        scheduler.frame_rendered(Duration::MAX, false);
+        assert!(matches!(
+            scheduler.poll(),
+            (false, ControlFlow::WaitUntil(_))
+        ));
+
+        scheduler.fps_cap = None;
+        assert_eq!(scheduler.poll(), (true, ControlFlow::Wait));
+    }
+}
@@ -0,0 +1,418 @@
+//
+// Recording and replaying input sessions, for reproducible bug reports
+//
+// winit's `WindowEvent`s can be neither serialized nor constructed by hand
+// (they carry platform specific data), so a session stores what they were
+// translated into: the egui input of every frame, together with the window
+// geometry, the time on the app's clock and the `UserEvent`s in between.
+// Sessions are stored as MessagePack, which keeps dropped files compact.
+//
+
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+/// Files up to this size are recorded verbatim, larger ones only by size and
+/// hash (and replayed as zeros).
+pub const MAX_RECORDED_FILE_SIZE: usize = 16 << 20;
+
+/// Whether a session is recorded or replayed from the start.
+pub enum SessionMode {
+    Off,
+    /// Natively the session is written to `path` when the app exits; on the
+    /// web `path` is only the name of the downloaded file.
+    Record {
+        path: PathBuf,
+    },
+    Replay(Session),
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Session {
+    pub version: u32,
+    pub frames: Vec<RecordedFrame>,
+}
+impl Session {
+    pub const VERSION: u32 = 1;
+
+    pub fn new() -> Self {
+        Self {
+            version: Self::VERSION,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        // With field names, so egui's optional fields stay readable.
+        rmp_serde::to_vec_named(self).map_err(|err| Error::Session(err.into()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let session: Self =
+            rmp_serde::from_slice(bytes).map_err(|err| Error::Session(err.into()))?;
+        if session.version > Self::VERSION {
+            log::warn!(
+                "session was recorded with format version {}, expected {}",
+                session.version,
+                Self::VERSION
+            );
+        }
+        Ok(session)
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    pub fn load(path: &std::path::Path) -> Result<Self> {
+        let bytes = std::fs::read(path).map_err(|source| Error::FileIo {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::from_bytes(&bytes)
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    pub fn save(&self, path: &std::path::Path) -> Result<()> {
+        std::fs::write(path, self.to_bytes()?).map_err(|source| Error::FileIo {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+}
+
+/// Everything that went into one frame.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// Time on the app's clock, in seconds.
+    pub time: f64,
+    /// In points.
+    pub screen_rect: Option<egui::Rect>,
+    pub pixels_per_point: Option<f32>,
+    pub focused: bool,
+    pub modifiers: egui::Modifiers,
+    pub events: Vec<egui::Event>,
+    /// Delivered to the app logic before the frame.
+    pub user_events: Vec<RecordedUserEvent>,
+}
+
+impl RecordedFrame {
+    /// The frame egui's input describes; its time must already be the app's
+    /// clock, not egui's own.
+    pub fn from_input(raw_input: &egui::RawInput) -> Self {
+        Self {
+            time: raw_input.time.unwrap_or_default(),
+            screen_rect: raw_input.screen_rect,
+            pixels_per_point: raw_input
+                .viewports
+                .get(&egui::ViewportId::ROOT)
+                .and_then(|viewport| viewport.native_pixels_per_point),
+            focused: raw_input.focused,
+            modifiers: raw_input.modifiers,
+            events: raw_input.events.clone(),
+            user_events: Vec::new(),
+        }
+    }
+
+    /// Replaces the live input with the recorded one.
+    pub fn apply_to(self, raw_input: &mut egui::RawInput) {
+        raw_input.time = Some(self.time);
+        raw_input.screen_rect = self.screen_rect;
+        raw_input.focused = self.focused;
+        raw_input.modifiers = self.modifiers;
+        raw_input.events = self.events;
+        let viewport = raw_input
+            .viewports
+            .entry(egui::ViewportId::ROOT)
+            .or_default();
+        viewport.native_pixels_per_point = self.pixels_per_point;
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RecordedUserEvent {
+    FileDropped(RecordedFile),
+    Message(Message),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedFile {
+    pub name: String,
+    pub len: usize,
+    /// FNV-1a of the contents.
+    pub hash: u64,
+    /// Only for files up to [`MAX_RECORDED_FILE_SIZE`].
+    #[serde(with = "serde_bytes")]
+    pub bytes: Option<Vec<u8>>,
+}
+impl RecordedFile {
+    fn new(dropped_file: &DroppedFile) -> Self {
+        let hash = dropped_file
+            .bytes
+            .iter()
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+                (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+            });
+        Self {
+            name: dropped_file.name.clone(),
+            len: dropped_file.bytes.len(),
+            hash,
+            bytes: (dropped_file.bytes.len() <= MAX_RECORDED_FILE_SIZE)
+                .then(|| dropped_file.bytes.to_vec()),
+        }
+    }
+
+    pub fn to_dropped_file(&self) -> DroppedFile {
+        let bytes = self.bytes.clone().unwrap_or_else(|| {
+            log::warn!(
+                "replaying '{}' as zeros, only its hash was recorded",
+                self.name
+            );
+            vec![0; self.len]
+        });
+        DroppedFile::from_bytes(self.name.clone(), bytes).with_source(FileSource::Replay)
+    }
+}
+
+/// The recorder or player behind [`SessionMode`], owned by `App`.
+pub(crate) enum SessionState {
+    Idle,
+    Recording {
+        session: Session,
+        path: PathBuf,
+        /// Arrived since the last frame.
+        user_events: Vec<RecordedUserEvent>,
+    },
+    Replaying {
+        frames: VecDeque<RecordedFrame>,
+        total: usize,
+        /// Dropped files that were too large to record, see
+        /// [`MAX_RECORDED_FILE_SIZE`].
+        zeroed_files: usize,
+        /// The window has been resized to the recorded size.
+        resized: bool,
+    },
+}
+impl SessionState {
+    pub fn new(mode: SessionMode) -> Self {
+        match mode {
+            SessionMode::Off => Self::Idle,
+            SessionMode::Record { path } => Self::record(path),
+            SessionMode::Replay(session) => Self::Replaying {
+                total: session.frames.len(),
+                zeroed_files: session
+                    .frames
+                    .iter()
+                    .flat_map(|frame| &frame.user_events)
+                    .filter(|event| {
+                        matches!(event, RecordedUserEvent::FileDropped(file) if file.bytes.is_none())
+                    })
+                    .count(),
+                frames: session.frames.into(),
+                resized: false,
+            },
+        }
+    }
+
+    fn record(path: PathBuf) -> Self {
+        log::info!("recording session to '{}'", path.display());
+        Self::Recording {
+            session: Session::new(),
+            path,
+            user_events: Vec::new(),
+        }
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        matches!(self, Self::Replaying { .. })
+    }
+
+    /// Records a dropped file; returns `false` if it has to be ignored
+    /// because a session is being replayed.
+    pub fn on_file_dropped(&mut self, dropped_file: &DroppedFile) -> bool {
+        self.on_user_event(RecordedUserEvent::FileDropped(RecordedFile::new(
+            dropped_file,
+        )))
+    }
+
+    /// Like [`SessionState::on_file_dropped`], for messages.
+    pub fn on_message(&mut self, message: &Message) -> bool {
+        self.on_user_event(RecordedUserEvent::Message(message.clone()))
+    }
+
+    fn on_user_event(&mut self, event: RecordedUserEvent) -> bool {
+        match self {
+            Self::Idle => true,
+            Self::Recording { user_events, .. } => {
+                user_events.push(event);
+                true
+            }
+            Self::Replaying { .. } => {
+                log::info!("ignoring {event:?} while replaying a session");
+                false
+            }
+        }
+    }
+
+    /// The next recorded frame when replaying; once all of them have been
+    /// played, live input takes over again. The `window` is resized to the
+    /// recorded size first.
+    pub fn next_replay_frame(&mut self, window: Option<&Window>) -> Option<RecordedFrame> {
+        let Self::Replaying {
+            frames, resized, ..
+        } = self
+        else {
+            return None;
+        };
+        let Some(frame) = frames.pop_front() else {
+            log::info!("session replay finished");
+            *self = Self::Idle;
+            return None;
+        };
+        if let (false, Some(rect), Some(pixels_per_point), Some(window)) =
+            (*resized, frame.screen_rect, frame.pixels_per_point, window)
+        {
+            let size = rect.size() * pixels_per_point;
+            let _ = window.request_inner_size(PhysicalSize::new(size.x as u32, size.y as u32));
+            *resized = true;
+        }
+        Some(frame)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        matches!(self, Self::Recording { .. })
+    }
+
+    /// Adds a frame (see [`RecordedFrame::from_input`]) to the recording,
+    /// with the `UserEvent`s that arrived since the last one.
+    pub fn record_frame(&mut self, mut frame: RecordedFrame) {
+        if let Self::Recording {
+            session,
+            user_events,
+            ..
+        } = self
+        {
+            frame.user_events = std::mem::take(user_events);
+            session.frames.push(frame);
+        }
+    }
+
+    /// Stores the recording so far: natively to its file, on the web as a
+    /// download.
+    fn save(&self) {
+        let Self::Recording { session, path, .. } = self else {
+            return;
+        };
+        #[cfg(not(target_family = "wasm"))]
+        let result = session.save(path);
+        #[cfg(target_family = "wasm")]
+        let result = session.to_bytes().and_then(|bytes| {
+            let file_name = path.file_name().and_then(|name| name.to_str());
+            wasm::download(file_name.unwrap_or("session.bin"), &bytes)
+        });
+        match result {
+            Ok(()) => log::info!(
+                "saved {} recorded frames to '{}'",
+                session.frames.len(),
+                path.display()
+            ),
+            Err(err) => log::warn!("failed to save the session recording: {err}"),
+        }
+    }
+
+    /// Saves a native recording when the app exits.
+    pub fn finish(&mut self) {
+        #[cfg(not(target_family = "wasm"))]
+        self.save();
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let save_label = if cfg!(target_family = "wasm") {
+            "download"
+        } else {
+            "save"
+        };
+        ui.horizontal(|ui| match self {
+            Self::Idle => {
+                ui.label("session:");
+                if ui.button("record").clicked() {
+                    *self = Self::record(PathBuf::from("session.bin"));
+                }
+            }
+            Self::Recording { session, .. } => {
+                ui.label(format!("recording: {} frames", session.frames.len()));
+                if ui.button(save_label).clicked() {
+                    self.save();
+                }
+                let stop = ui
+                    .button("stop")
+                    .on_hover_text(format!("Stops recording and {save_label}s the session"));
+                if stop.clicked() {
+                    self.save();
+                    *self = Self::Idle;
+                }
+            }
+            Self::Replaying {
+                frames,
+                total,
+                zeroed_files,
+                ..
+            } => {
+                ui.label(format!(
+                    "replaying: frame {} of {total}",
+                    *total - frames.len()
+                ));
+                if *zeroed_files > 0 {
+                    ui.label(format!("({zeroed_files} dropped file(s) replay as zeros)"))
+                        .on_hover_text(format!(
+                            "Only the size and hash of files over {} MiB are recorded",
+                            MAX_RECORDED_FILE_SIZE >> 20
+                        ));
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE_LEN: usize = 1 << 16;
+
+    fn session() -> Session {
+        let raw_input = egui::RawInput {
+            time: Some(1.5),
+            events: vec![egui::Event::Text("hi".to_string())],
+            ..Default::default()
+        };
+        let mut frame = RecordedFrame::from_input(&raw_input);
+        let file = DroppedFile::new("a.bin", &[0xff; FILE_LEN]);
+        frame.user_events = vec![
+            RecordedUserEvent::FileDropped(RecordedFile::new(&file)),
+            RecordedUserEvent::Message(Message::new("set_theme", "dark").unwrap()),
+        ];
+        Session {
+            frames: vec![frame],
+            ..Session::new()
+        }
+    }
+
+    #[test]
+    fn sessions_are_stored_compactly() {
+        let bytes = session().to_bytes().unwrap();
+        // The file takes up a byte per byte.
+        assert!(bytes.len() < FILE_LEN + 1024, "{} bytes", bytes.len());
+
+        let session = Session::from_bytes(&bytes).unwrap();
+        assert_eq!(session.version, Session::VERSION);
+        let frame = &session.frames[0];
+        assert_eq!(frame.time, 1.5);
+        assert_eq!(frame.events, [egui::Event::Text("hi".to_string())]);
+        let [RecordedUserEvent::FileDropped(file), RecordedUserEvent::Message(message)] =
+            &frame.user_events[..]
+        else {
+            panic!("{:?}", frame.user_events);
+        };
+        assert_eq!(file.bytes.as_deref(), Some(&[0xff; FILE_LEN][..]));
+        assert_eq!(message.payload::<String>().unwrap(), "dark");
+    }
+}
@@ -0,0 +1,83 @@
+//
+// Tiny persistent key/value store for user settings
+//
+// Natively the values live in `<config dir>/wasm_winit_wgpu/settings.txt` as
+// `key=value` lines, on the web they live in `localStorage`.
+//
+
+#[cfg(not(target_family = "wasm"))]
+fn settings_path() -> Option<std::path::PathBuf> {
+    let config_dir = std::env::var_os("APPDATA")
+        .or_else(|| std::env::var_os("XDG_CONFIG_HOME"))
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".config"))
+        })?;
+    Some(config_dir.join("wasm_winit_wgpu").join("settings.txt"))
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn read_all() -> Vec<(String, String)> {
+    let Some(text) = settings_path().and_then(|path| std::fs::read_to_string(path).ok()) else {
+        return Vec::new();
+    };
+    text.lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+#[cfg(not(target_family = "wasm"))]
+pub fn load(key: &str) -> Option<String> {
+    read_all()
+        .into_iter()
+        .find_map(|(k, value)| (k == key).then_some(value))
+}
+
+#[cfg(not(target_family = "wasm"))]
+pub fn save(key: &str, value: &str) {
+    let Some(path) = settings_path() else {
+        log::warn!("no config directory, not saving setting '{key}'");
+        return;
+    };
+    let mut entries = read_all();
+    match entries.iter_mut().find(|(k, _)| k == key) {
+        Some((_, v)) => *v = value.to_string(),
+        None => entries.push((key.to_string(), value.to_string())),
+    }
+    let text: String = entries
+        .iter()
+        .map(|(key, value)| format!("{key}={value}\n"))
+        .collect();
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&path, text));
+    if let Err(err) = result {
+        log::warn!(
+            "failed to save setting '{key}' to '{}': {err}",
+            path.display()
+        );
+    }
+}
+
+#[cfg(target_family = "wasm")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_family = "wasm")]
+pub fn load(key: &str) -> Option<String> {
+    local_storage()?
+        .get_item(&format!("wasm_winit_wgpu.{key}"))
+        .ok()?
+}
+
+#[cfg(target_family = "wasm")]
+pub fn save(key: &str, value: &str) {
+    let stored =
+        local_storage().map(|storage| storage.set_item(&format!("wasm_winit_wgpu.{key}"), value));
+    if !matches!(stored, Some(Ok(()))) {
+        log::warn!("failed to save setting '{key}' to localStorage");
+    }
+}
@@ -0,0 +1,590 @@
+//
+// WGSL shaders: checking them with naga before wgpu gets to see them, and a
+// registry that (re)loads them and rebuilds the pipelines that use them
+//
+// Natively a `ShaderSources::Directory` is watched, and a changed file is
+// read on the watcher's thread; on the web the same names are fetched from
+// the page's origin. Either way the code lands in `ShaderRegistry::incoming`
+// and is compiled by the next `ShaderRegistry::update`, on the render thread.
+//
+
+use crate::*;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// What is wrong with a WGSL shader, and where (both 1-based) if known.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShaderError {
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub message: String,
+}
+impl ShaderError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            line: None,
+            column: None,
+            message: message.into(),
+        }
+    }
+
+    fn at(location: Option<naga::SourceLocation>, message: String) -> Self {
+        Self {
+            line: location.map(|location| location.line_number),
+            column: location.map(|location| location.line_position),
+            message,
+        }
+    }
+}
+impl std::fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, "line {line}:{column}: {}", self.message),
+            (Some(line), None) => write!(f, "line {line}: {}", self.message),
+            _ => f.write_str(&self.message),
+        }
+    }
+}
+impl std::error::Error for ShaderError {}
+
+/// Parses and validates `source` the way wgpu does when creating a shader
+/// module on a device with `capabilities` (see
+/// [`GpuState::shader_capabilities`]), but hands the first error back instead
+/// of passing it to the device's error handler (which panics by default).
+pub fn check_wgsl(
+    source: &str,
+    capabilities: naga::valid::Capabilities,
+) -> Result<naga::Module, ShaderError> {
+    use naga::valid::{Capabilities, ShaderStages, SubgroupOperationSet};
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|err| ShaderError::at(err.location(source), err.message().to_string()))?;
+    let mut validator =
+        naga::valid::Validator::new(naga::valid::ValidationFlags::all(), capabilities);
+    if capabilities.contains(Capabilities::SUBGROUP) {
+        // In which stages is up to wgpu, which validates again.
+        use SubgroupOperationSet as S;
+        validator.subgroup_stages(ShaderStages::all());
+        validator.subgroup_operations(
+            S::BASIC | S::VOTE | S::ARITHMETIC | S::BALLOT | S::SHUFFLE | S::SHUFFLE_RELATIVE,
+        );
+    }
+    validator.validate(&module).map_err(|err| {
+        // The spans go from the outermost (the function) to the innermost
+        // (the offending expression).
+        let location = err.spans().last().map(|(span, _)| span.location(source));
+        ShaderError::at(location, error_chain(err.as_inner()))
+    })?;
+    Ok(module)
+}
+
+/// `err` followed by all of its sources; naga's validation errors say little
+/// on their own ("Function [1] 'fs_main' is invalid").
+fn error_chain(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+    message
+}
+
+/// Runs `f` (which creates shader modules or pipelines on `device`) in an
+/// error scope, so a validation error is handed back instead of reaching the
+/// device's error handler (which panics by default).
+///
+/// WebGPU only reports errors some time later, by then whatever `f` created
+/// is in use; on the web they are logged as coming from `what`.
+pub fn validated<T>(
+    device: &Device,
+    #[cfg_attr(not(target_family = "wasm"), allow(unused_variables))] what: &str,
+    f: impl FnOnce() -> T,
+) -> Result<T, ShaderError> {
+    device.push_error_scope(ErrorFilter::Validation);
+    let value = f();
+    let error = device.pop_error_scope();
+    #[cfg(not(target_family = "wasm"))]
+    if let Some(err) = pollster::block_on(error) {
+        return Err(ShaderError::new(err.to_string()));
+    }
+    #[cfg(target_family = "wasm")]
+    {
+        let what = what.to_string();
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Some(err) = error.await {
+                log::error!("{what}: {err}");
+            }
+        });
+    }
+    Ok(value)
+}
+
+/// Where a [`ShaderRegistry`] gets the WGSL code of its shaders from.
+#[derive(Clone, Debug)]
+pub enum ShaderSources {
+    /// `<directory>/<name>`, where names may go into subdirectories with
+    /// `/` (eg. `post/blur.wgsl`). Natively read from disk and reloaded
+    /// whenever the file changes; on the web fetched from the page's origin,
+    /// relative to the page.
+    Directory(PathBuf),
+    /// Shaders compiled into the app (eg. with `include_str!`), by name.
+    Embedded(&'static [(&'static str, &'static str)]),
+}
+
+/// A render pipeline of a [`ShaderRegistry`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RenderPipelineId(usize);
+
+/// A compute pipeline of a [`ShaderRegistry`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ComputePipelineId(usize);
+
+/// Shader code (or why there is none) that was loaded but not yet compiled.
+type Incoming = Arc<Mutex<Vec<(String, Result<String, ShaderError>)>>>;
+
+#[derive(Default)]
+struct Shader {
+    /// The last code that compiled, and its module.
+    module: Option<(String, ShaderModule)>,
+    error: Option<ShaderError>,
+}
+
+/// Builds a pipeline from a freshly compiled module.
+type Build<P> = Box<dyn Fn(&Device, &ShaderModule) -> P>;
+
+struct Pipeline<P> {
+    shader: String,
+    build: Build<P>,
+    pipeline: Option<P>,
+}
+
+/// Rebuilds the pipelines that use `shader`; one that fails keeps its
+/// previous pipeline. Returns the first error.
+fn rebuild<P>(
+    pipelines: &mut [Pipeline<P>],
+    device: &Device,
+    shader: &str,
+    module: &ShaderModule,
+) -> Option<ShaderError> {
+    let mut first_err = None;
+    for pipeline in pipelines
+        .iter_mut()
+        .filter(|pipeline| pipeline.shader == shader)
+    {
+        match validated(device, shader, || (pipeline.build)(device, module)) {
+            Ok(built) => pipeline.pipeline = Some(built),
+            Err(err) => {
+                first_err.get_or_insert(err);
+            }
+        }
+    }
+    first_err
+}
+
+/// WGSL shaders by name, and the render and compute pipelines built from
+/// them.
+///
+/// Shaders are loaded when a pipeline first asks for them, and compiled (and
+/// their pipelines rebuilt) by [`update`](Self::update), which is meant to be
+/// called once per frame. Code that does not compile, or a pipeline that
+/// fails to build, leaves the previous pipeline in place and the error in
+/// [`error`](Self::error).
+pub struct ShaderRegistry {
+    sources: ShaderSources,
+    shaders: BTreeMap<String, Shader>,
+    render_pipelines: Vec<Pipeline<RenderPipeline>>,
+    compute_pipelines: Vec<Pipeline<ComputePipeline>>,
+    incoming: Incoming,
+    /// Woken up when a fetch is done.
+    #[cfg(target_family = "wasm")]
+    proxy: Option<AppProxy>,
+    #[cfg(not(target_family = "wasm"))]
+    _watcher: Option<notify::RecommendedWatcher>,
+}
+impl ShaderRegistry {
+    /// An empty registry that loads shaders from `sources`. With a `proxy`
+    /// the event loop is woken up (with [`UserEvent::ShadersChanged`]) when a
+    /// shader file changed or was fetched.
+    pub fn new(sources: ShaderSources, proxy: Option<AppProxy>) -> Self {
+        let incoming = Incoming::default();
+        #[cfg(not(target_family = "wasm"))]
+        let watcher = match &sources {
+            ShaderSources::Directory(directory) => {
+                match watch(directory, incoming.clone(), proxy) {
+                    Ok(watcher) => Some(watcher),
+                    Err(err) => {
+                        log::warn!(
+                            "not watching '{}' for shader changes: {err}",
+                            directory.display()
+                        );
+                        None
+                    }
+                }
+            }
+            ShaderSources::Embedded(_) => None,
+        };
+        Self {
+            sources,
+            shaders: BTreeMap::new(),
+            render_pipelines: Vec::new(),
+            compute_pipelines: Vec::new(),
+            incoming,
+            #[cfg(target_family = "wasm")]
+            proxy,
+            #[cfg(not(target_family = "wasm"))]
+            _watcher: watcher,
+        }
+    }
+
+    /// Adds a render pipeline built by `build` from the shader `shader`, now
+    /// if that is already compiled, otherwise once it is (see
+    /// [`update`](Self::update)), and again whenever it changes.
+    pub fn add_render_pipeline(
+        &mut self,
+        gpu: &GpuState,
+        shader: &str,
+        build: impl Fn(&Device, &ShaderModule) -> RenderPipeline + 'static,
+    ) -> RenderPipelineId {
+        let pipeline = self.pipeline(gpu, shader, Box::new(build));
+        self.render_pipelines.push(pipeline);
+        RenderPipelineId(self.render_pipelines.len() - 1)
+    }
+
+    /// Like [`add_render_pipeline`](Self::add_render_pipeline), for a compute
+    /// pipeline.
+    pub fn add_compute_pipeline(
+        &mut self,
+        gpu: &GpuState,
+        shader: &str,
+        build: impl Fn(&Device, &ShaderModule) -> ComputePipeline + 'static,
+    ) -> ComputePipelineId {
+        let pipeline = self.pipeline(gpu, shader, Box::new(build));
+        self.compute_pipelines.push(pipeline);
+        ComputePipelineId(self.compute_pipelines.len() - 1)
+    }
+
+    fn pipeline<P>(&mut self, gpu: &GpuState, shader: &str, build: Build<P>) -> Pipeline<P> {
+        self.load(shader);
+        let mut pipeline = Pipeline {
+            shader: shader.to_string(),
+            build,
+            pipeline: None,
+        };
+        let entry = self.shaders.get_mut(shader).expect("just loaded");
+        if let Some((_, module)) = &entry.module {
+            if let Some(err) = rebuild(
+                std::slice::from_mut(&mut pipeline),
+                gpu.device(),
+                shader,
+                module,
+            ) {
+                entry.error = Some(err);
+            }
+        }
+        pipeline
+    }
+
+    /// The pipeline, once its shader compiled and it was built.
+    pub fn render_pipeline(&self, id: RenderPipelineId) -> Option<&RenderPipeline> {
+        self.render_pipelines[id.0].pipeline.as_ref()
+    }
+
+    pub fn compute_pipeline(&self, id: ComputePipelineId) -> Option<&ComputePipeline> {
+        self.compute_pipelines[id.0].pipeline.as_ref()
+    }
+
+    /// The module last compiled from the shader `name`.
+    pub fn module(&self, name: &str) -> Option<&ShaderModule> {
+        let (_, module) = self.shaders.get(name)?.module.as_ref()?;
+        Some(module)
+    }
+
+    /// Why the shader `name` could not be loaded or compiled, or why one of
+    /// its pipelines could not be built; `None` once that is fixed.
+    pub fn error(&self, name: &str) -> Option<&ShaderError> {
+        self.shaders.get(name)?.error.as_ref()
+    }
+
+    /// Replaces the code of the shader `name`, compiled by the next
+    /// [`update`](Self::update). A shader set before any pipeline asked for
+    /// it is not loaded from the registry's sources at all.
+    pub fn set_source(&mut self, name: &str, source: impl Into<String>) {
+        self.shaders.entry(name.to_string()).or_default();
+        push(&self.incoming, name, Ok(source.into()));
+    }
+
+    /// Compiles the code that was loaded since the last call, and rebuilds
+    /// the pipelines of the shaders that compiled. Returns their names.
+    pub fn update(&mut self, gpu: &GpuState) -> Vec<String> {
+        let incoming = match self.incoming.lock() {
+            Ok(mut incoming) => std::mem::take(&mut *incoming),
+            Err(_) => return Vec::new(),
+        };
+        // Only the latest version of each shader.
+        let incoming: BTreeMap<_, _> = incoming.into_iter().collect();
+        let device = gpu.device();
+        let mut compiled = Vec::new();
+        for (name, source) in incoming {
+            // Changes to files no pipeline asked for.
+            let Some(shader) = self.shaders.get_mut(&name) else {
+                continue;
+            };
+            match source.and_then(|source| Ok((compile(gpu, &name, &source)?, source))) {
+                Ok((module, source)) => {
+                    let render_err = rebuild(&mut self.render_pipelines, device, &name, &module);
+                    let compute_err = rebuild(&mut self.compute_pipelines, device, &name, &module);
+                    shader.error = render_err.or(compute_err);
+                    shader.module = Some((source, module));
+                    compiled.push(name);
+                }
+                Err(err) => {
+                    log::warn!("shader '{name}': {err}");
+                    shader.error = Some(err);
+                }
+            }
+        }
+        compiled
+    }
+
+    /// Recompiles every shader from its last good code, and rebuilds all
+    /// pipelines, on the replacement for a lost device.
+    pub fn on_device_recreated(&mut self, gpu: &GpuState) {
+        for pipeline in &mut self.render_pipelines {
+            pipeline.pipeline = None;
+        }
+        for pipeline in &mut self.compute_pipelines {
+            pipeline.pipeline = None;
+        }
+        for (name, shader) in &mut self.shaders {
+            if let Some((source, _)) = shader.module.take() {
+                push(&self.incoming, name, Ok(source));
+            }
+        }
+        self.update(gpu);
+    }
+
+    /// Starts loading the shader `name` unless that already happened.
+    fn load(&mut self, name: &str) {
+        if self.shaders.contains_key(name) {
+            return;
+        }
+        self.shaders.insert(name.to_string(), Shader::default());
+        match &self.sources {
+            ShaderSources::Embedded(shaders) => {
+                let source = shaders
+                    .iter()
+                    .find(|(embedded, _)| *embedded == name)
+                    .map(|(_, source)| source.to_string())
+                    .ok_or_else(|| ShaderError::new(format!("no embedded shader '{name}'")));
+                push(&self.incoming, name, source);
+            }
+            #[cfg(not(target_family = "wasm"))]
+            ShaderSources::Directory(directory) => {
+                push(&self.incoming, name, read(&directory.join(name)));
+            }
+            #[cfg(target_family = "wasm")]
+            ShaderSources::Directory(directory) => {
+                let url = directory.join(name).to_string_lossy().into_owned();
+                let (name, incoming, proxy) =
+                    (name.to_string(), self.incoming.clone(), self.proxy.clone());
+                wasm_bindgen_futures::spawn_local(async move {
+                    let source = fetch(&url)
+                        .await
+                        .map_err(|err| ShaderError::new(err.to_string()));
+                    push(&incoming, &name, source);
+                    if let Some(proxy) = proxy {
+                        proxy.send(UserEvent::ShadersChanged);
+                    }
+                });
+            }
+        }
+    }
+}
+
+impl GpuState {
+    /// What naga may accept in shaders for this device, derived from its
+    /// features like wgpu does; for [`check_wgsl`].
+    pub fn shader_capabilities(&self) -> naga::valid::Capabilities {
+        use naga::valid::Capabilities as Caps;
+        let features = self.device().features();
+        let downlevel = self.adapter.get_downlevel_capabilities().flags;
+        let mut caps = Caps::empty();
+        for (cap, enabled) in [
+            (
+                Caps::PUSH_CONSTANT,
+                features.contains(Features::PUSH_CONSTANTS),
+            ),
+            (Caps::FLOAT64, features.contains(Features::SHADER_F64)),
+            (
+                Caps::PRIMITIVE_INDEX,
+                features.contains(Features::SHADER_PRIMITIVE_INDEX),
+            ),
+            (
+                Caps::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING,
+                features.contains(
+                    Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING,
+                ),
+            ),
+            (
+                Caps::UNIFORM_BUFFER_AND_STORAGE_TEXTURE_ARRAY_NON_UNIFORM_INDEXING,
+                features.contains(
+                    Features::UNIFORM_BUFFER_AND_STORAGE_TEXTURE_ARRAY_NON_UNIFORM_INDEXING,
+                ),
+            ),
+            // wgpu has no feature of its own for this one.
+            (
+                Caps::SAMPLER_NON_UNIFORM_INDEXING,
+                features.contains(
+                    Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING,
+                ),
+            ),
+            (
+                Caps::STORAGE_TEXTURE_16BIT_NORM_FORMATS,
+                features.contains(Features::TEXTURE_FORMAT_16BIT_NORM),
+            ),
+            (Caps::MULTIVIEW, features.contains(Features::MULTIVIEW)),
+            (
+                Caps::EARLY_DEPTH_TEST,
+                features.contains(Features::SHADER_EARLY_DEPTH_TEST),
+            ),
+            (
+                Caps::SHADER_INT64,
+                features.contains(Features::SHADER_INT64),
+            ),
+            (
+                Caps::MULTISAMPLED_SHADING,
+                downlevel.contains(DownlevelFlags::MULTISAMPLED_SHADING),
+            ),
+            (
+                Caps::DUAL_SOURCE_BLENDING,
+                features.contains(Features::DUAL_SOURCE_BLENDING),
+            ),
+            (
+                Caps::CUBE_ARRAY_TEXTURES,
+                downlevel.contains(DownlevelFlags::CUBE_ARRAY_TEXTURES),
+            ),
+            (
+                Caps::SUBGROUP,
+                features.intersects(Features::SUBGROUP | Features::SUBGROUP_VERTEX),
+            ),
+            (
+                Caps::SUBGROUP_BARRIER,
+                features.contains(Features::SUBGROUP_BARRIER),
+            ),
+        ] {
+            caps.set(cap, enabled);
+        }
+        caps
+    }
+}
+
+fn push(incoming: &Incoming, name: &str, source: Result<String, ShaderError>) {
+    if let Ok(mut incoming) = incoming.lock() {
+        incoming.push((name.to_string(), source));
+    }
+}
+
+/// Checks `source` with naga first, so the error has a location.
+fn compile(gpu: &GpuState, name: &str, source: &str) -> Result<ShaderModule, ShaderError> {
+    check_wgsl(source, gpu.shader_capabilities())?;
+    let device = gpu.device();
+    validated(device, name, || {
+        device.create_shader_module(ShaderModuleDescriptor {
+            label: Some(name),
+            source: ShaderSource::Wgsl(source.into()),
+        })
+    })
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn read(path: &std::path::Path) -> Result<String, ShaderError> {
+    std::fs::read_to_string(path).map_err(|source| {
+        let err = Error::FileIo {
+            path: path.to_path_buf(),
+            source,
+        };
+        ShaderError::new(err.to_string())
+    })
+}
+
+/// Reads the `.wgsl` files in `directory` (and below) that were written to,
+/// on the watcher's thread.
+#[cfg(not(target_family = "wasm"))]
+fn watch(
+    directory: &std::path::Path,
+    incoming: Incoming,
+    proxy: Option<AppProxy>,
+) -> notify::Result<notify::RecommendedWatcher> {
+    use notify::Watcher;
+    // Some platforms report canonical paths.
+    let roots = [
+        directory.to_path_buf(),
+        directory
+            .canonicalize()
+            .unwrap_or_else(|_| directory.to_path_buf()),
+    ];
+    // Shaders are named by their path below `directory`, with `/`s.
+    let name = move |path: &std::path::Path| {
+        let relative = roots.iter().find_map(|root| path.strip_prefix(root).ok())?;
+        let components: Option<Vec<_>> = relative
+            .components()
+            .map(|component| component.as_os_str().to_str())
+            .collect();
+        Some(components?.join("/"))
+    };
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let event = match event {
+            Ok(event) if event.kind.is_create() || event.kind.is_modify() => event,
+            Ok(_) => return,
+            Err(err) => {
+                log::warn!("shader watcher: {err}");
+                return;
+            }
+        };
+        let mut changed = false;
+        for path in &event.paths {
+            let is_wgsl = path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("wgsl"));
+            let Some(name) = name(path) else {
+                continue;
+            };
+            // Removed again before it could be read, eg. an editor's backup.
+            if is_wgsl && path.is_file() {
+                push(&incoming, &name, read(path));
+                changed = true;
+            }
+        }
+        if let (true, Some(proxy)) = (changed, &proxy) {
+            proxy.send(UserEvent::ShadersChanged);
+        }
+    })?;
+    watcher.watch(directory, notify::RecursiveMode::Recursive)?;
+    Ok(watcher)
+}
+
+/// `url` as text, from the page's origin.
+#[cfg(target_family = "wasm")]
+async fn fetch(url: &str) -> Result<String> {
+    use wasm_bindgen::JsCast;
+    let window = web_sys::window().ok_or(Error::WebDom("window"))?;
+    let failed = |status| Error::Fetch {
+        url: url.to_string(),
+        status,
+    };
+    let response = wasm_bindgen_futures::JsFuture::from(window.fetch_with_str(url))
+        .await
+        .map_err(|_| failed(None))?
+        .dyn_into::<web_sys::Response>()
+        .map_err(|_| failed(None))?;
+    if !response.ok() {
+        return Err(failed(Some(response.status())));
+    }
+    let text = response.text().map_err(|_| failed(None))?;
+    let text = wasm_bindgen_futures::JsFuture::from(text)
+        .await
+        .map_err(|_| failed(None))?;
+    text.as_string().ok_or_else(|| failed(None))
+}
@@ -0,0 +1,202 @@
+//
+// Keyframe animations on a pausable timeline
+//
+
+use std::time::Duration;
+
+/// How a value moves from the previous keyframe to the next one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    /// Holds the previous value and jumps at the keyframe.
+    Step,
+}
+impl Easing {
+    /// Maps the linear progress `t` in `0..=1` to the eased progress.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+            Easing::Step => {
+                if t < 1.0 {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+        }
+    }
+}
+
+/// What happens after the last keyframe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Repeat {
+    /// Stays at the last value.
+    Once,
+    /// Starts over from the first keyframe.
+    Loop,
+    /// Plays backwards to the start, then forwards again.
+    PingPong,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe {
+    pub at: Duration,
+    pub value: f32,
+    /// Easing from the previous keyframe to this one.
+    pub easing: Easing,
+}
+
+/// A value over time, interpolated between keyframes.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use wasm_winit_wgpu::{Animation, Easing, Repeat};
+/// let hue = Animation::new(0.0)
+///     .key(Duration::from_millis(5000), 360.0, Easing::Linear)
+///     .repeat(Repeat::Loop);
+/// assert_eq!(hue.sample(Duration::from_millis(1250)), 90.0);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animation {
+    /// Sorted by time; the first one is always at zero.
+    keyframes: Vec<Keyframe>,
+    repeat: Repeat,
+}
+impl Animation {
+    /// Starts at `value`; without further keyframes the value is constant.
+    pub fn new(value: f32) -> Self {
+        Self {
+            keyframes: vec![Keyframe {
+                at: Duration::ZERO,
+                value,
+                easing: Easing::Linear,
+            }],
+            repeat: Repeat::Once,
+        }
+    }
+
+    /// Adds a keyframe, replacing one at the same time.
+    pub fn key(mut self, at: Duration, value: f32, easing: Easing) -> Self {
+        let keyframe = Keyframe { at, value, easing };
+        match self.keyframes.binary_search_by_key(&at, |k| k.at) {
+            Ok(index) => self.keyframes[index] = keyframe,
+            Err(index) => self.keyframes.insert(index, keyframe),
+        }
+        self
+    }
+
+    pub fn repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    /// Time of the last keyframe.
+    pub fn duration(&self) -> Duration {
+        self.keyframes.last().map_or(Duration::ZERO, |k| k.at)
+    }
+
+    pub fn sample(&self, time: Duration) -> f32 {
+        let duration = self.duration();
+        if duration.is_zero() {
+            return self.keyframes[0].value;
+        }
+        let time = match self.repeat {
+            Repeat::Once => time.min(duration),
+            Repeat::Loop => Duration::from_nanos((time.as_nanos() % duration.as_nanos()) as u64),
+            Repeat::PingPong => {
+                let cycle = time.as_nanos() % (2 * duration.as_nanos());
+                let cycle = Duration::from_nanos(cycle as u64);
+                if cycle > duration {
+                    2 * duration - cycle
+                } else {
+                    cycle
+                }
+            }
+        };
+        // The first keyframe after `time`, interpolating from the one before.
+        let next = self.keyframes.partition_point(|k| k.at <= time);
+        let Some(to) = self.keyframes.get(next) else {
+            return self.keyframes[next - 1].value;
+        };
+        let from = &self.keyframes[next - 1];
+        let t = (time - from.at).as_secs_f32() / (to.at - from.at).as_secs_f32();
+        from.value + (to.value - from.value) * to.easing.apply(t)
+    }
+}
+
+/// Named animations sharing one playhead, which follows the app's clock
+/// while playing and can be paused and scrubbed independently of it.
+#[derive(Clone, Debug, Default)]
+pub struct Timeline {
+    animations: Vec<(String, Animation)>,
+    playhead: Duration,
+    last_now: Option<Duration>,
+    paused: bool,
+}
+impl Timeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an animation, replacing one with the same name.
+    pub fn with(mut self, name: &str, animation: Animation) -> Self {
+        self.insert(name, animation);
+        self
+    }
+
+    pub fn insert(&mut self, name: &str, animation: Animation) {
+        match self.animations.iter_mut().find(|(n, _)| n == name) {
+            Some((_, existing)) => *existing = animation,
+            None => self.animations.push((name.to_string(), animation)),
+        }
+    }
+
+    pub fn animation(&self, name: &str) -> Option<&Animation> {
+        self.animations
+            .iter()
+            .find_map(|(n, animation)| (n == name).then_some(animation))
+    }
+
+    /// The value of animation `name` at the playhead.
+    pub fn value(&self, name: &str) -> Option<f32> {
+        Some(self.animation(name)?.sample(self.playhead))
+    }
+
+    /// Moves the playhead by however much `now` (the app's clock) advanced
+    /// since the last call, unless paused.
+    pub fn update(&mut self, now: Duration) {
+        if let Some(last_now) = self.last_now {
+            if !self.paused {
+                self.playhead += now.saturating_sub(last_now);
+            }
+        }
+        self.last_now = Some(now);
+    }
+
+    pub fn playhead(&self) -> Duration {
+        self.playhead
+    }
+
+    pub fn seek(&mut self, time: Duration) {
+        self.playhead = time;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+}
@@ -0,0 +1,230 @@
+//
+// Checksums of dropped files, and the demo's integrity panel, which checks
+// them against checksum lists
+//
+
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use wasm_winit_wgpu::demo::IntegrityPanel;
+use wasm_winit_wgpu::headless::UiDriver;
+use wasm_winit_wgpu::*;
+
+const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+const ABC_MD5: &str = "900150983cd24fb0d6963f7d28e17f72";
+
+fn hex(algorithm: HashAlgorithm, bytes: &[u8]) -> String {
+    let mut hasher = Hasher::new(&[algorithm]);
+    hasher.update(bytes);
+    hasher.finish().remove(0).hex
+}
+
+#[test]
+fn known_checksums_of_abc() {
+    assert_eq!(hex(HashAlgorithm::Crc32, b"abc"), "352441c2");
+    assert_eq!(hex(HashAlgorithm::Adler32, b"abc"), "024d0127");
+    assert_eq!(hex(HashAlgorithm::Md5, b"abc"), ABC_MD5);
+    assert_eq!(
+        hex(HashAlgorithm::Sha1, b"abc"),
+        "a9993e364706816aba3e25717850c26c9cd0d89d"
+    );
+    assert_eq!(hex(HashAlgorithm::Sha256, b"abc"), ABC_SHA256);
+    assert_eq!(
+        hex(HashAlgorithm::Blake3, b"abc"),
+        "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+    );
+}
+
+#[test]
+fn hashing_in_pieces_gives_the_same_checksums() {
+    let bytes: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+    let mut whole = Hasher::new(&HashAlgorithm::ALL);
+    whole.update(&bytes);
+    let mut pieces = Hasher::new(&HashAlgorithm::ALL);
+    for piece in bytes.chunks(777) {
+        pieces.update(piece);
+    }
+    assert_eq!(whole.finish(), pieces.finish());
+
+    let task = ChecksumTask::spawn(Arc::from(bytes.as_slice()), &HashAlgorithm::ALL);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let checksums = loop {
+        if let Some(checksums) = task.checksums() {
+            break checksums;
+        }
+        assert!(Instant::now() < deadline, "timed out");
+        std::thread::sleep(Duration::from_millis(1));
+    };
+    assert_eq!(task.progress(), 1.0);
+    let mut hasher = Hasher::new(&HashAlgorithm::ALL);
+    hasher.update(&bytes);
+    assert_eq!(checksums, hasher.finish());
+}
+
+#[test]
+fn checksum_lists_are_parsed() {
+    assert!(is_checksum_list("SHA256SUMS"));
+    assert!(is_checksum_list("app.zip.sha256"));
+    assert!(is_checksum_list("B3SUMS"));
+    assert!(!is_checksum_list("app.zip"));
+
+    let text = format!(
+        "# release 1.0\n{ABC_SHA256}  app.zip\n{ABC_SHA256} *dist/app.exe\n\nnot a checksum\n"
+    );
+    let expected = parse_checksum_list("SHA256SUMS", &text);
+    assert_eq!(expected.len(), 2);
+    assert_eq!(expected[0].name.as_deref(), Some("app.zip"));
+    assert_eq!(expected[0].algorithm, Some(HashAlgorithm::Sha256));
+    assert_eq!(expected[1].name.as_deref(), Some("app.exe"));
+
+    let expected = parse_checksum_list("CHECKSUMS", &format!("MD5 (app.zip) = {ABC_MD5}"));
+    assert_eq!(expected[0].algorithm, Some(HashAlgorithm::Md5));
+    assert_eq!(expected[0].name.as_deref(), Some("app.zip"));
+
+    // GNU style, with a name that looks a bit like BSD style.
+    let text = format!("{ABC_SHA256}  app (1).zip\n{ABC_SHA256}  MD5 (copy).zip");
+    let expected = parse_checksum_list("SHA256SUMS", &text);
+    assert_eq!(expected.len(), 2);
+    assert_eq!(expected[0].name.as_deref(), Some("app (1).zip"));
+    assert_eq!(expected[0].algorithm, Some(HashAlgorithm::Sha256));
+    assert_eq!(expected[1].name.as_deref(), Some("MD5 (copy).zip"));
+
+    let expected = parse_checksum_list("app.zip.sha256", &ABC_SHA256.to_uppercase());
+    assert_eq!(expected[0].name.as_deref(), Some("app.zip"));
+    assert_eq!(expected[0].hex, ABC_SHA256);
+
+    // Without a hint, anything of the same length may match.
+    let pasted = ExpectedChecksum::parse(ABC_SHA256).unwrap();
+    assert_eq!(pasted.algorithm, None);
+    let blake3 = Checksum {
+        algorithm: HashAlgorithm::Blake3,
+        hex: ABC_SHA256.to_string(),
+    };
+    assert!(pasted.matches(&blake3));
+    assert!(!expected[0].matches(&blake3));
+    assert!(ExpectedChecksum::parse("not hex").is_none());
+}
+
+/// Just the integrity panel window.
+#[derive(Default)]
+struct Integrity(IntegrityPanel);
+impl AppLogic for Integrity {
+    fn ui(&mut self, ctx: &egui::Context) {
+        self.0.ui(ctx);
+    }
+    fn on_file_event(&mut self, event: FileEvent) {
+        self.0.on_file_event(&event);
+    }
+}
+
+/// Also returns the id of the checksums of "app.zip".
+fn driver() -> (UiDriver<Integrity>, u64) {
+    let mut integrity = IntegrityPanel::default();
+    let app_zip = integrity.add(&DroppedFile::new("app.zip", b"abc")).unwrap();
+    integrity.add(&DroppedFile::new("other.zip", b"abd"));
+    let sums = format!("{ABC_SHA256}  app.zip\n{ABC_SHA256}  other.zip\n");
+    assert_eq!(
+        integrity.add(&DroppedFile::new("SHA256SUMS", sums.as_bytes())),
+        None
+    );
+    let mut driver = common::driver(Integrity(integrity));
+    wait_for_checksums(&mut driver);
+    (driver, app_zip)
+}
+
+fn wait_for_checksums(driver: &mut UiDriver<Integrity>) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while driver.logic().0.is_busy() {
+        assert!(Instant::now() < deadline, "timed out");
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    driver.run_frames(2);
+}
+
+#[test]
+fn dropped_files_are_checked_against_checksum_lists() {
+    let (mut driver, _) = driver();
+    driver.get(ABC_SHA256);
+    driver.get("matches SHA256SUMS (SHA-256)");
+    driver.get("does not match SHA256SUMS");
+    driver.get("2 expected checksums from checksum lists");
+
+    driver.logic_mut().0.set_pasted(ABC_MD5);
+    driver.run_frames(2);
+    driver.get("matches the MD5 of app.zip");
+
+    driver
+        .logic_mut()
+        .0
+        .set_pasted(&format!("{ABC_SHA256}  other.zip"));
+    driver.run_frames(2);
+    driver.get("matches none of the files");
+}
+
+#[test]
+fn algorithms_can_be_turned_off() {
+    let (mut driver, app_zip) = driver();
+    // Not the "SHA-256" label next to the checksum.
+    let checkbox = driver
+        .widgets()
+        .iter()
+        .find(|widget| widget.label == "SHA-256" && widget.role == egui::accesskit::Role::CheckBox)
+        .expect("no SHA-256 checkbox")
+        .rect;
+    driver.click_at(checkbox.center());
+    wait_for_checksums(&mut driver);
+    assert!(!driver
+        .logic()
+        .0
+        .algorithms()
+        .contains(&HashAlgorithm::Sha256));
+    let checksums = driver.logic().0.checksums(app_zip).unwrap();
+    assert_eq!(checksums.len(), 5);
+    assert!(driver.find(ABC_SHA256).is_none());
+    driver.get("does not match SHA256SUMS");
+}
+
+#[test]
+fn files_of_the_same_name_keep_their_own_checksums() {
+    let mut integrity = IntegrityPanel::default();
+    let first = integrity.add(&DroppedFile::new("app.zip", b"abc")).unwrap();
+    let second = integrity.add(&DroppedFile::new("app.zip", b"abd")).unwrap();
+    let mut driver = common::driver(Integrity(integrity));
+    wait_for_checksums(&mut driver);
+    let sha256 = |id| {
+        let checksums = driver.logic().0.checksums(id).unwrap();
+        let sha256 = checksums
+            .into_iter()
+            .find(|checksum| checksum.algorithm == HashAlgorithm::Sha256);
+        sha256.unwrap().hex
+    };
+    assert_eq!(sha256(first), ABC_SHA256);
+    assert_ne!(sha256(second), ABC_SHA256);
+}
+
+#[test]
+fn files_too_large_to_keep_are_hashed_as_they_are_read() {
+    let dir = std::env::temp_dir().join(format!("wasm_winit_wgpu-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("large.bin");
+    let contents: Vec<u8> = (0..4096).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&path, &contents).unwrap();
+    let expected = hex(HashAlgorithm::Crc32, &contents);
+
+    let mut integrity = IntegrityPanel::default();
+    for algorithm in HashAlgorithm::ALL {
+        integrity.set_algorithm(algorithm, algorithm == HashAlgorithm::Crc32);
+    }
+    let mut driver = common::driver(Integrity(integrity));
+    driver.app_mut().set_max_assembled_file_size(1024);
+    read_file(path.clone(), driver.app().proxy());
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while driver.find(&expected).is_none() {
+        assert!(Instant::now() < deadline, "timed out");
+        driver.run_frames(1);
+    }
+    assert!(!driver.logic().0.is_busy());
+    driver.get("too large to keep for the file manager and viewers");
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,35 @@
+//
+// Shared setup for the headless integration tests
+//
+
+use wasm_winit_wgpu::headless::{HeadlessApp, UiDriver};
+use wasm_winit_wgpu::*;
+
+/// A software adapter (lavapipe, WARP or SwiftShader), so results do not
+/// depend on the GPU of the machine running the tests. Panics without one: a
+/// test that could not run must not pass.
+pub fn software_gpu() -> GpuState {
+    let config = GpuConfig::new().force_fallback_adapter(true);
+    let gpu = match GpuState::headless(&config) {
+        Ok(gpu) => gpu,
+        Err(err) => panic!("no adapter available ({err}); install a software one, eg. lavapipe"),
+    };
+    assert_eq!(
+        gpu.tier(),
+        AdapterTier::Software,
+        "no software adapter available (got '{}'); install one, eg. lavapipe",
+        gpu.adapter_info().name
+    );
+    gpu
+}
+
+/// Drives `logic` in an 800×600 window on a [`software_gpu`].
+#[allow(dead_code)] // Not every test crate drives a UI.
+pub fn driver<L: AppLogic>(logic: L) -> UiDriver<L> {
+    UiDriver::new(HeadlessApp::new(
+        software_gpu(),
+        logic,
+        (800, 600).into(),
+        1.0,
+    ))
+}
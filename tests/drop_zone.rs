@@ -0,0 +1,122 @@
+//
+// The drop-zone overlay: while files are dragged over the app it lists them
+// with whether the registered file types can open them, and how files are
+// matched against those types
+//
+
+mod common;
+
+use wasm_winit_wgpu::headless::UiDriver;
+use wasm_winit_wgpu::*;
+
+struct NoUi;
+impl AppLogic for NoUi {
+    fn ui(&mut self, _ctx: &egui::Context) {}
+}
+
+fn driver() -> UiDriver<NoUi> {
+    let mut driver = common::driver(NoUi);
+    driver.app_mut().set_file_types(
+        FileTypes::new().with(FileType::new("image").extension("png").mime_type("image/*")),
+    );
+    driver
+}
+
+fn native_file(path: &str) -> egui::HoveredFile {
+    egui::HoveredFile {
+        path: Some(path.into()),
+        mime: String::new(),
+    }
+}
+
+fn web_file(mime: &str) -> egui::HoveredFile {
+    egui::HoveredFile {
+        path: None,
+        mime: mime.to_string(),
+    }
+}
+
+#[test]
+fn hovering_files_shows_whether_they_can_be_opened() {
+    let mut driver = driver();
+    assert!(driver.find("Drop to open").is_none());
+
+    driver.hover_files(vec![
+        native_file("/photos/Photo.PNG"),
+        web_file("image/jpeg"),
+    ]);
+    driver.run_frames(2);
+    driver.get("Drop to open");
+    driver.get("Photo.PNG: image");
+    driver.get("file (image/jpeg): image");
+
+    driver.hover_files(vec![native_file("notes.txt"), web_file("text/plain")]);
+    driver.run_frames(2);
+    driver.get("These files cannot be opened");
+    driver.get("notes.txt: not supported");
+    driver.get("file (text/plain): not supported");
+
+    driver.hover_files(Vec::new());
+    driver.run_frames(2);
+    assert!(driver.find("Drop to open").is_none());
+    assert!(driver.find("These files cannot be opened").is_none());
+}
+
+#[test]
+fn files_of_unknown_type_are_checked_once_dropped() {
+    let mut driver = driver();
+    driver.hover_files(vec![web_file("")]);
+    driver.run_frames(2);
+    driver.get("Drop to open");
+    driver.get("file: checked once dropped");
+}
+
+fn text_and_images() -> FileTypes {
+    FileTypes::new()
+        .with(
+            FileType::new("text")
+                .extension("txt")
+                .mime_type("text/plain"),
+        )
+        .with(FileType::new("image").extension("PNG").mime_type("image/*"))
+}
+
+#[test]
+fn files_are_matched_by_extension_or_mime_type() {
+    let file_types = text_and_images();
+    let name = |file| {
+        file_types
+            .find(&file)
+            .map(|file_type| file_type.name.as_str())
+    };
+    assert_eq!(name(native_file("/photos/Photo.png")), Some("image"));
+    assert_eq!(name(native_file("notes.TXT")), Some("text"));
+    assert_eq!(name(web_file("image/jpeg")), Some("image"));
+    assert_eq!(name(web_file("text/plain")), Some("text"));
+    assert_eq!(name(web_file("text/html")), None);
+    // "image/*" is a family, not a prefix.
+    assert_eq!(name(web_file("imagery/x")), None);
+    assert_eq!(name(native_file("png")), None);
+
+    assert_eq!(
+        file_types.check(&web_file("image/webp")),
+        FileAcceptance::Accepted
+    );
+    assert_eq!(
+        file_types.check(&native_file("a.bin")),
+        FileAcceptance::Rejected
+    );
+    assert_eq!(file_types.check(&web_file("")), FileAcceptance::Unknown);
+    assert_eq!(
+        FileTypes::new().check(&native_file("a.bin")),
+        FileAcceptance::Accepted
+    );
+}
+
+#[test]
+fn dropped_files_are_filtered_by_type() {
+    let file_types = FileTypes::new().with(FileType::new("text").extension("txt"));
+    assert!(file_types.accepts("/some/dir/notes.TXT", ""));
+    assert!(!file_types.accepts("/some/dir/photo.png", "image/png"));
+    assert!(FileTypes::new().accepts("anything.bin", ""));
+}
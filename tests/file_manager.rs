@@ -0,0 +1,142 @@
+//
+// The demo's file manager: sorting, filtering and selecting dropped files
+// with the mouse
+//
+
+mod common;
+
+use wasm_winit_wgpu::demo::{format_size, FileManager, SortKey};
+use wasm_winit_wgpu::headless::UiDriver;
+use wasm_winit_wgpu::*;
+
+/// Just the file manager window.
+#[derive(Default)]
+struct Files(FileManager);
+impl AppLogic for Files {
+    fn ui(&mut self, ctx: &egui::Context) {
+        self.0.ui(ctx);
+    }
+}
+
+fn driver() -> UiDriver<Files> {
+    let mut files = Files::default();
+    files.0.add(DroppedFile::new("notes.txt", b"some notes"));
+    files
+        .0
+        .add(DroppedFile::new("photo.png", b"\x89PNG\r\n\x1a\n"));
+    files.0.add(DroppedFile::new("Data.bin", &[0; 4096]));
+    common::driver(files)
+}
+
+fn names(files: Vec<&DroppedFile>) -> Vec<&str> {
+    files.into_iter().map(|file| file.name.as_str()).collect()
+}
+
+#[test]
+fn files_can_be_sorted_and_filtered() {
+    let mut driver = driver();
+    driver.get("3 of 3 files shown, 0 selected");
+    let visible = |driver: &UiDriver<Files>| {
+        names(driver.logic().0.visible())
+            .into_iter()
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(visible(&driver), ["notes.txt", "photo.png", "Data.bin"]);
+
+    driver.click("name");
+    assert_eq!(driver.logic().0.sort_key(), (SortKey::Name, true));
+    assert_eq!(visible(&driver), ["Data.bin", "notes.txt", "photo.png"]);
+
+    driver.click("size");
+    driver.click("size");
+    assert_eq!(driver.logic().0.sort_key(), (SortKey::Size, false));
+    assert_eq!(visible(&driver), ["Data.bin", "notes.txt", "photo.png"]);
+    driver.get("4.0 KiB");
+
+    driver.click("type");
+    assert_eq!(visible(&driver), ["Data.bin", "photo.png", "notes.txt"]);
+
+    driver.logic_mut().0.set_filter("IMAGE");
+    driver.run_frames(2);
+    assert_eq!(visible(&driver), ["photo.png"]);
+    driver.get("1 of 3 files shown, 0 selected");
+    driver.logic_mut().0.set_filter("DATA");
+    driver.run_frames(2);
+    assert_eq!(visible(&driver), ["Data.bin"]);
+    driver.logic_mut().0.set_filter("nothing");
+    driver.run_frames(2);
+    assert!(visible(&driver).is_empty());
+    driver.get("0 of 3 files shown, 0 selected");
+}
+
+#[test]
+fn selected_files_show_details_and_can_be_removed() {
+    let mut driver = driver();
+    assert!(driver.find("dropped at").is_none());
+
+    driver.click("photo.png");
+    assert_eq!(names(driver.logic().0.selected()), ["photo.png"]);
+    driver.get("dropped at");
+    driver.get("8 B (8 bytes)");
+    driver.click("hex view");
+    let (_, opened) = driver.logic_mut().0.take_opened().expect("opened");
+    assert_eq!(opened.name, "photo.png");
+    assert!(driver.logic_mut().0.take_opened().is_none());
+    // Asked for again, but removed before it is opened.
+    driver.click("hex view");
+
+    driver.set_modifiers(egui::Modifiers::COMMAND);
+    driver.click("notes.txt");
+    assert_eq!(
+        names(driver.logic().0.selected()),
+        ["notes.txt", "photo.png"]
+    );
+    driver.set_modifiers(egui::Modifiers::NONE);
+
+    driver.click("remove selected");
+    assert_eq!(names(driver.logic().0.visible()), ["Data.bin"]);
+    assert!(driver.logic().0.selected().is_empty());
+    assert!(driver.logic_mut().0.take_opened().is_none());
+    assert_eq!(driver.logic_mut().0.take_removed().len(), 2);
+
+    driver.click("clear");
+    assert!(driver.logic().0.is_empty());
+    assert!(driver.find("Dropped files").is_none());
+    assert_eq!(driver.logic_mut().0.take_removed().len(), 1);
+    assert!(driver.logic_mut().0.take_removed().is_empty());
+}
+
+#[test]
+fn shift_click_selects_a_range() {
+    let mut driver = driver();
+    driver.click("notes.txt");
+    driver.set_modifiers(egui::Modifiers::SHIFT);
+    driver.click("Data.bin");
+    assert_eq!(
+        names(driver.logic().0.selected()),
+        ["notes.txt", "photo.png", "Data.bin"]
+    );
+    driver.get("3 of 3 files shown, 3 selected");
+}
+
+#[test]
+fn shift_click_selects_nothing_more_while_the_focused_file_is_filtered_out() {
+    let mut driver = driver();
+    driver.click("notes.txt");
+    driver.logic_mut().0.set_filter("png");
+    driver.run_frames(2);
+    driver.set_modifiers(egui::Modifiers::SHIFT);
+    driver.click("photo.png");
+    assert_eq!(names(driver.logic().0.selected()), ["photo.png"]);
+    assert_eq!(driver.logic().0.focused().unwrap().name, "photo.png");
+}
+
+#[test]
+fn sizes_are_formatted_for_humans() {
+    assert_eq!(format_size(0), "0 B");
+    assert_eq!(format_size(1023), "1023 B");
+    assert_eq!(format_size(1536), "1.5 KiB");
+    assert_eq!(format_size(5 << 30), "5.0 GiB");
+    assert_eq!(format_size(u64::MAX), "16777216.0 TiB");
+}
@@ -0,0 +1,122 @@
+//
+// Golden-image tests: render full frames (background pass plus egui) on a
+// software adapter and compare them against the images in `tests/golden/`
+//
+// Run with `UPDATE_GOLDEN=1` to (re)write the reference images after an
+// intended visual change.
+//
+
+mod common;
+
+use std::path::{Path, PathBuf};
+use wasm_winit_wgpu::headless::{read_png, write_png, HeadlessApp};
+use wasm_winit_wgpu::*;
+use winit::dpi::PhysicalSize;
+
+/// Maximum difference per color channel before a pixel counts as different;
+/// absorbs rounding differences between software rasterizers.
+const CHANNEL_TOLERANCE: u8 = 4;
+/// Number of differing pixels (eg. anti-aliased edges) still accepted.
+const MAX_DIFFERING_PIXELS: usize = 16;
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{name}.png"))
+}
+
+/// Renders `frames` frames of the demo (with the background frozen) at a
+/// fixed clock of 60 frames per second and returns the last one.
+fn render_demo(
+    gpu: GpuState,
+    size: PhysicalSize<u32>,
+    pixels_per_point: f32,
+    frames: u32,
+) -> Vec<u8> {
+    let mut demo = demo::DemoApp::new();
+    demo.freeze_at(std::time::Duration::from_millis(1250));
+    let mut app = HeadlessApp::new(gpu, demo, size, pixels_per_point);
+    for _ in 0..frames {
+        let raw_input = app.raw_input();
+        app.render_frame(raw_input);
+    }
+    app.read_rgba().expect("failed to read back the frame")
+}
+
+/// Compares `actual` against the golden image `name`; on failure the actual
+/// image and a diff image (differing pixels in red) are written next to the
+/// test binaries.
+fn assert_golden(name: &str, size: PhysicalSize<u32>, actual: &[u8]) {
+    let path = golden_path(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        write_png(&path, size, actual).unwrap();
+        return;
+    }
+    let (expected_size, expected) = read_png(&path).unwrap_or_else(|err| {
+        panic!("no golden image for '{name}' ({err}); run with UPDATE_GOLDEN=1 to create it")
+    });
+    assert_eq!(
+        expected_size, size,
+        "golden image '{name}' has a different size"
+    );
+
+    let mut differing_pixels = 0;
+    let mut diff = Vec::with_capacity(expected.len());
+    for (expected, actual) in expected.chunks_exact(4).zip(actual.chunks_exact(4)) {
+        let differs = expected
+            .iter()
+            .zip(actual)
+            .any(|(e, a)| e.abs_diff(*a) > CHANNEL_TOLERANCE);
+        if differs {
+            differing_pixels += 1;
+            diff.extend([255, 0, 0, 255]);
+        } else {
+            // Faded out expected image, so the differences stand out.
+            let gray = ((expected[0] as u32 + expected[1] as u32 + expected[2] as u32) / 6) as u8;
+            diff.extend([gray, gray, gray, 255]);
+        }
+    }
+    if differing_pixels <= MAX_DIFFERING_PIXELS {
+        return;
+    }
+
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    std::fs::create_dir_all(&out_dir).unwrap();
+    let actual_path = out_dir.join(format!("{name}.actual.png"));
+    let diff_path = out_dir.join(format!("{name}.diff.png"));
+    write_png(&actual_path, size, actual).unwrap();
+    write_png(&diff_path, size, &diff).unwrap();
+    panic!(
+        "'{name}' differs from the golden image in {differing_pixels} pixels\n  expected: {}\n  actual:   {}\n  diff:     {}",
+        path.display(),
+        actual_path.display(),
+        diff_path.display()
+    );
+}
+
+#[test]
+fn background_only() {
+    let gpu = common::software_gpu();
+    let size = PhysicalSize::new(160, 120);
+    // egui windows are laid out, but not shown, on their first frame.
+    let frame = render_demo(gpu, size, 1.0, 1);
+    assert_golden("background_only", size, &frame);
+}
+
+#[test]
+fn demo_window() {
+    let gpu = common::software_gpu();
+    let size = PhysicalSize::new(480, 320);
+    let frame = render_demo(gpu, size, 1.0, 2);
+    assert_golden("demo_window", size, &frame);
+}
+
+#[test]
+fn demo_window_hidpi() {
+    let gpu = common::software_gpu();
+    let size = PhysicalSize::new(960, 640);
+    let frame = render_demo(gpu, size, 2.0, 2);
+    assert_golden("demo_window_hidpi", size, &frame);
+}
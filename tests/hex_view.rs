@@ -0,0 +1,46 @@
+//
+// The demo's hex viewer: editing bytes with the mouse and keyboard
+//
+
+mod common;
+
+use wasm_winit_wgpu::demo::HexViewer;
+use wasm_winit_wgpu::*;
+
+/// Just the hex viewer window.
+struct Viewer(HexViewer);
+impl AppLogic for Viewer {
+    fn ui(&mut self, ctx: &egui::Context) {
+        self.0.ui(ctx);
+    }
+}
+
+#[test]
+fn typing_hex_digits_edits_the_byte_at_the_cursor() {
+    let viewer = HexViewer::new(&DroppedFile::new("test.bin", b"\0\x01\x02\x03"));
+    let mut driver = common::driver(Viewer(viewer));
+
+    // The top left is the first byte.
+    let rows = driver.get("hex view").rect;
+    driver.click_at(rows.min + egui::vec2(2.0, 2.0));
+    driver.type_text("4");
+    driver.step();
+    assert_eq!(driver.logic().0.bytes()[0], 0);
+    driver.type_text("1");
+    driver.step();
+    assert_eq!(&*driver.logic().0.bytes(), b"A\x01\x02\x03");
+    assert_eq!(driver.logic().0.cursor(), 1);
+    driver.step();
+    driver.get("4 B (4), 1 bytes changed");
+
+    driver.key(egui::Key::ArrowRight);
+    driver.step();
+    assert_eq!(driver.logic().0.cursor(), 2);
+
+    driver.set_modifiers(egui::Modifiers::COMMAND);
+    driver.key(egui::Key::Z);
+    driver.step();
+    driver.set_modifiers(egui::Modifiers::NONE);
+    assert_eq!(&*driver.logic().0.bytes(), b"\0\x01\x02\x03");
+    assert!(!driver.logic().0.is_modified());
+}
@@ -0,0 +1,187 @@
+//
+// The demo's image viewer: decoding dropped images and showing them as wgpu
+// textures
+//
+
+mod common;
+
+use image::{ImageEncoder, ImageFormat, RgbaImage};
+use std::time::{Duration, Instant};
+use wasm_winit_wgpu::demo::{is_viewable_image, Channel, ImageViewer, UiState};
+use wasm_winit_wgpu::headless::UiDriver;
+use wasm_winit_wgpu::*;
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+/// 40×20 pixels, red on the left and blue on the right.
+fn red_and_blue() -> RgbaImage {
+    RgbaImage::from_fn(40, 20, |x, _| image::Rgba(if x < 20 { RED } else { BLUE }))
+}
+
+fn encode(image: &RgbaImage, format: ImageFormat) -> Vec<u8> {
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut bytes, format).unwrap();
+    bytes.into_inner()
+}
+
+/// A TIFF header with just a "Make" field.
+const EXIF: &[u8] = b"MM\0\x2a\0\0\0\x08\0\x01\x01\x0f\0\x02\0\0\0\x05\0\0\0\x1a\0\0\0\0Acme\0";
+
+/// Just the image viewer window.
+struct Viewer(ImageViewer);
+impl AppLogic for Viewer {
+    fn ui(&mut self, ctx: &egui::Context) {
+        self.0.ui(ctx);
+    }
+    fn update_textures(&mut self, gpu: &GpuState, textures: &mut NativeTextures<'_>) {
+        self.0.update_textures(gpu, textures);
+    }
+}
+
+#[test]
+fn images_are_shown_in_tiles() {
+    let bytes = encode(&red_and_blue(), ImageFormat::Png);
+    let mut viewer = ImageViewer::new(&DroppedFile::new("image.png", &bytes)).unwrap();
+    viewer.set_max_tile_side(16);
+    let mut driver = common::driver(Viewer(viewer));
+    // Until the window has faded in.
+    driver.run_frames(10);
+    assert_eq!(driver.logic().0.tile_count(), 6);
+    driver.get("40 × 20 px, Png, in 6 texture(s)");
+
+    // The middle of an image pixel, on screen.
+    let view = driver.get("image view").rect;
+    let on_screen = |driver: &UiDriver<Viewer>, x: u32, y: u32| {
+        let viewer = &driver.logic().0;
+        let center = viewer.image_pos(egui::Vec2::ZERO);
+        let offset = egui::pos2(x as f32 + 0.5, y as f32 + 0.5) - center;
+        view.center() + offset * viewer.zoom()
+    };
+    let color_at = |driver: &UiDriver<Viewer>, x: u32, y: u32| {
+        let pos = on_screen(driver, x, y);
+        let rgba = driver.app().read_rgba().unwrap();
+        let i = (pos.y as usize * 800 + pos.x as usize) * 4;
+        [rgba[i], rgba[i + 1], rgba[i + 2], rgba[i + 3]]
+    };
+    // One pixel from each tile.
+    for (x, y, color) in [
+        (5, 5, RED),
+        (17, 3, RED),
+        (35, 3, BLUE),
+        (5, 17, RED),
+        (19, 19, RED),
+        (33, 17, BLUE),
+    ] {
+        assert_eq!(color_at(&driver, x, y), color, "at {x}, {y}");
+    }
+
+    driver.move_pointer(on_screen(&driver, 35, 15));
+    driver.run_frames(2);
+    assert_eq!(driver.logic().0.hovered(), Some([35, 15]));
+    driver.get("pixel 35, 15: #0000ffff (0, 0, 255, 255)");
+
+    driver.click("red");
+    driver.run_frames(2);
+    assert_eq!(driver.logic().0.channel(), Channel::Red);
+    assert_eq!(color_at(&driver, 5, 5), [255, 255, 255, 255]);
+    assert_eq!(color_at(&driver, 35, 3), [0, 0, 0, 255]);
+}
+
+#[test]
+fn dropped_images_are_decoded_in_the_background() {
+    let ctx = egui::Context::default();
+    let mut ui_state = UiState::new();
+    let frame = |ui_state: &mut UiState| {
+        let _ = ctx.run(egui::RawInput::default(), |ctx| ui_state.run_egui(ctx));
+    };
+    let bytes = encode(&red_and_blue(), ImageFormat::Png);
+    ui_state.drop_file(DroppedFile::new("image.png", &bytes));
+    // Which starts decoding it.
+    frame(&mut ui_state);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while ui_state.is_decoding_image() {
+        assert!(Instant::now() < deadline, "timed out");
+        std::thread::sleep(Duration::from_millis(1));
+        frame(&mut ui_state);
+    }
+    let viewer = ui_state.image_viewer().expect("opened once decoded");
+    assert_eq!(viewer.size(), [40, 20]);
+
+    // A broken image leaves the last one open.
+    ui_state.drop_file(DroppedFile::new("broken.png", &bytes[..bytes.len() / 2]));
+    frame(&mut ui_state);
+    while ui_state.is_decoding_image() {
+        assert!(Instant::now() < deadline, "timed out");
+        std::thread::sleep(Duration::from_millis(1));
+        frame(&mut ui_state);
+    }
+    assert_eq!(ui_state.image_viewer().unwrap().name(), "image.png");
+
+    // Removing the files from the file manager closes the viewer and drops
+    // their checksums.
+    while ui_state.integrity().is_busy() {
+        assert!(Instant::now() < deadline, "timed out");
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert!(ui_state.integrity().checksums(0).is_some());
+    ui_state.file_manager_mut().clear();
+    frame(&mut ui_state);
+    assert!(ui_state.image_viewer().is_none());
+    assert!(ui_state.integrity().checksums(0).is_none());
+}
+
+#[test]
+fn images_are_decoded_with_their_exif_metadata() {
+    let image = red_and_blue();
+    let mut png = Vec::new();
+    let mut encoder = image::codecs::png::PngEncoder::new(&mut png);
+    encoder.set_exif_metadata(EXIF.to_vec()).unwrap();
+    encoder
+        .write_image(image.as_raw(), 40, 20, image::ExtendedColorType::Rgba8)
+        .unwrap();
+    let viewer = ImageViewer::new(&DroppedFile::new("photo.png", &png)).unwrap();
+    assert_eq!(viewer.size(), [40, 20]);
+    assert_eq!(viewer.pixel(0, 0), Some(RED));
+    assert_eq!(viewer.pixel(39, 19), Some(BLUE));
+    assert_eq!(viewer.pixel(40, 0), None);
+    let make = viewer.exif().iter().find(|(tag, _)| tag == "Make");
+    assert!(
+        make.is_some_and(|(_, value)| value.contains("Acme")),
+        "{make:?}"
+    );
+
+    // TGA files are only known by their name.
+    let tga = encode(&image, ImageFormat::Tga);
+    assert!(is_viewable_image(&DroppedFile::new("photo.tga", &tga)));
+    assert!(!is_viewable_image(&DroppedFile::new("photo.bin", &tga)));
+    let viewer = ImageViewer::new(&DroppedFile::new("photo.tga", &tga)).unwrap();
+    assert_eq!(viewer.pixel(39, 0), Some(BLUE));
+    assert!(viewer.exif().is_empty());
+
+    for format in [ImageFormat::Bmp, ImageFormat::Gif, ImageFormat::WebP] {
+        let file = DroppedFile::new("image", &encode(&image, format));
+        assert!(is_viewable_image(&file), "{format:?}");
+    }
+    assert!(!is_viewable_image(&DroppedFile::new("notes.txt", b"notes")));
+    let truncated = DroppedFile::new("broken.png", &png[..png.len() / 2]);
+    assert!(ImageViewer::new(&truncated).is_err());
+}
+
+#[test]
+fn zooming_keeps_the_spot_under_the_cursor() {
+    let bytes = encode(&red_and_blue(), ImageFormat::Png);
+    let mut viewer = ImageViewer::new(&DroppedFile::new("image.png", &bytes)).unwrap();
+    // Fitted into the view.
+    assert_eq!(viewer.image_pos(egui::Vec2::ZERO), egui::pos2(20.0, 10.0));
+    let zoom = viewer.zoom();
+
+    let cursor = egui::vec2(-100.0, 50.0);
+    let spot = viewer.image_pos(cursor);
+    viewer.zoom_around(2.0, cursor);
+    assert_eq!(viewer.zoom(), zoom * 2.0);
+    assert!((viewer.image_pos(cursor) - spot).length() < 1e-3);
+
+    viewer.fit();
+    assert_eq!(viewer.zoom(), zoom);
+}
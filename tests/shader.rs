@@ -0,0 +1,302 @@
+//
+// WGSL checking, the shader registry that reloads shaders and rebuilds their
+// pipelines, and the demo's background shader that is drawn instead of the
+// clear color
+//
+
+mod common;
+
+use std::cell::Cell;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use wasm_winit_wgpu::demo::{BackgroundShader, UiState, EXAMPLE_SHADER};
+use wasm_winit_wgpu::headless::HeadlessApp;
+use wasm_winit_wgpu::*;
+
+const GREEN: &str = "
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0, 1.0, 0.0, 1.0);
+}
+";
+
+#[test]
+fn wgsl_errors_have_a_location() {
+    assert!(check_wgsl("fn f() -> f32 { return 1.0; }", Default::default()).is_ok());
+
+    let err = check_wgsl("fn f() -> f32 {\n    return 1.0\n}", Default::default()).unwrap_err();
+    assert_eq!((err.line, err.column), (Some(3), Some(1)));
+    assert!(err.to_string().starts_with("line 3:1: "), "{err}");
+
+    // Parses, but does not validate.
+    let err = check_wgsl("fn f() -> f32 {\n    return 1u;\n}", Default::default()).unwrap_err();
+    assert_eq!(err.line, Some(2));
+    assert!(err.message.contains("'f' is invalid: "), "{}", err.message);
+
+    // Only with a capability the device may lack.
+    let float64 = "fn f() -> f64 { return 1.0lf; }";
+    assert!(check_wgsl(float64, Default::default()).is_err());
+    assert!(check_wgsl(float64, naga::valid::Capabilities::FLOAT64).is_ok());
+    let gpu = common::software_gpu();
+    let supported = gpu.granted_features().contains(wgpu::Features::SHADER_F64);
+    assert_eq!(
+        check_wgsl(float64, gpu.shader_capabilities()).is_ok(),
+        supported
+    );
+
+    let err = ShaderError::new("no location");
+    assert_eq!(err.to_string(), "no location");
+}
+
+/// The background shader, or red where it draws nothing.
+struct Background(BackgroundShader);
+impl AppLogic for Background {
+    fn ui(&mut self, _ctx: &egui::Context) {}
+    fn render(&mut self, gpu: &GpuState, mut target: RenderTarget<'_>) {
+        if !self.0.render(gpu, &mut target) {
+            target.clear(wgpu::Color::RED);
+        }
+    }
+}
+
+fn render(app: &mut HeadlessApp<Background>) -> [u8; 4] {
+    let raw_input = app.raw_input();
+    app.render_frame(raw_input);
+    let rgba = app.read_rgba().unwrap();
+    [rgba[0], rgba[1], rgba[2], rgba[3]]
+}
+
+#[test]
+fn broken_shaders_leave_the_previous_one_running() {
+    let gpu = common::software_gpu();
+    let background = Background(BackgroundShader::new());
+    let mut app = HeadlessApp::new(gpu, background, (64, 32).into(), 1.0);
+    // It would cover all of the 64×32 pixels.
+    app.set_show_surface_settings(false);
+    assert_eq!(render(&mut app), [255, 0, 0, 255], "off at first");
+
+    app.logic_mut().0.set_source("green.wgsl", GREEN);
+    assert_eq!(render(&mut app), [0, 255, 0, 255]);
+    assert!(app.logic().0.error().is_none());
+
+    let broken = GREEN.replace("1.0);", "1.0)");
+    app.logic_mut().0.set_source("green.wgsl", &broken);
+    assert_eq!(render(&mut app), [0, 255, 0, 255]);
+    // Counted in the editor, without the prelude.
+    assert_eq!(app.logic().0.error().unwrap().line, Some(5));
+    assert_eq!(app.logic().0.running_source(), Some(GREEN));
+
+    let two_components = GREEN
+        .replace("vec4<f32>(0.0, 1.0, 0.0, 1.0)", "vec2<f32>(0.0, 1.0)")
+        .replace("-> @location(0) vec4<f32>", "-> @location(0) vec2<f32>");
+    app.logic_mut().0.set_source("green.wgsl", &two_components);
+    assert_eq!(render(&mut app), [0, 255, 0, 255]);
+    let err = app.logic().0.error().unwrap();
+    assert!(err.message.contains("vec4<f32>"), "{err}");
+
+    // The resolution is 64×32.
+    let resolution = GREEN.replace(
+        "vec4<f32>(0.0, 1.0, 0.0, 1.0)",
+        "vec4<f32>(uniforms.resolution / 256.0, 0.0, 1.0)",
+    );
+    app.logic_mut().0.set_source("resolution.wgsl", &resolution);
+    assert_eq!(render(&mut app), [64, 32, 0, 255]);
+    assert_eq!(app.logic().0.inputs().resolution, [64.0, 32.0]);
+
+    app.logic_mut().0.set_source("editor", EXAMPLE_SHADER);
+    render(&mut app);
+    assert!(app.logic().0.error().is_none());
+    assert_eq!(app.logic().0.inputs().frame, 5);
+
+    app.logic_mut().0.set_enabled(false);
+    assert_eq!(render(&mut app), [255, 0, 0, 255]);
+}
+
+#[test]
+fn dropped_wgsl_files_become_the_background() {
+    let mut ui_state = UiState::new();
+    assert!(!ui_state.background().is_enabled());
+    ui_state.drop_file(DroppedFile::new("Plasma.WGSL", GREEN.as_bytes()));
+    assert!(ui_state.background().is_enabled());
+    assert_eq!(ui_state.background().source(), GREEN);
+    ui_state.drop_file(DroppedFile::new("notes.txt", b"not a shader"));
+    assert_eq!(ui_state.background().source(), GREEN);
+}
+
+/// A render and a compute entry point; `FILL` is replaced to change it.
+const PIPELINES: &str = "
+@vertex
+fn vs_main(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(f32(i), 0.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(FILL);
+}
+
+@compute @workgroup_size(1)
+fn cs_main() {}
+";
+
+fn pipelines(fill: &str) -> String {
+    PIPELINES.replace("FILL", fill)
+}
+
+/// Adds a render and a compute pipeline on `shader`, counting how often they
+/// were built.
+fn add_pipelines(
+    registry: &mut ShaderRegistry,
+    gpu: &GpuState,
+    shader: &str,
+) -> (RenderPipelineId, ComputePipelineId, Rc<Cell<u32>>) {
+    let builds = Rc::new(Cell::new(0));
+    let counter = builds.clone();
+    let render = registry.add_render_pipeline(gpu, shader, move |device, module| {
+        counter.set(counter.get() + 1);
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: None,
+            vertex: wgpu::VertexState {
+                module,
+                entry_point: "vs_main",
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::TextureFormat::Rgba8Unorm.into())],
+            }),
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: None,
+        })
+    });
+    let counter = builds.clone();
+    let compute = registry.add_compute_pipeline(gpu, shader, move |device, module| {
+        counter.set(counter.get() + 1);
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: None,
+            layout: None,
+            module,
+            entry_point: "cs_main",
+            compilation_options: Default::default(),
+        })
+    });
+    (render, compute, builds)
+}
+
+#[test]
+fn embedded_shaders_are_compiled_on_update() {
+    let gpu = common::software_gpu();
+    static EMBEDDED: &[(&str, &str)] = &[("broken.wgsl", "fn f( {")];
+    let mut registry = ShaderRegistry::new(ShaderSources::Embedded(EMBEDDED), None);
+    registry.set_source("fill.wgsl", pipelines("1.0"));
+    let (render, compute, builds) = add_pipelines(&mut registry, &gpu, "fill.wgsl");
+    assert!(registry.render_pipeline(render).is_none());
+    assert_eq!(registry.update(&gpu), ["fill.wgsl"]);
+    assert!(registry.render_pipeline(render).is_some());
+    assert!(registry.compute_pipeline(compute).is_some());
+    assert!(registry.module("fill.wgsl").is_some());
+    assert_eq!(builds.get(), 2);
+
+    // Compiled already, so built right away.
+    let (render, _, builds) = add_pipelines(&mut registry, &gpu, "fill.wgsl");
+    assert!(registry.render_pipeline(render).is_some());
+    assert_eq!(builds.get(), 2);
+
+    // Compiles, but has no `cs_main`: the compute pipeline is not replaced.
+    let no_compute = pipelines("0.5").replace("fn cs_main", "fn f");
+    registry.set_source("fill.wgsl", no_compute);
+    assert_eq!(registry.update(&gpu), ["fill.wgsl"]);
+    assert!(registry.error("fill.wgsl").is_some());
+    assert!(registry.compute_pipeline(compute).is_some());
+
+    let (render, _, _) = add_pipelines(&mut registry, &gpu, "broken.wgsl");
+    add_pipelines(&mut registry, &gpu, "missing.wgsl");
+    assert!(registry.update(&gpu).is_empty());
+    assert!(registry.render_pipeline(render).is_none());
+    assert_eq!(registry.error("broken.wgsl").unwrap().line, Some(1));
+    let err = registry.error("missing.wgsl").unwrap();
+    assert_eq!(err.message, "no embedded shader 'missing.wgsl'");
+}
+
+/// Replaced in one go, so the watcher never sees half a file.
+fn write(dir: &Path, name: &str, source: &str) {
+    let temp = dir.join(format!("{name}.tmp"));
+    std::fs::write(&temp, source).unwrap();
+    std::fs::rename(temp, dir.join(name)).unwrap();
+}
+
+/// Calls `update` until `done`, or panics after a while.
+fn update_until(
+    registry: &mut ShaderRegistry,
+    gpu: &GpuState,
+    mut done: impl FnMut(&ShaderRegistry) -> bool,
+) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        registry.update(gpu);
+        if done(registry) {
+            return;
+        }
+        assert!(Instant::now() < deadline, "the change was not picked up");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn changed_shader_files_are_reloaded() {
+    let gpu = common::software_gpu();
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("shader_registry");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("post")).unwrap();
+    write(&dir, "fill.wgsl", &pipelines("1.0"));
+    write(&dir, "post/fill.wgsl", &pipelines("1.0"));
+
+    let mut registry = ShaderRegistry::new(ShaderSources::Directory(dir.clone()), None);
+    let (render, compute, builds) = add_pipelines(&mut registry, &gpu, "fill.wgsl");
+    assert_eq!(registry.update(&gpu), ["fill.wgsl"]);
+    assert!(registry.render_pipeline(render).is_some());
+    assert!(registry.compute_pipeline(compute).is_some());
+    assert_eq!(builds.get(), 2);
+
+    write(&dir, "fill.wgsl", &pipelines("0.5"));
+    update_until(&mut registry, &gpu, |_| builds.get() == 4);
+
+    // The pipelines stay as they were.
+    write(
+        &dir,
+        "fill.wgsl",
+        &pipelines("0.5").replace("0.5);", "0.5)"),
+    );
+    update_until(&mut registry, &gpu, |registry| {
+        registry.error("fill.wgsl").is_some()
+    });
+    assert_eq!(registry.error("fill.wgsl").unwrap().line, Some(10));
+    assert!(registry.render_pipeline(render).is_some());
+    assert_eq!(builds.get(), 4);
+
+    write(&dir, "fill.wgsl", &pipelines("0.25"));
+    update_until(&mut registry, &gpu, |registry| {
+        registry.error("fill.wgsl").is_none()
+    });
+    assert_eq!(builds.get(), 6);
+
+    // In a subdirectory.
+    let (_, _, builds) = add_pipelines(&mut registry, &gpu, "post/fill.wgsl");
+    update_until(&mut registry, &gpu, |_| builds.get() == 2);
+    write(&dir, "post/fill.wgsl", &pipelines("0.5"));
+    update_until(&mut registry, &gpu, |_| builds.get() == 4);
+
+    // Neither a shader in use nor WGSL at all.
+    write(&dir, "other.wgsl", "fn f( {");
+    std::fs::write(dir.join("notes.txt"), "notes").unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(registry.update(&gpu).is_empty());
+    assert!(registry.error("other.wgsl").is_none());
+}
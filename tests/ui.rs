@@ -0,0 +1,151 @@
+//
+// UI tests: drive `UiState::run_egui` with scripted input on a headless
+// adapter and check how it reacts
+//
+
+mod common;
+
+use common::driver;
+use wasm_winit_wgpu::demo::UiState;
+use wasm_winit_wgpu::headless::UiDriver;
+use wasm_winit_wgpu::{demo, AppLogic, DroppedFile, Message};
+
+/// Just the demo's egui window, without the animated background.
+#[derive(Default)]
+struct UiOnly(UiState);
+impl AppLogic for UiOnly {
+    fn ui(&mut self, ctx: &egui::Context) {
+        self.0.run_egui(ctx);
+    }
+}
+
+/// Just a text field, to check typed text arrives.
+#[derive(Default)]
+struct TextField(String);
+impl AppLogic for TextField {
+    fn ui(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.0).hint_text("name"));
+        });
+    }
+}
+
+#[test]
+fn clicking_the_button_counts_clicks() {
+    let mut driver = driver(UiOnly::default());
+    assert!(driver.find("You've clicked the button").is_none());
+
+    driver.click("I dare you!");
+    assert_eq!(driver.logic().0.num_clicks(), 1);
+    driver.get("You've clicked the button 1 time(s)");
+
+    driver.click("Oo-ooh! Now you've done it!");
+    driver.click("Oo-ooh! Now you've done it! Twice!");
+    assert_eq!(driver.logic().0.num_clicks(), 3);
+    driver.get("Oo-ooh! Now you've done it! 3 times already...");
+}
+
+#[test]
+fn toggling_the_checkbox_counts_checks() {
+    let mut driver = driver(UiOnly::default());
+    driver.get("The checkbox is *not* checked");
+
+    driver.click("Some checkbox");
+    assert!(driver.logic().0.is_checked());
+    driver.get("The checkbox *is* checked");
+
+    driver.click("Some checkbox");
+    driver.click("Some checkbox");
+    assert!(driver.logic().0.is_checked());
+    assert_eq!(driver.logic().0.num_checks(), 2);
+    driver.get("The checkbox has been checked 2 time(s)");
+}
+
+#[test]
+fn keyboard_activates_the_focused_widget() {
+    let mut driver = driver(UiOnly::default());
+    driver.focus("Some checkbox");
+    driver.step();
+    driver.key(egui::Key::Space);
+    driver.run_frames(2);
+    assert!(driver.logic().0.is_checked());
+    assert_eq!(driver.logic().0.num_clicks(), 0);
+
+    driver.focus("I dare you!");
+    driver.step();
+    driver.key(egui::Key::Enter);
+    driver.run_frames(2);
+    assert_eq!(driver.logic().0.num_clicks(), 1);
+}
+
+#[test]
+fn typed_text_ends_up_in_the_text_field() {
+    let mut driver = driver(TextField::default());
+    let text_field = driver
+        .widgets()
+        .iter()
+        .find(|widget| widget.role == egui::accesskit::Role::TextInput)
+        .expect("no text field")
+        .id;
+    driver.focus_widget(text_field);
+    driver.step();
+    driver.type_text("Ferris");
+    driver.step();
+    driver.key(egui::Key::Backspace);
+    driver.step();
+    assert_eq!(driver.logic().0, "Ferri");
+}
+
+#[test]
+fn messages_from_the_host_reach_the_demo_and_answers_come_back() {
+    let mut driver = driver(demo::DemoApp::new());
+    let proxy = driver.app().proxy().clone();
+
+    proxy.send_message(Message::new("set_animate_background", false).unwrap());
+    driver.step();
+    assert!(!driver.logic().is_animating());
+    // Unless a shader of your own draws the background.
+    let shader = DroppedFile::new("background.wgsl", demo::EXAMPLE_SHADER.as_bytes());
+    driver.logic_mut().on_file_dropped(shader);
+    assert!(driver.logic().is_animating());
+
+    driver.click("I dare you!");
+    proxy.send_message(Message::new("set_theme", "purple").unwrap());
+    proxy.send_message(Message::new("launch_rockets", ()).unwrap());
+    let posted = driver.app_mut().take_posted();
+    let kinds: Vec<&str> = posted.iter().map(|m| m.kind.as_str()).collect();
+    assert_eq!(kinds, ["clicked", "error", "error"]);
+    assert_eq!(posted[0].payload::<usize>().unwrap(), 1);
+}
+
+#[test]
+fn frames_include_the_framework_windows() {
+    let mut driver = driver(TextField::default());
+    let id = egui::Id::new("Surface settings");
+    let is_shown = |driver: &UiDriver<TextField>| {
+        let layer = egui::LayerId::new(egui::Order::Middle, id);
+        driver
+            .app()
+            .context()
+            .memory(|memory| memory.areas().visible_last_frame(&layer))
+    };
+    assert!(is_shown(&driver));
+
+    // Opened like a click on its title bar would.
+    let ctx = driver.app().context().clone();
+    let mut collapsing = egui::collapsing_header::CollapsingState::load_with_default_open(
+        &ctx,
+        id.with("collapsing"),
+        false,
+    );
+    collapsing.set_open(true);
+    collapsing.store(&ctx);
+    driver.run_frames(10);
+    // No surface, so only the render mode and the session.
+    driver.get("continuous");
+    assert!(driver.find("present mode").is_none());
+
+    driver.app_mut().set_show_surface_settings(false);
+    driver.run_frames(2);
+    assert!(!is_shown(&driver));
+}